@@ -0,0 +1,102 @@
+#[cfg(feature = "ssr")]
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+#[cfg(feature = "ssr")]
+use argon2::Argon2;
+
+#[cfg(feature = "ssr")]
+pub const SESSION_COOKIE: &str = "session";
+#[cfg(feature = "ssr")]
+pub const SESSION_DAYS: u64 = 30;
+#[cfg(feature = "ssr")]
+const MAX_NAME_LEN: usize = 20;
+#[cfg(feature = "ssr")]
+const MIN_USERNAME_LEN: usize = 3;
+#[cfg(feature = "ssr")]
+const MIN_PASSWORD_LEN: usize = 8;
+#[cfg(feature = "ssr")]
+const MAX_PASSWORD_LEN: usize = 128;
+
+// An Argon2id hash in PHC string format, which carries its own salt and
+// parameters
+#[cfg(feature = "ssr")]
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::encode_b64(uuid::Uuid::new_v4().as_bytes())
+        .map_err(|err| format!("Could not hash password: {}", err))?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| format!("Could not hash password: {}", err))
+}
+
+#[cfg(feature = "ssr")]
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+// The random part of a session cookie; the cookie itself is signed
+#[cfg(feature = "ssr")]
+pub fn new_session_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+#[cfg(feature = "ssr")]
+pub fn validate_username(username: &str) -> Result<(), String> {
+    let len = username.chars().count();
+    if !(MIN_USERNAME_LEN..=MAX_NAME_LEN).contains(&len) {
+        return Err(format!(
+            "Usernames are {} to {} characters long",
+            MIN_USERNAME_LEN, MAX_NAME_LEN
+        ));
+    }
+    if !username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err("Usernames may only contain letters, digits, '_' and '-'".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "ssr")]
+pub fn validate_password(password: &str) -> Result<(), String> {
+    let len = password.chars().count();
+    if len < MIN_PASSWORD_LEN {
+        return Err(format!(
+            "Passwords need at least {} characters",
+            MIN_PASSWORD_LEN
+        ));
+    }
+    if len > MAX_PASSWORD_LEN {
+        return Err(format!(
+            "Passwords are limited to {} characters",
+            MAX_PASSWORD_LEN
+        ));
+    }
+    Ok(())
+}
+
+// Display names of accounts and guests alike; a blank name means none
+#[cfg(feature = "ssr")]
+pub fn display_name(name: Option<String>) -> Result<Option<String>, String> {
+    let Some(name) = name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+    else {
+        return Ok(None);
+    };
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(format!("Names are limited to {} characters", MAX_NAME_LEN));
+    }
+    if name.chars().any(char::is_control) {
+        return Err("Names cannot contain control characters".to_string());
+    }
+    Ok(Some(name))
+}
@@ -0,0 +1,101 @@
+#[cfg(feature = "ssr")]
+use crate::shared::{ChatChannel, ChatEntry, ChatRole};
+#[cfg(feature = "ssr")]
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "ssr")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "ssr")]
+const MAX_MESSAGE_LEN: usize = 300;
+#[cfg(feature = "ssr")]
+const MIN_INTERVAL_MS: u64 = 1_000;
+#[cfg(feature = "ssr")]
+const MAX_LOG_LEN: usize = 200;
+
+// Chat history and moderation state for a single room
+#[cfg(feature = "ssr")]
+#[derive(Default)]
+pub struct ChatLog {
+    pub entries: Vec<ChatEntry>,
+    pub muted: HashSet<String>,
+    last_sent: HashMap<String, u64>,
+}
+
+#[cfg(feature = "ssr")]
+impl ChatLog {
+    pub fn post(
+        &mut self,
+        sender_id: &str,
+        role: ChatRole,
+        text: &str,
+    ) -> Result<ChatEntry, String> {
+        let now = current_time_ms();
+        if let Some(last) = self.last_sent.get(sender_id)
+            && now.saturating_sub(*last) < MIN_INTERVAL_MS
+        {
+            return Err("You are sending messages too quickly".to_string());
+        }
+
+        let text = moderate(text)?;
+        let channel = match role {
+            ChatRole::Player(_) => ChatChannel::Players,
+            ChatRole::Spectator => ChatChannel::Spectators,
+        };
+        let entry = ChatEntry {
+            role,
+            channel,
+            text,
+            timestamp: now,
+        };
+
+        self.last_sent.insert(sender_id.to_string(), now);
+        self.entries.push(entry.clone());
+        if self.entries.len() > MAX_LOG_LEN {
+            self.entries.remove(0);
+        }
+        Ok(entry)
+    }
+
+    // The part of the log a reader may see: players never see spectator chat
+    pub fn visible_to(&self, is_player: bool) -> Vec<ChatEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| !is_player || entry.channel == ChatChannel::Players)
+            .cloned()
+            .collect()
+    }
+
+    pub fn set_muted(&mut self, player_id: &str, muted: bool) {
+        if muted {
+            self.muted.insert(player_id.to_string());
+        } else {
+            self.muted.remove(player_id);
+        }
+    }
+}
+
+// Moderation hook every message passes through before it is stored or sent
+#[cfg(feature = "ssr")]
+pub fn moderate(text: &str) -> Result<String, String> {
+    let text: String = text.trim().chars().filter(|c| !c.is_control()).collect();
+
+    if text.is_empty() {
+        return Err("Message is empty".to_string());
+    }
+    if text.chars().count() > MAX_MESSAGE_LEN {
+        return Err(format!(
+            "Message is longer than {} characters",
+            MAX_MESSAGE_LEN
+        ));
+    }
+
+    Ok(text)
+}
+
+#[cfg(feature = "ssr")]
+fn current_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
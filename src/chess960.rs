@@ -0,0 +1,419 @@
+#[cfg(feature = "ssr")]
+use chess::{Board, BoardBuilder, Color, File, Piece, Square, ALL_FILES};
+#[cfg(feature = "ssr")]
+use std::str::FromStr;
+
+#[cfg(feature = "ssr")]
+pub const POSITIONS: u16 = 960;
+// The standard chess setup, RNBQKBNR
+#[cfg(feature = "ssr")]
+pub const STANDARD_POSITION: u16 = 518;
+
+// Knight placements over the five squares left after the bishops and queen,
+// indexed by the standard numbering scheme
+#[cfg(feature = "ssr")]
+const KNIGHT_PLACEMENTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+// White's back rank for a start position number
+#[cfg(feature = "ssr")]
+pub fn back_rank(position: u16) -> [Piece; 8] {
+    let mut rank: [Option<Piece>; 8] = [None; 8];
+    let mut n = position as usize % POSITIONS as usize;
+
+    rank[n % 4 * 2 + 1] = Some(Piece::Bishop);
+    n /= 4;
+    rank[n % 4 * 2] = Some(Piece::Bishop);
+    n /= 4;
+
+    let queen = n % 6;
+    n /= 6;
+    place_on_empty(&mut rank, queen, Piece::Queen);
+
+    let (first, second) = KNIGHT_PLACEMENTS[n];
+    place_on_empty(&mut rank, second, Piece::Knight);
+    place_on_empty(&mut rank, first, Piece::Knight);
+
+    for piece in [Piece::Rook, Piece::King, Piece::Rook] {
+        place_on_empty(&mut rank, 0, piece);
+    }
+
+    rank.map(|piece| piece.unwrap_or(Piece::Pawn))
+}
+
+#[cfg(feature = "ssr")]
+fn place_on_empty(rank: &mut [Option<Piece>; 8], index: usize, piece: Piece) {
+    if let Some(square) = rank.iter_mut().filter(|square| square.is_none()).nth(index) {
+        *square = Some(piece);
+    }
+}
+
+// The start position with no castling rights in the board itself; castling
+// is tracked by `CastlingRights` instead
+#[cfg(feature = "ssr")]
+pub fn start_board(position: u16) -> Board {
+    let pieces: String = back_rank(position)
+        .iter()
+        .map(|piece| match piece {
+            Piece::King => 'k',
+            Piece::Queen => 'q',
+            Piece::Rook => 'r',
+            Piece::Bishop => 'b',
+            Piece::Knight => 'n',
+            Piece::Pawn => 'p',
+        })
+        .collect();
+    let fen = format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w - - 0 1",
+        pieces,
+        pieces.to_uppercase()
+    );
+    Board::from_str(&fen).unwrap_or_default()
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastleSide {
+    King,
+    Queen,
+}
+
+// Files of the rooks each side may still castle with, indexed by color and
+// then by side (king side first)
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CastlingRights {
+    rooks: [[Option<File>; 2]; 2],
+}
+
+#[cfg(feature = "ssr")]
+impl CastlingRights {
+    // Both sides start able to castle with the rooks either side of the king
+    pub fn initial(board: &Board) -> Self {
+        let mut rights = Self::default();
+        for color in [Color::White, Color::Black] {
+            let rank = color.to_my_backrank();
+            let king = board.king_square(color).get_file().to_index();
+            for file in ALL_FILES {
+                let square = Square::make_square(rank, file);
+                if board.piece_on(square) != Some(Piece::Rook)
+                    || board.color_on(square) != Some(color)
+                {
+                    continue;
+                }
+                let side = if file.to_index() > king {
+                    CastleSide::King
+                } else {
+                    CastleSide::Queen
+                };
+                let slot = &mut rights.rooks[color.to_index()][side_index(side)];
+                if slot.is_none() || side == CastleSide::King {
+                    *slot = Some(file);
+                }
+            }
+        }
+        rights
+    }
+
+    // The position after castling if moving the king from `from` to `to` is
+    // a legal castle; the king may be dropped onto its own rook or onto its
+    // destination square
+    pub fn castle(&self, board: &Board, from: Square, to: Square) -> Option<(Board, CastleSide)> {
+        let color = board.side_to_move();
+        let rank = color.to_my_backrank();
+        if from != board.king_square(color) || from.get_rank() != rank || to.get_rank() != rank {
+            return None;
+        }
+
+        let side = [CastleSide::King, CastleSide::Queen]
+            .into_iter()
+            .find(|&side| {
+                let Some(rook_file) = self.rooks[color.to_index()][side_index(side)] else {
+                    return false;
+                };
+                to.get_file() == rook_file || to.get_file() == king_destination(side)
+            })?;
+        let rook_file = self.rooks[color.to_index()][side_index(side)]?;
+        let rook = Square::make_square(rank, rook_file);
+        let king_to = Square::make_square(rank, king_destination(side));
+        let rook_to = Square::make_square(rank, rook_destination(side));
+
+        if board.checkers().popcnt() > 0 {
+            return None;
+        }
+
+        // Everything the king and rook cross must be empty apart from themselves
+        let files = [from, rook, king_to, rook_to].map(|square| square.get_file().to_index());
+        let (low, high) = (*files.iter().min()?, *files.iter().max()?);
+        for file in &ALL_FILES[low..=high] {
+            let square = Square::make_square(rank, *file);
+            if square != from && square != rook && board.piece_on(square).is_some() {
+                return None;
+            }
+        }
+
+        // The king may not pass through or land on an attacked square; the
+        // rook is lifted first so it cannot shield the king
+        let (king_low, king_high) = {
+            let (a, b) = (from.get_file().to_index(), king_to.get_file().to_index());
+            (a.min(b), a.max(b))
+        };
+        for file in &ALL_FILES[king_low..=king_high] {
+            let square = Square::make_square(rank, *file);
+            let mut builder = BoardBuilder::from(board);
+            builder
+                .clear_square(rook)
+                .clear_square(from)
+                .piece(square, Piece::King, color)
+                .en_passant(None);
+            match Board::try_from(&builder) {
+                Ok(probe) if probe.checkers().popcnt() == 0 => {}
+                _ => return None,
+            }
+        }
+
+        let mut builder = BoardBuilder::from(board);
+        builder
+            .clear_square(from)
+            .clear_square(rook)
+            .piece(king_to, Piece::King, color)
+            .piece(rook_to, Piece::Rook, color)
+            .side_to_move(!color)
+            .en_passant(None);
+        Board::try_from(&builder).ok().map(|next| (next, side))
+    }
+
+    // Drops rights lost by a move from `from` to `to` in `board`, played
+    // before the move is applied
+    pub fn update(&mut self, board: &Board, from: Square, to: Square) {
+        let color = board.side_to_move();
+        if board.piece_on(from) == Some(Piece::King) {
+            self.rooks[color.to_index()] = [None, None];
+        }
+
+        for (square, owner) in [(from, color), (to, !color)] {
+            if square.get_rank() != owner.to_my_backrank() {
+                continue;
+            }
+            for slot in &mut self.rooks[owner.to_index()] {
+                if *slot == Some(square.get_file()) {
+                    *slot = None;
+                }
+            }
+        }
+    }
+
+    // Castling field in X-FEN: KQkq for the outermost rooks, the rook's file
+    // letter when an inner rook is the one that may castle
+    pub fn xfen(&self, board: &Board) -> String {
+        let mut field = String::new();
+        for color in [Color::White, Color::Black] {
+            for side in [CastleSide::King, CastleSide::Queen] {
+                let Some(file) = self.rooks[color.to_index()][side_index(side)] else {
+                    continue;
+                };
+                let outermost = !ALL_FILES.iter().any(|other| {
+                    let beyond = match side {
+                        CastleSide::King => other.to_index() > file.to_index(),
+                        CastleSide::Queen => other.to_index() < file.to_index(),
+                    };
+                    let square = Square::make_square(color.to_my_backrank(), *other);
+                    beyond
+                        && board.piece_on(square) == Some(Piece::Rook)
+                        && board.color_on(square) == Some(color)
+                });
+                let letter = match (outermost, side) {
+                    (true, CastleSide::King) => 'K',
+                    (true, CastleSide::Queen) => 'Q',
+                    (false, _) => (b'A' + file.to_index() as u8) as char,
+                };
+                field.push(match color {
+                    Color::White => letter,
+                    Color::Black => letter.to_ascii_lowercase(),
+                });
+            }
+        }
+
+        if field.is_empty() {
+            "-".to_string()
+        } else {
+            field
+        }
+    }
+
+    // Mixed into position hashes so repetitions respect castling rights
+    pub fn hash(&self) -> u64 {
+        self.rooks
+            .iter()
+            .flatten()
+            .fold(0u64, |hash, file| {
+                hash.rotate_left(4) ^ file.map_or(0xf, |f| f.to_index() as u64)
+            })
+            .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub fn castle_san(side: CastleSide, next: &Board) -> String {
+    let mut san = match side {
+        CastleSide::King => "O-O",
+        CastleSide::Queen => "O-O-O",
+    }
+    .to_string();
+    if next.status() == chess::BoardStatus::Checkmate {
+        san.push('#');
+    } else if next.checkers().popcnt() > 0 {
+        san.push('+');
+    }
+    san
+}
+
+#[cfg(feature = "ssr")]
+fn side_index(side: CastleSide) -> usize {
+    match side {
+        CastleSide::King => 0,
+        CastleSide::Queen => 1,
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn king_destination(side: CastleSide) -> File {
+    match side {
+        CastleSide::King => File::G,
+        CastleSide::Queen => File::C,
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn rook_destination(side: CastleSide) -> File {
+    match side {
+        CastleSide::King => File::F,
+        CastleSide::Queen => File::D,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_str(fen).unwrap()
+    }
+
+    fn square(name: &str) -> Square {
+        Square::from_str(name).unwrap()
+    }
+
+    fn rank(position: u16) -> String {
+        back_rank(position)
+            .iter()
+            .map(|&piece| crate::shared::pgn::piece_char(piece))
+            .collect()
+    }
+
+    // The king and rook squares after castling, as "Kc1 Rd1"
+    fn castled(fen: &str, from: &str, to: &str) -> Option<(String, CastleSide)> {
+        let board = board(fen);
+        let rights = CastlingRights::initial(&board);
+        rights
+            .castle(&board, square(from), square(to))
+            .map(|(next, side)| {
+                let king = next.king_square(Color::White);
+                let rooks = next.pieces(Piece::Rook) & next.color_combined(Color::White);
+                let rooks: Vec<String> = rooks.map(|square| format!("R{}", square)).collect();
+                (format!("K{} {}", king, rooks.join(" ")), side)
+            })
+    }
+
+    #[test]
+    fn back_rank_follows_the_standard_numbering() {
+        assert_eq!(rank(STANDARD_POSITION), "RNBQKBNR");
+        assert_eq!(rank(0), "BBQNNRKR");
+        assert_eq!(rank(1), "BQNBNRKR");
+        assert_eq!(rank(959), "RKRNNQBB");
+    }
+
+    #[test]
+    fn king_may_castle_onto_its_rook() {
+        assert_eq!(
+            castled("4k3/8/8/8/8/8/8/RK5R w - - 0 1", "b1", "a1"),
+            Some(("Kc1 Rd1 Rh1".to_string(), CastleSide::Queen))
+        );
+        assert_eq!(
+            castled("4k3/8/8/8/8/8/8/RK5R w - - 0 1", "b1", "c1"),
+            Some(("Kc1 Rd1 Rh1".to_string(), CastleSide::Queen))
+        );
+    }
+
+    #[test]
+    fn king_or_rook_may_already_stand_on_its_destination() {
+        assert_eq!(
+            castled("4k3/8/8/8/8/8/8/R5KR w - - 0 1", "g1", "h1"),
+            Some(("Kg1 Ra1 Rf1".to_string(), CastleSide::King))
+        );
+        assert_eq!(
+            castled("4k3/8/8/8/8/8/8/R3KR2 w - - 0 1", "e1", "g1"),
+            Some(("Kg1 Ra1 Rf1".to_string(), CastleSide::King))
+        );
+        assert_eq!(
+            castled("4k3/8/8/8/8/8/8/R3KR2 w - - 0 1", "e1", "f1"),
+            Some(("Kg1 Ra1 Rf1".to_string(), CastleSide::King))
+        );
+    }
+
+    #[test]
+    fn castling_needs_a_clear_and_safe_path() {
+        let blocked = "4k3/8/8/8/8/8/8/RN2K2R w - - 0 1";
+        assert_eq!(castled(blocked, "e1", "c1"), None);
+        assert_eq!(castled(blocked, "e1", "a1"), None);
+        assert!(castled(blocked, "e1", "g1").is_some());
+
+        // Not through an attacked square, nor out of check
+        assert_eq!(
+            castled("4kr2/8/8/8/8/8/8/R3K2R w - - 0 1", "e1", "g1"),
+            None
+        );
+        assert!(castled("4kr2/8/8/8/8/8/8/R3K2R w - - 0 1", "e1", "c1").is_some());
+        assert_eq!(
+            castled("4r1k1/8/8/8/8/8/8/R3K2R w - - 0 1", "e1", "c1"),
+            None
+        );
+    }
+
+    #[test]
+    fn xfen_names_inner_rooks_by_file() {
+        let standard = start_board(STANDARD_POSITION);
+        assert_eq!(CastlingRights::initial(&standard).xfen(&standard), "KQkq");
+
+        // Another rook has since reached h1, beyond the one that may castle
+        let rights = CastlingRights::initial(&board("4k3/8/8/8/8/8/8/1R2K1R1 w - - 0 1"));
+        let later = board("4k3/8/8/8/8/8/8/1R2K1RR w - - 0 1");
+        assert_eq!(rights.xfen(&later), "GQ");
+
+        let mut moved = CastlingRights::initial(&standard);
+        moved.update(&standard, square("e1"), square("e2"));
+        assert_eq!(moved.xfen(&standard), "kq");
+    }
+
+    #[test]
+    fn castle_san_marks_checks() {
+        let quiet = board("4k3/8/8/8/8/8/8/4K2R w - - 0 1");
+        let checking = board("5k2/8/8/8/8/8/8/4K2R w - - 0 1");
+        let mating = board("5k2/6p1/4Q3/8/8/8/8/4K2R w - - 0 1");
+        for (board, expected) in [(quiet, "O-O"), (checking, "O-O+"), (mating, "O-O#")] {
+            let rights = CastlingRights::initial(&board);
+            let (next, side) = rights.castle(&board, square("e1"), square("g1")).unwrap();
+            assert_eq!(castle_san(side, &next), expected);
+        }
+    }
+}
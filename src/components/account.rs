@@ -0,0 +1,160 @@
+use super::http::{get_text, post_json};
+use crate::shared::{Account, CategoryRating, LoginRequest, RatingProfile, RegisterRequest};
+use leptos::prelude::*;
+
+// Log in, register or log out; guests can keep playing without an account
+#[component]
+pub fn AccountPanel(
+    account: ReadSignal<Option<Account>>,
+    set_account: WriteSignal<Option<Account>>,
+) -> impl IntoView {
+    let (username, set_username) = signal(String::new());
+    let (password, set_password) = signal(String::new());
+    let (display_name, set_display_name) = signal(String::new());
+    let (registering, set_registering) = signal(false);
+    let (error, set_error) = signal::<Option<String>>(None);
+    let (ratings, set_ratings) = signal::<Vec<CategoryRating>>(Vec::new());
+
+    Effect::new(move |_| {
+        get_text("/api/me", move |response| {
+            if let Ok(me) = response.and_then(|body| parse_account(&body)) {
+                set_account.set(Some(me));
+            }
+        });
+    });
+
+    Effect::new(move |_| {
+        let Some(me) = account.get() else {
+            set_ratings.set(Vec::new());
+            return;
+        };
+        get_text(&format!("/api/ratings/{}", me.username), move |response| {
+            if let Ok(profile) = response.and_then(|body| {
+                serde_json::from_str::<RatingProfile>(&body).map_err(|err| err.to_string())
+            }) {
+                set_ratings.set(profile.ratings);
+            }
+        });
+    });
+
+    let on_account = move |response: Result<String, String>| match response
+        .and_then(|body| parse_account(&body))
+    {
+        Ok(me) => {
+            set_account.set(Some(me));
+            set_password.set(String::new());
+            set_error.set(None);
+        }
+        Err(message) => set_error.set(Some(message)),
+    };
+
+    let submit = move |_| {
+        let body = if registering.get() {
+            let display_name = display_name.get();
+            serde_json::to_string(&RegisterRequest {
+                username: username.get(),
+                password: password.get(),
+                display_name: (!display_name.trim().is_empty()).then_some(display_name),
+            })
+        } else {
+            serde_json::to_string(&LoginRequest {
+                username: username.get(),
+                password: password.get(),
+            })
+        };
+        let url = if registering.get() {
+            "/api/register"
+        } else {
+            "/api/login"
+        };
+        if let Ok(body) = body {
+            post_json(url, &body, on_account);
+        }
+    };
+
+    let logout = move |_| {
+        post_json("/api/logout", "", move |_| set_account.set(None));
+    };
+
+    view! {
+        <div class="account-panel">
+            {move || match account.get() {
+                Some(me) => {
+                    view! {
+                        <span>{format!("Signed in as {}", me.name())}</span>
+                        <span class="account-ratings">
+                            {move || {
+                                ratings
+                                    .get()
+                                    .iter()
+                                    .map(|r| {
+                                        format!(
+                                            "{} {}{}",
+                                            r.category.label(),
+                                            r.rating,
+                                            if r.provisional { "?" } else { "" },
+                                        )
+                                    })
+                                    .collect::<Vec<_>>()
+                                    .join(" · ")
+                            }}
+                        </span>
+                        <button class="btn btn-secondary" on:click=logout>
+                            "Log out"
+                        </button>
+                    }
+                        .into_any()
+                }
+                None => {
+                    view! {
+                        <input
+                            type="text"
+                            placeholder="Username"
+                            prop:value=username
+                            on:input=move |ev| set_username.set(event_target_value(&ev))
+                        />
+                        <input
+                            type="password"
+                            placeholder="Password"
+                            prop:value=password
+                            on:input=move |ev| set_password.set(event_target_value(&ev))
+                        />
+                        <Show when=move || registering.get()>
+                            <input
+                                type="text"
+                                maxlength="20"
+                                placeholder="Display name (optional)"
+                                prop:value=display_name
+                                on:input=move |ev| set_display_name.set(event_target_value(&ev))
+                            />
+                        </Show>
+                        <button class="btn btn-secondary" on:click=submit>
+                            {move || if registering.get() { "Register" } else { "Log in" }}
+                        </button>
+                        <a
+                            class="account-switch"
+                            on:click=move |_| {
+                                set_registering.update(|r| *r = !*r);
+                                set_error.set(None);
+                            }
+                        >
+                            {move || {
+                                if registering.get() {
+                                    "Have an account? Log in"
+                                } else {
+                                    "New here? Register"
+                                }
+                            }}
+                        </a>
+                    }
+                        .into_any()
+                }
+            }}
+            {move || error.get().map(|message| view! { <p class="error">{message}</p> })}
+        </div>
+    }
+}
+
+pub fn parse_account(body: &str) -> Result<Account, String> {
+    serde_json::from_str(body).map_err(|err| err.to_string())
+}
@@ -0,0 +1,281 @@
+use super::http::get_text;
+use crate::components::Board;
+use crate::shared::pgn::{parse_pgn, side_to_move, PgnGame};
+use crate::shared::*;
+use leptos::prelude::*;
+use leptos_router::hooks::use_query_map;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{FileReader, HtmlInputElement};
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum MoveItem {
+    Move { index: usize, label: String },
+    Comment(String),
+    OpenVariation,
+    CloseVariation,
+}
+
+#[component]
+pub fn Analysis() -> impl IntoView {
+    let (pgn_text, set_pgn_text) = signal(String::new());
+    let (games, set_games) = signal::<Vec<PgnGame>>(Vec::new());
+    let (selected_game, set_selected_game) = signal(0usize);
+    let (current, set_current) = signal::<Option<usize>>(None);
+    let (error, set_error) = signal::<Option<String>>(None);
+
+    let (fen, set_fen) = signal(pgn::STARTING_FEN.to_string());
+    let (current_turn, set_current_turn) = signal(PlayerColor::White);
+    let (orientation, set_orientation) = signal::<Option<PlayerColor>>(None);
+    let (game_over, _) = signal(true);
+
+    Effect::new(move |_| {
+        let position = games.with(|games| {
+            games
+                .get(selected_game.get())
+                .map(|game| match current.get() {
+                    Some(index) => game.tree.nodes[index].fen.clone(),
+                    None => game.start_fen.clone(),
+                })
+        });
+        let position = position.unwrap_or_else(|| pgn::STARTING_FEN.to_string());
+        set_current_turn.set(side_to_move(&position));
+        set_fen.set(position);
+    });
+
+    let load = move |text: String| match parse_pgn(&text) {
+        Ok(parsed) => {
+            set_games.set(parsed);
+            set_selected_game.set(0);
+            set_current.set(None);
+            set_error.set(None);
+        }
+        Err(message) => set_error.set(Some(message)),
+    };
+
+    // Replays an archived game, as linked from the games list
+    let query = use_query_map();
+    Effect::new(move |_| {
+        let Some(id) = query.with(|q| q.get("game")) else {
+            return;
+        };
+        let url = format!(
+            "/api/games/{}.pgn",
+            String::from(js_sys::encode_uri_component(&id))
+        );
+        get_text(&url, move |response| match response {
+            Ok(text) => {
+                set_pgn_text.set(text.clone());
+                load(text);
+            }
+            Err(message) => set_error.set(Some(message)),
+        });
+    });
+
+    let on_upload = move |ev: leptos::ev::Event| {
+        let input: HtmlInputElement = event_target(&ev);
+        let Some(file) = input.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        let Ok(reader) = FileReader::new() else {
+            return;
+        };
+
+        let reader_clone = reader.clone();
+        let onload = Closure::wrap(Box::new(move || {
+            if let Some(text) = reader_clone.result().ok().and_then(|r| r.as_string()) {
+                set_pgn_text.set(text.clone());
+                load(text);
+            }
+        }) as Box<dyn FnMut()>);
+        reader.set_onload(Some(onload.as_ref().unchecked_ref()));
+        onload.forget();
+        let _ = reader.read_as_text(&file);
+    };
+
+    let with_game = move |f: &dyn Fn(&PgnGame) -> Option<usize>| {
+        games.with(|games| games.get(selected_game.get_untracked()).and_then(f))
+    };
+    let go_start = move |_| set_current.set(None);
+    let go_back = move |_| {
+        let current_node = current.get_untracked();
+        set_current.set(with_game(&|game| {
+            current_node.and_then(|index| game.tree.nodes[index].parent)
+        }));
+    };
+    let go_forward = move |_| {
+        let current_node = current.get_untracked();
+        if let Some(next) = with_game(&|game| game.tree.children(current_node).first().copied()) {
+            set_current.set(Some(next));
+        }
+    };
+    let go_end = move |_| {
+        let mut node = current.get_untracked();
+        while let Some(next) = with_game(&|game| game.tree.children(node).first().copied()) {
+            node = Some(next);
+        }
+        set_current.set(node);
+    };
+    let flip = move |_| {
+        set_orientation.update(|o| {
+            *o = match o {
+                Some(_) => None,
+                None => Some(PlayerColor::Black),
+            }
+        })
+    };
+
+    let items = move || {
+        games.with(|games| {
+            games
+                .get(selected_game.get())
+                .map(move_items)
+                .unwrap_or_default()
+                .into_iter()
+                .enumerate()
+                .collect::<Vec<_>>()
+        })
+    };
+
+    let game_title = |game: &PgnGame| {
+        format!(
+            "{} vs {} ({})",
+            game.tag("White").unwrap_or("?"),
+            game.tag("Black").unwrap_or("?"),
+            game.result
+        )
+    };
+
+    view! {
+        <div class="analysis">
+            <div class="analysis-input">
+                <h2>"Analysis"</h2>
+                <textarea
+                    rows="8"
+                    placeholder="Paste a PGN here..."
+                    prop:value=pgn_text
+                    on:input=move |ev| set_pgn_text.set(event_target_value(&ev))
+                ></textarea>
+                <div class="analysis-actions">
+                    <button class="btn btn-secondary" on:click=move |_| load(pgn_text.get())>
+                        "Load PGN"
+                    </button>
+                    <input type="file" accept=".pgn,text/plain" on:change=on_upload />
+                </div>
+                {move || error.get().map(|message| view! { <p class="error">{message}</p> })}
+                <Show when=move || games.with(|games| games.len() > 1)>
+                    <select on:change=move |ev| {
+                        set_selected_game.set(event_target_value(&ev).parse().unwrap_or(0));
+                        set_current.set(None);
+                    }>
+                        {move || {
+                            games
+                                .with(|games| {
+                                    games
+                                        .iter()
+                                        .enumerate()
+                                        .map(|(i, game)| {
+                                            view! {
+                                                <option value=i.to_string()>
+                                                    {format!("{}. {}", i + 1, game_title(game))}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()
+                                })
+                        }}
+                    </select>
+                </Show>
+            </div>
+
+            <div class="game-board-wrapper">
+                <Board
+                    fen=fen
+                    player_color=orientation
+                    current_turn=current_turn
+                    on_move=|_: String, _: String, _: Option<String>| {}
+                    game_over=game_over
+                />
+                <div class="analysis-controls">
+                    <button class="btn btn-secondary" on:click=go_start>"|<"</button>
+                    <button class="btn btn-secondary" on:click=go_back>"<"</button>
+                    <button class="btn btn-secondary" on:click=go_forward>">"</button>
+                    <button class="btn btn-secondary" on:click=go_end>">|"</button>
+                    <button class="btn btn-secondary" on:click=flip>"Flip"</button>
+                </div>
+            </div>
+
+            <div class="move-history">
+                <h3>"Moves"</h3>
+                {move || {
+                    games.with(|games| {
+                        games
+                            .get(selected_game.get())
+                            .and_then(|game| game.comment.clone())
+                            .map(|comment| view! { <p class="move-comment">{comment}</p> })
+                    })
+                }}
+                <div class="moves-list">
+                    <For
+                        each=items
+                        key=|(position, item)| (*position, item.clone())
+                        children=move |(_, item): (usize, MoveItem)| match item {
+                            MoveItem::Move { index, label } => {
+                                view! {
+                                    <span
+                                        class="move-item"
+                                        class:current=move || current.get() == Some(index)
+                                        on:click=move |_| set_current.set(Some(index))
+                                    >
+                                        {label}
+                                    </span>
+                                }
+                                    .into_any()
+                            }
+                            MoveItem::Comment(text) => {
+                                view! { <span class="move-comment">{text}</span> }.into_any()
+                            }
+                            MoveItem::OpenVariation => {
+                                view! { <span class="variation">"("</span> }.into_any()
+                            }
+                            MoveItem::CloseVariation => {
+                                view! { <span class="variation">")"</span> }.into_any()
+                            }
+                        }
+                    />
+                </div>
+            </div>
+        </div>
+    }
+}
+
+// Flattens the move tree into PGN order: each move, then the variations that
+// branch off in its place, then the rest of the line
+fn move_items(game: &PgnGame) -> Vec<MoveItem> {
+    let mut items = Vec::new();
+    push_line(game, &game.tree.roots, true, &mut items);
+    items
+}
+
+fn push_line(game: &PgnGame, siblings: &[usize], mut with_number: bool, items: &mut Vec<MoveItem>) {
+    let mut siblings = siblings.to_vec();
+    while let Some((&main, variations)) = siblings.split_first() {
+        let node = &game.tree.nodes[main];
+        items.push(MoveItem::Move {
+            index: main,
+            label: game.move_label(main, with_number),
+        });
+        if let Some(comment) = &node.comment {
+            items.push(MoveItem::Comment(comment.clone()));
+        }
+
+        for &variation in variations {
+            items.push(MoveItem::OpenVariation);
+            push_line(game, &[variation], true, items);
+            items.push(MoveItem::CloseVariation);
+        }
+
+        with_number = !variations.is_empty() || node.comment.is_some();
+        siblings = node.children.clone();
+    }
+}
@@ -1,4 +1,4 @@
-use crate::shared::PlayerColor;
+use crate::shared::{PlayerColor, Pockets, POCKET_PIECES};
 use leptos::prelude::*;
 use std::collections::HashMap;
 
@@ -9,6 +9,10 @@ pub fn Board<F>(
     current_turn: ReadSignal<PlayerColor>,
     on_move: F,
     game_over: ReadSignal<bool>,
+    // Crazyhouse pockets; pieces are dropped as moves from "N@" style squares
+    #[prop(optional)] pockets: Option<ReadSignal<Option<Pockets>>>,
+    // Antichess lets pawns promote to a king
+    #[prop(optional)] king_promotion: Option<Signal<bool>>,
 ) -> impl IntoView
 where
     F: Fn(String, String, Option<String>) + 'static + Copy + Send,
@@ -30,9 +34,30 @@ where
             return;
         }
 
+        let is_own_piece = move |piece: &String| {
+            let is_white_piece = piece.chars().next().unwrap().is_uppercase();
+            match turn {
+                PlayerColor::White => is_white_piece,
+                PlayerColor::Black => !is_white_piece,
+            }
+        };
+
         if let Some(from) = selected_square.get() {
+            let current_board = board_state.get();
+            let own_target = current_board.get(&square).filter(|p| is_own_piece(p));
+            // Dropping the king on one of its own rooks castles (as in Chess960);
+            // any other own piece just takes over the selection
+            let castling = current_board
+                .get(&from)
+                .is_some_and(|p| p.eq_ignore_ascii_case("k"))
+                && own_target.is_some_and(|p| p.eq_ignore_ascii_case("r"));
+            if from != square && own_target.is_some() && !castling {
+                set_selected_square.set(Some(square));
+                return;
+            }
+
             if from != square {
-                if is_promotion_move(&from, &square, &board_state.get(), turn) {
+                if is_promotion_move(&from, &square, &current_board, turn) {
                     set_promotion_state.set(Some((from.clone(), square.clone())));
                 } else {
                     on_move(from.clone(), square.clone(), None);
@@ -41,16 +66,8 @@ where
             set_selected_square.set(None);
         } else {
             let current_board = board_state.get();
-            if let Some(piece) = current_board.get(&square) {
-                let is_white_piece = piece.chars().next().unwrap().is_uppercase();
-                let is_my_piece = match turn {
-                    PlayerColor::White => is_white_piece,
-                    PlayerColor::Black => !is_white_piece,
-                };
-
-                if is_my_piece {
-                    set_selected_square.set(Some(square));
-                }
+            if current_board.get(&square).is_some_and(is_own_piece) {
+                set_selected_square.set(Some(square));
             }
         }
     };
@@ -64,8 +81,52 @@ where
 
     let is_flipped = move || player_color.get() == Some(PlayerColor::Black);
 
+    let select_drop = move |color: PlayerColor, drop: String| {
+        if !game_over.get() && player_color.get() == Some(color) && current_turn.get() == color {
+            set_selected_square.set(Some(drop));
+        }
+    };
+
+    let pocket_view = move |color: PlayerColor| {
+        let pocket = *pockets.and_then(|pockets| pockets.get())?.get(color);
+        let pieces = POCKET_PIECES
+            .iter()
+            .filter(|&&letter| pocket.count(letter) > 0)
+            .map(|&letter| {
+                let drop = format!("{}@", letter.to_ascii_uppercase());
+                let drop_click = drop.clone();
+                let drop_selected = drop.clone();
+                let symbol = match color {
+                    PlayerColor::White => letter.to_ascii_uppercase(),
+                    PlayerColor::Black => letter,
+                }
+                .to_string();
+
+                view! {
+                    <div
+                        class="pocket-piece"
+                        class:selected=move || selected_square.get() == Some(drop_selected.clone())
+                        draggable="true"
+                        on:click=move |_| select_drop(color, drop_click.clone())
+                        on:dragstart=move |ev| {
+                            if let Some(data) = ev.data_transfer() {
+                                let _ = data.set_data("text/plain", &drop);
+                            }
+                            select_drop(color, drop.clone());
+                        }
+                    >
+                        {get_piece_symbol(&symbol)}
+                        <span class="pocket-count">{pocket.count(letter)}</span>
+                    </div>
+                }
+            })
+            .collect_view();
+        Some(view! { <div class="pocket">{pieces}</div> })
+    };
+
     view! {
         <div class="board-container">
+            {move || pocket_view(if is_flipped() { PlayerColor::White } else { PlayerColor::Black })}
             <div class="chess-board" class:flipped=is_flipped>
                 <For
                     each=move || {
@@ -83,6 +144,7 @@ where
                         let sq_click = square_name.clone();
                         let sq_selected = square_name.clone();
                         let sq_memo = square_name.clone();
+                        let sq_drop = square_name.clone();
 
                         let piece = Memo::new(move |_| {
                             board_state.get().get(&sq_memo).cloned()
@@ -101,6 +163,11 @@ where
                                 class:dark=!is_light
                                 class:selected=is_selected
                                 on:click=move |_| handle_square_click(sq_click.clone())
+                                on:dragover=|ev| ev.prevent_default()
+                                on:drop=move |ev| {
+                                    ev.prevent_default();
+                                    handle_square_click(sq_drop.clone());
+                                }
                             >
                                 {move || piece.get().map(|p| {
                                     view! { <div class="piece">{get_piece_symbol(&p)}</div> }
@@ -110,6 +177,7 @@ where
                     }
                 />
             </div>
+            {move || pocket_view(if is_flipped() { PlayerColor::Black } else { PlayerColor::White })}
 
             {move || promotion_state.get().map(|_| {
                 let color = current_turn.get();
@@ -142,6 +210,14 @@ where
                                 >
                                     {if color == PlayerColor::White { "♘" } else { "♞" }}
                                 </button>
+                                {move || king_promotion.is_some_and(|allowed| allowed.get()).then(|| view! {
+                                    <button
+                                        class="promotion-button"
+                                        on:click=move |_| handle_promotion("k")
+                                    >
+                                        {if color == PlayerColor::White { "♔" } else { "♚" }}
+                                    </button>
+                                })}
                             </div>
                         </div>
                     </div>
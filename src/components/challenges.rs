@@ -0,0 +1,224 @@
+use super::game::{socket_url, store_rejoin_token};
+use crate::shared::{
+    ClientMessage, ColorPreference, DirectChallenge, ServerMessage, TimeControl, Variant,
+};
+use leptos::prelude::*;
+use leptos_router::hooks::use_navigate;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{MessageEvent, WebSocket};
+
+// Challenges a player by username with the options chosen above, and lists
+// the challenges waiting for an answer. Every open tab of the recipient shows
+// an incoming challenge until one of them answers it
+#[component]
+pub fn Challenges(
+    time_control: Signal<Option<TimeControl>>,
+    variant: Signal<Variant>,
+    rated: Signal<bool>,
+) -> impl IntoView {
+    let (ws, set_ws) = signal_local::<Option<WebSocket>>(None);
+    let (to_user, set_to_user) = signal(String::new());
+    let (color, set_color) = signal(ColorPreference::Random);
+    let (incoming, set_incoming) = signal::<Vec<DirectChallenge>>(Vec::new());
+    let (outgoing, set_outgoing) = signal::<Option<DirectChallenge>>(None);
+    let (status, set_status) = signal::<Option<String>>(None);
+    let (error, set_error) = signal::<Option<String>>(None);
+    let navigate = use_navigate();
+
+    Effect::new(move |_| {
+        let Ok(socket) = WebSocket::new(&socket_url()) else {
+            set_error.set(Some("Failed to connect".to_string()));
+            return;
+        };
+
+        // Drops a challenge that has been answered, expired or withdrawn
+        let settle = move |id: &str, message: String| {
+            set_incoming.update(|incoming| incoming.retain(|c| c.id != id));
+            if outgoing.get_untracked().is_some_and(|c| c.id == id) {
+                set_outgoing.set(None);
+                set_status.set(Some(message));
+            }
+        };
+
+        let navigate = navigate.clone();
+        let socket_clone = socket.clone();
+        let onmessage = Closure::wrap(Box::new(move |e: MessageEvent| {
+            let Some(msg) = e
+                .data()
+                .as_string()
+                .and_then(|txt| serde_json::from_str::<ServerMessage>(&txt).ok())
+            else {
+                return;
+            };
+            match msg {
+                ServerMessage::ChallengeSent { challenge } => {
+                    set_status.set(Some(format!("Waiting for {}...", challenge.to)));
+                    set_outgoing.set(Some(challenge));
+                    set_error.set(None);
+                }
+                ServerMessage::ChallengeReceived { challenge } => {
+                    set_incoming.update(|incoming| incoming.push(challenge));
+                }
+                ServerMessage::ChallengeAccepted { id } => {
+                    settle(&id, "Challenge accepted".to_string());
+                }
+                ServerMessage::ChallengeDeclined { id, reason } => {
+                    let message = match reason {
+                        Some(reason) => format!("Challenge declined: {}", reason),
+                        None => "Challenge declined".to_string(),
+                    };
+                    settle(&id, message);
+                }
+                ServerMessage::ChallengeCancelled { id } => {
+                    settle(&id, "Challenge cancelled".to_string());
+                }
+                ServerMessage::ChallengeExpired { id } => {
+                    settle(&id, "Nobody answered the challenge".to_string());
+                }
+                ServerMessage::MatchFound {
+                    room_code,
+                    rejoin_token,
+                    ..
+                } => {
+                    store_rejoin_token(&room_code, &rejoin_token);
+                    socket_clone.set_onclose(None);
+                    let _ = socket_clone.close();
+                    navigate(&format!("/game/{}", room_code), Default::default());
+                }
+                ServerMessage::Error { message } => set_error.set(Some(message)),
+                _ => {}
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        onmessage.forget();
+
+        let onclose = Closure::wrap(Box::new(move || {
+            set_incoming.set(Vec::new());
+            set_outgoing.set(None);
+            set_status.set(None);
+            set_error.set(Some("Lost the connection for challenges".to_string()));
+        }) as Box<dyn FnMut()>);
+        socket.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+        onclose.forget();
+
+        set_ws.set(Some(socket));
+    });
+
+    on_cleanup(move || {
+        if let Some(socket) = ws.get_untracked() {
+            socket.set_onclose(None);
+            let _ = socket.close();
+        }
+    });
+
+    let send = move |msg: ClientMessage| {
+        if let (Some(socket), Ok(json)) = (ws.get_untracked(), serde_json::to_string(&msg)) {
+            let _ = socket.send_with_str(&json);
+        }
+    };
+
+    let challenge = move |_| {
+        set_status.set(None);
+        send(ClientMessage::Challenge {
+            to_user: to_user.get_untracked(),
+            time_control: time_control.get_untracked(),
+            variant: variant.get_untracked(),
+            color: color.get_untracked(),
+            rated: rated.get_untracked(),
+        });
+    };
+
+    let cancel = move |_| {
+        if let Some(challenge) = outgoing.get_untracked() {
+            send(ClientMessage::CancelChallenge { id: challenge.id });
+        }
+    };
+
+    view! {
+        <div class="challenges">
+            <div class="challenge-form">
+                <input
+                    type="text"
+                    placeholder="Challenge a player"
+                    prop:value=to_user
+                    on:input=move |ev| set_to_user.set(event_target_value(&ev))
+                />
+                <select on:change=move |ev| {
+                    set_color
+                        .set(
+                            match event_target_value(&ev).as_str() {
+                                "white" => ColorPreference::White,
+                                "black" => ColorPreference::Black,
+                                _ => ColorPreference::Random,
+                            },
+                        )
+                }>
+                    <option value="random" selected>"Random color"</option>
+                    <option value="white">"White"</option>
+                    <option value="black">"Black"</option>
+                </select>
+                <Show
+                    when=move || outgoing.get().is_some()
+                    fallback=move || {
+                        view! {
+                            <button class="btn btn-secondary" on:click=challenge>
+                                "Challenge"
+                            </button>
+                        }
+                    }
+                >
+                    <button class="btn btn-secondary" on:click=cancel>
+                        "Cancel"
+                    </button>
+                </Show>
+            </div>
+            {move || status.get().map(|message| view! { <p class="seek-status">{message}</p> })}
+            {move || error.get().map(|message| view! { <p class="error">{message}</p> })}
+            <For
+                each=move || incoming.get()
+                key=|challenge| challenge.id.clone()
+                children=move |challenge| {
+                    let accept_id = challenge.id.clone();
+                    let decline_id = challenge.id.clone();
+                    view! {
+                        <div class="incoming-challenge">
+                            <span>
+                                {format!(
+                                    "{} challenges you: {}, {}, {}",
+                                    challenge.from,
+                                    challenge
+                                        .time_control
+                                        .map_or("Untimed".to_string(), |tc| tc.label()),
+                                    challenge.variant.label(),
+                                    if challenge.rated { "rated" } else { "casual" },
+                                )}
+                            </span>
+                            <button
+                                class="btn btn-secondary"
+                                on:click=move |_| {
+                                    send(ClientMessage::AcceptChallenge {
+                                        id: accept_id.clone(),
+                                    })
+                                }
+                            >
+                                "Accept"
+                            </button>
+                            <button
+                                class="btn btn-secondary"
+                                on:click=move |_| {
+                                    send(ClientMessage::DeclineChallenge {
+                                        id: decline_id.clone(),
+                                        reason: None,
+                                    })
+                                }
+                            >
+                                "Decline"
+                            </button>
+                        </div>
+                    }
+                }
+            />
+        </div>
+    }
+}
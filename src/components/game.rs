@@ -29,124 +29,118 @@ pub fn Game() -> impl IntoView {
     };
 
     let action = move || query.with(|q| q.get("action").unwrap_or_else(|| "join".to_string()));
+    let rated = move || query.with(|q| q.get("rated").is_some_and(|v| v == "true"));
+    let room_config = move || {
+        query.with(|q| RoomConfig {
+            allow_takebacks: q.get("takebacks").map(|v| v != "false").unwrap_or(true),
+            time_control: parse_time_control(
+                &q.get("tc").unwrap_or_else(|| "10+0".to_string()),
+                q.get("delay").as_deref(),
+                q.get("delay_secs")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(0),
+            ),
+            start_fen: q.get("fen").filter(|fen| !fen.trim().is_empty()),
+            variant: parse_variant(
+                q.get("variant").as_deref().unwrap_or_default(),
+                q.get("position").and_then(|v| v.parse().ok()),
+            ),
+        })
+    };
 
     let (ws, set_ws) = signal_local::<Option<WebSocket>>(None);
     let (player_color, set_player_color) = signal::<Option<PlayerColor>>(None);
     let (fen, set_fen) =
         signal("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string());
     let (moves, set_moves) = signal::<Vec<MoveRecord>>(Vec::new());
-    let (white_time, set_white_time) = signal(600u64);
-    let (black_time, set_black_time) = signal(600u64);
+    let (white_time, set_white_time) = signal(0u64);
+    let (black_time, set_black_time) = signal(0u64);
     let (current_turn, set_current_turn) = signal(PlayerColor::White);
     let (status, set_status) = signal("Connecting...".to_string());
     let (game_over, set_game_over) = signal(false);
+    let (draw_offer, set_draw_offer) = signal::<Option<PlayerColor>>(None);
+    let (takeback_request, set_takeback_request) = signal::<Option<PlayerColor>>(None);
+    let (config, set_config) = signal(RoomConfig::default());
+    let (opponent_away, set_opponent_away) = signal::<Option<u64>>(None);
+    let (opponent_abandoned, set_opponent_abandoned) = signal(false);
+    let (spectators, set_spectators) = signal(0usize);
+    let (rematch_offer, set_rematch_offer) = signal::<Option<PlayerColor>>(None);
+    let (match_score, set_match_score) = signal::<Option<MatchScore>>(None);
+    let (checks, set_checks) = signal::<Option<CheckCount>>(None);
+    let (pockets, set_pockets) = signal::<Option<Pockets>>(None);
+    let (chat, set_chat) = signal::<Vec<ChatEntry>>(Vec::new());
+    let (chat_input, set_chat_input) = signal(String::new());
+    let (chat_muted, set_chat_muted) = signal(false);
 
     set_interval(
         move || {
-            if game_over.get() {
+            if let Some(secs) = opponent_away.get() {
+                set_opponent_away.set(Some(secs.saturating_sub(1)));
+            }
+
+            if game_over.get() || config.get().time_control.is_none() {
                 return;
             }
 
+            // The server declares the flag fall; the local clock only counts down
             let turn = current_turn.get();
             match turn {
                 PlayerColor::White => {
-                    let current = white_time.get();
-                    if current > 0 {
-                        set_white_time.set(current - 1);
-                    } else if current == 0 {
-                        set_game_over.set(true);
-                        set_status.set("Black wins on time!".to_string());
-                    }
+                    set_white_time.update(|t| *t = t.saturating_sub(1));
                 }
                 PlayerColor::Black => {
-                    let current = black_time.get();
-                    if current > 0 {
-                        set_black_time.set(current - 1);
-                    } else if current == 0 {
-                        set_game_over.set(true);
-                        set_status.set("White wins on time!".to_string());
-                    }
+                    set_black_time.update(|t| *t = t.saturating_sub(1));
                 }
             }
         },
         std::time::Duration::from_secs(1),
     );
 
-    Effect::new(move |_| {
-        let protocol = if web_sys::window()
-            .and_then(|w| w.location().protocol().ok())
-            .map(|p| p == "https:")
-            .unwrap_or(false)
-        {
-            "wss"
-        } else {
-            "ws"
-        };
-
-        let host = web_sys::window()
-            .and_then(|w| w.location().host().ok())
-            .unwrap_or_else(|| "localhost:3000".to_string());
-
-        let ws_url = format!("{}://{}/ws", protocol, host);
-        match WebSocket::new(&ws_url) {
-            Ok(socket) => {
-                let socket_clone = socket.clone();
-                let room_code_val = room_code();
-                let action_val = action();
-
-                let onopen = Closure::wrap(Box::new(move || {
-                    let msg = if action_val == "create" {
-                        ClientMessage::CreateRoom {
-                            room_code: room_code_val.clone(),
-                        }
-                    } else {
-                        ClientMessage::JoinRoom {
-                            room_code: room_code_val.clone(),
-                        }
-                    };
-
-                    if let Ok(json) = serde_json::to_string(&msg) {
-                        let _ = socket_clone.send_with_str(&json);
-                    }
-                }) as Box<dyn FnMut()>);
-                socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
-                onopen.forget();
-
-                let onmessage = Closure::wrap(Box::new(move |e: MessageEvent| {
-                    if let Some(txt) = e.data().as_string() {
-                        if let Ok(msg) = serde_json::from_str::<ServerMessage>(&txt) {
-                            handle_server_message(
-                                msg,
-                                set_player_color,
-                                set_fen,
-                                moves,
-                                set_moves,
-                                set_white_time,
-                                set_black_time,
-                                set_current_turn,
-                                set_status,
-                                set_game_over,
-                            );
-                        }
-                    }
-                }) as Box<dyn FnMut(MessageEvent)>);
-                socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
-                onmessage.forget();
+    let signals = GameSignals {
+        set_ws,
+        player_color,
+        set_player_color,
+        set_fen,
+        moves,
+        set_moves,
+        set_white_time,
+        set_black_time,
+        set_current_turn,
+        set_status,
+        game_over,
+        set_game_over,
+        set_draw_offer,
+        set_takeback_request,
+        set_config,
+        set_opponent_away,
+        set_opponent_abandoned,
+        set_spectators,
+        set_rematch_offer,
+        set_match_score,
+        set_checks,
+        set_pockets,
+        set_chat,
+    };
 
-                set_ws.set(Some(socket));
-            }
-            Err(_) => {
-                set_status.set("Failed to connect".to_string());
-            }
-        }
+    Effect::new(move |_| {
+        connect(room_code(), action(), room_config(), rated(), signals);
     });
 
+    let king_promotion = Signal::derive(move || config.get().variant == Variant::Antichess);
+
     let make_move = move |from: String, to: String, promotion: Option<String>| {
         if let Some(socket) = ws.get() {
-            let msg = ClientMessage::MakeMove {
-                from,
-                to,
-                promotion,
+            // The board reports Crazyhouse drops as moves from "N@"
+            let msg = match from.strip_suffix('@') {
+                Some(piece) => ClientMessage::DropPiece {
+                    piece: piece.to_lowercase(),
+                    square: to,
+                },
+                None => ClientMessage::MakeMove {
+                    from,
+                    to,
+                    promotion,
+                },
             };
             if let Ok(json) = serde_json::to_string(&msg) {
                 let _ = socket.send_with_str(&json);
@@ -154,23 +148,109 @@ pub fn Game() -> impl IntoView {
         }
     };
 
-    let resign = move |_| {
+    let send_message = move |msg: ClientMessage| {
         if let Some(socket) = ws.get() {
-            let msg = ClientMessage::Resign;
             if let Ok(json) = serde_json::to_string(&msg) {
                 let _ = socket.send_with_str(&json);
             }
         }
     };
 
+    let resign = move |_| send_message(ClientMessage::Resign);
+    let claim_draw = move |_| send_message(ClientMessage::ClaimDraw);
+    let claim_victory = move |_| send_message(ClientMessage::ClaimVictory);
+    let offer_draw = move |_| send_message(ClientMessage::OfferDraw);
+    let accept_draw = move |_| send_message(ClientMessage::AcceptDraw);
+    let decline_draw = move |_| send_message(ClientMessage::DeclineDraw);
+
+    let request_takeback = move |_| send_message(ClientMessage::RequestTakeback);
+    let accept_takeback = move |_| send_message(ClientMessage::AcceptTakeback);
+    let decline_takeback = move |_| send_message(ClientMessage::DeclineTakeback);
+
+    let offer_rematch = move |_| send_message(ClientMessage::OfferRematch);
+    let accept_rematch = move |_| send_message(ClientMessage::AcceptRematch);
+    let decline_rematch = move |_| send_message(ClientMessage::DeclineRematch);
+
+    let rematch_by_opponent =
+        move || rematch_offer.get().is_some() && rematch_offer.get() != player_color.get();
+
+    let send_chat = move || {
+        let text = chat_input.get();
+        if !text.trim().is_empty() {
+            send_message(ClientMessage::Chat { text });
+            set_chat_input.set(String::new());
+        }
+    };
+    let toggle_mute = move |ev| {
+        let muted = event_target_checked(&ev);
+        set_chat_muted.set(muted);
+        send_message(ClientMessage::MuteChat { muted });
+    };
+
+    let takeback_by_opponent =
+        move || takeback_request.get().is_some() && takeback_request.get() != player_color.get();
+
+    let clock_text = move |seconds: u64| {
+        if config.get().time_control.is_some() {
+            format_time(seconds)
+        } else {
+            "--:--".to_string()
+        }
+    };
+    let white_clock = move || clock_text(white_time.get());
+    let black_clock = move || clock_text(black_time.get());
+
+    let offered_by_opponent =
+        move || draw_offer.get().is_some() && draw_offer.get() != player_color.get();
+
     view! {
         <div class="game-container">
             <div class="game-info">
                 <h2>"Room: " {room_code}</h2>
                 <p class="status">{status}</p>
+                <p class="time-control">
+                    "Time control: "
+                    {move || {
+                        config
+                            .get()
+                            .time_control
+                            .map(|tc| tc.label())
+                            .unwrap_or_else(|| "Untimed".to_string())
+                    }}
+                </p>
+                <Show when=move || config.get().variant != Variant::Standard>
+                    <p class="time-control">"Variant: " {move || config.get().variant.label()}</p>
+                </Show>
+                {move || config.get().start_fen.map(|fen| {
+                    view! { <p class="time-control">"Starting position: " {fen}</p> }
+                })}
+                {move || opponent_away.get().map(|secs| {
+                    view! {
+                        <p class="presence">
+                            {format!("Opponent disconnected, you may claim the game in {}s", secs)}
+                        </p>
+                    }
+                })}
                 {move || player_color.get().map(|c| {
                     view! { <p class="player-color">"You are: " {format!("{:?}", c)}</p> }
                 })}
+                {move || checks.get().map(|checks| {
+                    view! {
+                        <p class="check-count">
+                            {format!("Checks: White {} - {} Black", checks.white, checks.black)}
+                        </p>
+                    }
+                })}
+                {move || match_score.get().map(|score| {
+                    view! {
+                        <p class="match-score">
+                            {format!("Match: White {} - {} Black", score.white, score.black)}
+                        </p>
+                    }
+                })}
+                <Show when=move || spectators.get() > 0>
+                    <p class="spectators">"Spectators: " {spectators}</p>
+                </Show>
             </div>
 
             <div class="game-board-wrapper">
@@ -179,7 +259,7 @@ pub fn Game() -> impl IntoView {
                     if is_black {
                         Either::Left(view! {
                             <div class="timer timer-white">
-                                "White: " {move || format_time(white_time.get())}
+                                "White: " {white_clock}
                             </div>
                             <Board
                                 fen=fen
@@ -187,15 +267,17 @@ pub fn Game() -> impl IntoView {
                                 current_turn=current_turn
                                 on_move=make_move
                                 game_over=game_over
+                                pockets=pockets
+                                king_promotion=king_promotion
                             />
                             <div class="timer timer-black">
-                                "Black: " {move || format_time(black_time.get())}
+                                "Black: " {black_clock}
                             </div>
                         })
                     } else {
                         Either::Right(view! {
                             <div class="timer timer-black">
-                                "Black: " {move || format_time(black_time.get())}
+                                "Black: " {black_clock}
                             </div>
                             <Board
                                 fen=fen
@@ -203,27 +285,128 @@ pub fn Game() -> impl IntoView {
                                 current_turn=current_turn
                                 on_move=make_move
                                 game_over=game_over
+                                pockets=pockets
+                                king_promotion=king_promotion
                             />
                             <div class="timer timer-white">
-                                "White: " {move || format_time(white_time.get())}
+                                "White: " {white_clock}
                             </div>
                         })
                     }
                 }}
             </div>
 
-            <div class="game-controls">
-                <button
-                    class="btn btn-danger"
-                    on:click=resign
-                    disabled=move || game_over.get()
-                >
-                    "Resign"
-                </button>
-            </div>
+            <Show when=move || player_color.get().is_some()>
+                <div class="game-controls">
+                    <button
+                        class="btn btn-danger"
+                        on:click=resign
+                        disabled=move || game_over.get()
+                    >
+                        "Resign"
+                    </button>
+                    <button
+                        class="btn btn-secondary"
+                        on:click=claim_draw
+                        disabled=move || game_over.get()
+                    >
+                        "Claim Draw"
+                    </button>
+                    <button
+                        class="btn btn-secondary"
+                        on:click=offer_draw
+                        disabled=move || game_over.get() || draw_offer.get().is_some()
+                    >
+                        {move || {
+                            if draw_offer.get().is_some() && !offered_by_opponent() {
+                                "Draw Offered"
+                            } else {
+                                "Offer Draw"
+                            }
+                        }}
+                    </button>
+                    <Show when=move || config.get().allow_takebacks>
+                        <button
+                            class="btn btn-secondary"
+                            on:click=request_takeback
+                            disabled=move || game_over.get() || takeback_request.get().is_some()
+                        >
+                            "Request Takeback"
+                        </button>
+                    </Show>
+                    <Show when=move || opponent_abandoned.get() && !game_over.get()>
+                        <div class="offer-panel">
+                            <p>"Your opponent left the game"</p>
+                            <button class="btn btn-secondary" on:click=claim_victory>
+                                "Claim Victory"
+                            </button>
+                            <button class="btn btn-secondary" on:click=claim_draw>
+                                "Claim Draw"
+                            </button>
+                        </div>
+                    </Show>
+                    <Show when=move || takeback_by_opponent() && !game_over.get()>
+                        <div class="offer-panel">
+                            <p>"Your opponent asks to take back a move"</p>
+                            <button class="btn btn-secondary" on:click=accept_takeback>
+                                "Accept"
+                            </button>
+                            <button class="btn btn-danger" on:click=decline_takeback>
+                                "Decline"
+                            </button>
+                        </div>
+                    </Show>
+                    <Show when=move || game_over.get() && !rematch_by_opponent()>
+                        <button
+                            class="btn btn-secondary"
+                            on:click=offer_rematch
+                            disabled=move || rematch_offer.get().is_some()
+                        >
+                            {move || {
+                                if rematch_offer.get().is_some() {
+                                    "Rematch Offered"
+                                } else {
+                                    "Rematch"
+                                }
+                            }}
+                        </button>
+                    </Show>
+                    <Show when=move || rematch_by_opponent() && game_over.get()>
+                        <div class="offer-panel">
+                            <p>"Your opponent wants a rematch"</p>
+                            <button class="btn btn-secondary" on:click=accept_rematch>
+                                "Accept"
+                            </button>
+                            <button class="btn btn-danger" on:click=decline_rematch>
+                                "Decline"
+                            </button>
+                        </div>
+                    </Show>
+                    <Show when=move || offered_by_opponent() && !game_over.get()>
+                        <div class="offer-panel">
+                            <p>"Your opponent offers a draw"</p>
+                            <button class="btn btn-secondary" on:click=accept_draw>
+                                "Accept"
+                            </button>
+                            <button class="btn btn-danger" on:click=decline_draw>
+                                "Decline"
+                            </button>
+                        </div>
+                    </Show>
+                </div>
+            </Show>
 
             <div class="move-history">
-                <h3>"Move History"</h3>
+                <div class="move-history-header">
+                    <h3>"Move History"</h3>
+                    <a
+                        class="btn btn-secondary"
+                        href=move || format!("/api/games/{}.pgn", room_code())
+                        download=""
+                    >
+                        "Download PGN"
+                    </a>
+                </div>
                 <div class="moves-list">
                     <For
                         each=move || moves.get()
@@ -234,12 +417,62 @@ pub fn Game() -> impl IntoView {
                     />
                 </div>
             </div>
+
+            <div class="chat">
+                <h3>"Chat"</h3>
+                <label class="chat-mute">
+                    <input type="checkbox" prop:checked=chat_muted on:change=toggle_mute />
+                    "Mute chat"
+                </label>
+                <div class="chat-messages">
+                    <For
+                        each=move || chat.get().into_iter().enumerate().collect::<Vec<_>>()
+                        key=|(index, _)| *index
+                        children=move |(_, entry): (usize, ChatEntry)| {
+                            let author = match entry.role {
+                                ChatRole::Player(color) => format!("{:?}", color),
+                                ChatRole::Spectator => "Spectator".to_string(),
+                            };
+                            view! {
+                                <div class="chat-message">
+                                    <span class="chat-author">{author} ": "</span>
+                                    {entry.text}
+                                </div>
+                            }
+                        }
+                    />
+                </div>
+                <div class="chat-input">
+                    <input
+                        type="text"
+                        maxlength="300"
+                        placeholder="Say something..."
+                        prop:value=chat_input
+                        disabled=chat_muted
+                        on:input=move |ev| set_chat_input.set(event_target_value(&ev))
+                        on:keydown=move |ev: web_sys::KeyboardEvent| {
+                            if ev.key() == "Enter" {
+                                send_chat();
+                            }
+                        }
+                    />
+                    <button
+                        class="btn btn-secondary"
+                        on:click=move |_| send_chat()
+                        disabled=chat_muted
+                    >
+                        "Send"
+                    </button>
+                </div>
+            </div>
         </div>
     }
 }
 
-fn handle_server_message(
-    msg: ServerMessage,
+#[derive(Clone, Copy)]
+struct GameSignals {
+    set_ws: WriteSignal<Option<WebSocket>, LocalStorage>,
+    player_color: ReadSignal<Option<PlayerColor>>,
     set_player_color: WriteSignal<Option<PlayerColor>>,
     set_fen: WriteSignal<String>,
     moves: ReadSignal<Vec<MoveRecord>>,
@@ -248,25 +481,243 @@ fn handle_server_message(
     set_black_time: WriteSignal<u64>,
     set_current_turn: WriteSignal<PlayerColor>,
     set_status: WriteSignal<String>,
+    game_over: ReadSignal<bool>,
     set_game_over: WriteSignal<bool>,
+    set_draw_offer: WriteSignal<Option<PlayerColor>>,
+    set_takeback_request: WriteSignal<Option<PlayerColor>>,
+    set_config: WriteSignal<RoomConfig>,
+    set_opponent_away: WriteSignal<Option<u64>>,
+    set_opponent_abandoned: WriteSignal<bool>,
+    set_spectators: WriteSignal<usize>,
+    set_rematch_offer: WriteSignal<Option<PlayerColor>>,
+    set_match_score: WriteSignal<Option<MatchScore>>,
+    set_checks: WriteSignal<Option<CheckCount>>,
+    set_pockets: WriteSignal<Option<Pockets>>,
+    set_chat: WriteSignal<Vec<ChatEntry>>,
+}
+
+// Opens the game socket; if it drops mid-game we reconnect and reclaim the
+// seat with the rejoin token handed out when the room was created or joined
+fn connect(
+    room_code: String,
+    action: String,
+    config: RoomConfig,
+    rated: bool,
+    signals: GameSignals,
 ) {
+    match WebSocket::new(&socket_url()) {
+        Ok(socket) => {
+            let socket_clone = socket.clone();
+            let room_code_val = room_code.clone();
+            let action_val = action.clone();
+            let config_val = config.clone();
+
+            let onopen = Closure::wrap(Box::new(move || {
+                let msg = if let Some(token) = load_rejoin_token(&room_code_val) {
+                    ClientMessage::Rejoin {
+                        room_code: room_code_val.clone(),
+                        token,
+                    }
+                } else if action_val == "spectate" {
+                    ClientMessage::Spectate {
+                        room_code: room_code_val.clone(),
+                    }
+                } else if action_val == "create" {
+                    ClientMessage::CreateRoom {
+                        room_code: room_code_val.clone(),
+                        config: config_val.clone(),
+                        name: load_player_name(),
+                        rated,
+                    }
+                } else {
+                    ClientMessage::JoinRoom {
+                        room_code: room_code_val.clone(),
+                        name: load_player_name(),
+                    }
+                };
+
+                if let Ok(json) = serde_json::to_string(&msg) {
+                    let _ = socket_clone.send_with_str(&json);
+                }
+            }) as Box<dyn FnMut()>);
+            socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+            onopen.forget();
+
+            let onmessage = Closure::wrap(Box::new(move |e: MessageEvent| {
+                if let Some(txt) = e.data().as_string() {
+                    if let Ok(msg) = serde_json::from_str::<ServerMessage>(&txt) {
+                        handle_server_message(msg, signals);
+                    }
+                }
+            }) as Box<dyn FnMut(MessageEvent)>);
+            socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+            onmessage.forget();
+
+            let onclose = Closure::wrap(Box::new(move || {
+                if signals.game_over.get_untracked() {
+                    return;
+                }
+                signals
+                    .set_status
+                    .set("Connection lost, reconnecting...".to_string());
+
+                let room_code = room_code.clone();
+                let action = action.clone();
+                let config = config.clone();
+                set_timeout(
+                    move || connect(room_code, action, config, rated, signals),
+                    std::time::Duration::from_secs(2),
+                );
+            }) as Box<dyn FnMut()>);
+            socket.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+            onclose.forget();
+
+            signals.set_ws.set(Some(socket));
+        }
+        Err(_) => {
+            signals.set_status.set("Failed to connect".to_string());
+        }
+    }
+}
+
+pub(crate) fn socket_url() -> String {
+    let protocol = if web_sys::window()
+        .and_then(|w| w.location().protocol().ok())
+        .map(|p| p == "https:")
+        .unwrap_or(false)
+    {
+        "wss"
+    } else {
+        "ws"
+    };
+
+    let host = web_sys::window()
+        .and_then(|w| w.location().host().ok())
+        .unwrap_or_else(|| "localhost:3000".to_string());
+
+    format!("{}://{}/ws", protocol, host)
+}
+
+fn rejoin_key(room_code: &str) -> String {
+    format!("chess-rejoin-{}", room_code)
+}
+
+fn load_rejoin_token(room_code: &str) -> Option<String> {
+    let storage = web_sys::window()?.local_storage().ok()??;
+    storage.get_item(&rejoin_key(room_code)).ok()?
+}
+
+pub(crate) fn store_rejoin_token(room_code: &str, token: &str) {
+    if let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
+        let _ = storage.set_item(&rejoin_key(room_code), token);
+    }
+}
+
+const PLAYER_NAME_KEY: &str = "chess-player-name";
+
+pub(crate) fn load_player_name() -> Option<String> {
+    let storage = web_sys::window()?.local_storage().ok()??;
+    storage
+        .get_item(PLAYER_NAME_KEY)
+        .ok()?
+        .filter(|name| !name.trim().is_empty())
+}
+
+pub(crate) fn store_player_name(name: &str) {
+    if let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
+        let _ = storage.set_item(PLAYER_NAME_KEY, name);
+    }
+}
+
+fn handle_server_message(msg: ServerMessage, signals: GameSignals) {
+    let GameSignals {
+        set_player_color,
+        set_fen,
+        moves,
+        set_moves,
+        set_white_time,
+        set_black_time,
+        set_current_turn,
+        set_status,
+        set_game_over,
+        set_draw_offer,
+        set_takeback_request,
+        set_config,
+        player_color,
+        set_opponent_away,
+        set_opponent_abandoned,
+        set_spectators,
+        set_rematch_offer,
+        set_match_score,
+        set_checks,
+        set_pockets,
+        set_chat,
+        ..
+    } = signals;
+
     match msg {
-        ServerMessage::RoomCreated { player_color, .. } => {
+        ServerMessage::RoomCreated {
+            room_code,
+            player_color,
+            config,
+            rejoin_token,
+        } => {
+            store_rejoin_token(&room_code, &rejoin_token);
             set_player_color.set(Some(player_color));
+            let base_secs = config
+                .time_control
+                .as_ref()
+                .map_or(0, |tc| tc.base_ms / 1000);
+            set_white_time.set(base_secs);
+            set_black_time.set(base_secs);
+            set_config.set(config);
             set_status.set("Waiting for opponent...".to_string());
         }
-        ServerMessage::RoomJoined { player_color, .. } => {
+        ServerMessage::RoomJoined {
+            room_code,
+            player_color,
+            config,
+            rejoin_token,
+        } => {
+            store_rejoin_token(&room_code, &rejoin_token);
             set_player_color.set(Some(player_color));
+            let base_secs = config
+                .time_control
+                .as_ref()
+                .map_or(0, |tc| tc.base_ms / 1000);
+            set_white_time.set(base_secs);
+            set_black_time.set(base_secs);
+            set_config.set(config);
             set_status.set("Game starting...".to_string());
         }
+        ServerMessage::Rejoined {
+            player_color,
+            config,
+            ..
+        } => {
+            set_player_color.set(Some(player_color));
+            set_config.set(config);
+            set_status.set("Reconnected".to_string());
+        }
+        ServerMessage::Spectating { config, .. } => {
+            set_config.set(config);
+            set_status.set("Spectating".to_string());
+        }
+        ServerMessage::SpectatorCount { count } => {
+            set_spectators.set(count);
+        }
         ServerMessage::GameState {
             fen,
             moves: game_moves,
             white_time,
             black_time,
             current_turn,
+            checks,
+            pockets,
         } => {
             set_fen.set(fen);
+            set_checks.set(checks);
+            set_pockets.set(pockets);
             set_moves.set(game_moves);
             set_white_time.set(white_time / 1000);
             set_black_time.set(black_time / 1000);
@@ -274,6 +725,7 @@ fn handle_server_message(
             set_status.set("Game in progress".to_string());
         }
         ServerMessage::MoveMade { fen, san, .. } => {
+            set_takeback_request.set(None);
             set_fen.set(fen.clone());
             let parts: Vec<&str> = fen.split_whitespace().collect();
             if parts.len() >= 2 {
@@ -296,11 +748,97 @@ fn handle_server_message(
             set_status.set("Opponent joined! Game started.".to_string());
         }
         ServerMessage::OpponentLeft => {
+            set_rematch_offer.set(None);
             set_status.set("Opponent left the game".to_string());
         }
-        ServerMessage::GameOver { result } => {
+        ServerMessage::OpponentDisconnected { color, grace_ms } => {
+            if player_color.get_untracked() != Some(color) {
+                set_opponent_away.set(Some(grace_ms / 1000));
+            }
+        }
+        ServerMessage::OpponentReconnected { color } => {
+            if player_color.get_untracked() != Some(color) {
+                set_opponent_away.set(None);
+                set_opponent_abandoned.set(false);
+                set_status.set("Opponent reconnected".to_string());
+            }
+        }
+        ServerMessage::OpponentAbandoned { color } => {
+            if player_color.get_untracked() != Some(color) {
+                set_opponent_away.set(None);
+                set_opponent_abandoned.set(true);
+            }
+        }
+        ServerMessage::DrawOffered { by } => {
+            set_draw_offer.set(Some(by));
+        }
+        ServerMessage::DrawDeclined { by } => {
+            set_draw_offer.set(None);
+            set_status.set(format!("{:?} declined the draw offer", by));
+        }
+        ServerMessage::DrawOfferExpired => {
+            set_draw_offer.set(None);
+        }
+        ServerMessage::TakebackRequested { by } => {
+            set_takeback_request.set(Some(by));
+        }
+        ServerMessage::TakebackDeclined { by } => {
+            set_takeback_request.set(None);
+            set_status.set(format!("{:?} declined the takeback", by));
+        }
+        ServerMessage::TakebackAccepted => {
+            set_takeback_request.set(None);
+            set_draw_offer.set(None);
+        }
+        ServerMessage::GameOver {
+            result,
+            rating_changes,
+        } => {
+            set_opponent_away.set(None);
+            set_opponent_abandoned.set(false);
+            set_takeback_request.set(None);
+            set_draw_offer.set(None);
             set_game_over.set(true);
-            set_status.set(format!("Game Over: {:?}", result));
+            let mut status = format!("Game Over: {:?}", result);
+            if let Some(changes) = rating_changes {
+                // Players see their own change, spectators both
+                let shown = match player_color.get_untracked() {
+                    Some(PlayerColor::White) => format_rating_change(&changes.white),
+                    Some(PlayerColor::Black) => format_rating_change(&changes.black),
+                    None => format!(
+                        "White {}, Black {}",
+                        format_rating_change(&changes.white),
+                        format_rating_change(&changes.black)
+                    ),
+                };
+                status.push_str(&format!(" ({} {})", changes.category.label(), shown));
+            }
+            set_status.set(status);
+        }
+        ServerMessage::RematchOffered { by } => {
+            set_rematch_offer.set(Some(by));
+        }
+        ServerMessage::RematchDeclined { by } => {
+            set_rematch_offer.set(None);
+            set_status.set(format!("{:?} declined the rematch", by));
+        }
+        ServerMessage::RematchStarted { score } => {
+            // The server swaps the seats, so each player now has the other color
+            set_player_color.update(|color| *color = color.map(PlayerColor::opponent));
+            set_moves.set(Vec::new());
+            set_game_over.set(false);
+            set_draw_offer.set(None);
+            set_takeback_request.set(None);
+            set_rematch_offer.set(None);
+            set_opponent_abandoned.set(false);
+            set_match_score.set(Some(score));
+            set_status.set("Rematch started".to_string());
+        }
+        ServerMessage::ChatMessage { message } => {
+            set_chat.update(|chat| chat.push(message));
+        }
+        ServerMessage::ChatHistory { messages } => {
+            set_chat.set(messages);
         }
         ServerMessage::Error { message } => {
             set_status.set(format!("Error: {}", message));
@@ -309,6 +847,69 @@ fn handle_server_message(
     }
 }
 
+// "1512 (+12)", with a "?" after provisional ratings
+fn format_rating_change(change: &RatingChange) -> String {
+    format!(
+        "{}{} ({:+})",
+        change.rating,
+        if change.provisional { "?" } else { "" },
+        change.delta
+    )
+}
+
+// Variants by the names the home page puts in the URL
+pub(crate) fn parse_variant(name: &str, chess960_position: Option<u16>) -> Variant {
+    match name {
+        "chess960" => Variant::Chess960 {
+            position: chess960_position,
+        },
+        "kingofthehill" => Variant::KingOfTheHill,
+        "threecheck" => Variant::ThreeCheck,
+        "racingkings" => Variant::RacingKings,
+        "crazyhouse" => Variant::Crazyhouse,
+        "atomic" => Variant::Atomic,
+        "antichess" => Variant::Antichess,
+        _ => Variant::Standard,
+    }
+}
+
+pub(crate) fn parse_time_control(
+    tc: &str,
+    delay: Option<&str>,
+    delay_secs: u64,
+) -> Option<TimeControl> {
+    if tc == "untimed" {
+        return None;
+    }
+
+    // "minutes+increment" or "minutes/moves+minutes" for a two-stage control
+    let (base, rest) = tc.split_once(['+', '/']).unwrap_or((tc, "0"));
+    let base_ms = base.parse::<u64>().unwrap_or(10) * 60_000;
+    let mut time_control = match rest.split_once('+') {
+        Some((moves, added)) if tc.contains('/') => {
+            let mut time_control = TimeControl::new(base_ms, 0);
+            time_control.stages.push(TimeControlStage {
+                after_moves: moves.parse().unwrap_or(40),
+                added_ms: added.parse::<u64>().unwrap_or(0) * 60_000,
+            });
+            time_control
+        }
+        _ => TimeControl::new(base_ms, rest.parse::<u64>().unwrap_or(0) * 1000),
+    };
+
+    time_control.delay = match delay {
+        Some("simple") => Some(Delay::Simple {
+            ms: delay_secs * 1000,
+        }),
+        Some("bronstein") => Some(Delay::Bronstein {
+            ms: delay_secs * 1000,
+        }),
+        _ => None,
+    };
+
+    Some(time_control)
+}
+
 fn format_time(seconds: u64) -> String {
     let mins = seconds / 60;
     let secs = seconds % 60;
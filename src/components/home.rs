@@ -1,15 +1,91 @@
+use super::game::{load_player_name, parse_time_control, parse_variant, store_player_name};
+use crate::components::{AccountPanel, Board, Challenges, Lobby, SeekPanel};
+use crate::shared::position::{MoveRules, Position};
+use crate::shared::{Account, PlayerColor};
 use leptos::prelude::*;
 use leptos_router::hooks::*;
+use std::str::FromStr;
 
 #[component]
 pub fn Home() -> impl IntoView {
     let (room_code, set_room_code) = signal(String::new());
+    let (player_name, set_player_name) = signal(load_player_name().unwrap_or_default());
+    let (account, set_account) = signal::<Option<Account>>(None);
+    let (allow_takebacks, set_allow_takebacks) = signal(true);
+    let (rated, set_rated) = signal(false);
+    let (time_control, set_time_control) = signal("10+0".to_string());
+    let (delay_mode, set_delay_mode) = signal("none".to_string());
+    let (delay_secs, set_delay_secs) = signal("5".to_string());
+    let (variant, set_variant) = signal("standard".to_string());
+    let (chess960_position, set_chess960_position) = signal(String::new());
+    let (start_fen, set_start_fen) = signal(String::new());
+    let (preview_fen, set_preview_fen) = signal(String::new());
+    let (preview_color, _) = signal::<Option<PlayerColor>>(None);
+    let (preview_turn, _) = signal(PlayerColor::White);
+    let (preview_only, _) = signal(true);
     let navigate = use_navigate();
 
+    // Variants with a fixed setup of their own take no custom FEN
+    let allows_start_fen = move || !matches!(variant.get().as_str(), "chess960" | "racingkings");
+
+    // Seeks and lobby challenges use the same options as a new room
+    let match_time_control = Signal::derive(move || {
+        let delay = delay_mode.get();
+        parse_time_control(
+            &time_control.get(),
+            Some(delay.as_str()).filter(|delay| *delay != "none"),
+            delay_secs.get().parse().unwrap_or(0),
+        )
+    });
+    let match_variant = Signal::derive(move || {
+        parse_variant(&variant.get(), chess960_position.get().trim().parse().ok())
+    });
+    let match_rated = Signal::derive(move || rated.get() && account.get().is_some());
+
     let navigate_clone1 = navigate.clone();
     let create_game = move |_| {
         let code = generate_room_code();
-        navigate_clone1(&format!("/game/{}?action=create", code), Default::default());
+        let mut url = format!(
+            "/game/{}?action=create&takebacks={}&tc={}",
+            code,
+            allow_takebacks.get(),
+            encode_query_value(&time_control.get())
+        );
+        // The server turns down rated games it cannot rate, such as variants
+        if rated.get() && account.get().is_some() {
+            url.push_str("&rated=true");
+        }
+        if delay_mode.get() != "none" {
+            url.push_str(&format!(
+                "&delay={}&delay_secs={}",
+                delay_mode.get(),
+                delay_secs.get()
+            ));
+        }
+        if variant.get() != "standard" {
+            url.push_str(&format!("&variant={}", variant.get()));
+        }
+        if variant.get() == "chess960" {
+            if let Ok(position) = chess960_position.get().trim().parse::<u16>() {
+                url.push_str(&format!("&position={}", position));
+            }
+        } else if allows_start_fen() && !start_fen.get().trim().is_empty() {
+            url.push_str(&format!(
+                "&fen={}",
+                encode_query_value(start_fen.get().trim())
+            ));
+        }
+        navigate_clone1(&url, Default::default());
+    };
+
+    // The server has the final say; this only decides whether to preview
+    let fen_is_valid = move || is_valid_fen(&variant.get(), start_fen.get().trim());
+    let update_start_fen = move |ev| {
+        let fen = event_target_value(&ev);
+        if is_valid_fen(&variant.get_untracked(), fen.trim()) {
+            set_preview_fen.set(fen.trim().to_string());
+        }
+        set_start_fen.set(fen);
     };
 
     let navigate_clone2 = navigate.clone();
@@ -20,10 +96,142 @@ pub fn Home() -> impl IntoView {
         }
     };
 
+    let navigate_clone3 = navigate.clone();
+    let watch_game = move |_| {
+        let code = room_code.get();
+        if !code.is_empty() {
+            navigate_clone3(
+                &format!("/game/{}?action=spectate", code),
+                Default::default(),
+            );
+        }
+    };
+
     view! {
         <div class="home">
             <h1>"Chess Game"</h1>
+            <AccountPanel account=account set_account=set_account />
+            <Show when=move || account.get().is_none()>
+                <label class="room-option">
+                    "Your name"
+                    <input
+                        type="text"
+                        class="name-input"
+                        maxlength="20"
+                        placeholder="Anonymous"
+                        prop:value=player_name
+                        on:input=move |ev| {
+                            let name = event_target_value(&ev);
+                            store_player_name(name.trim());
+                            set_player_name.set(name);
+                        }
+                    />
+                </label>
+            </Show>
+            <label class="room-option">
+                "Time control"
+                <select on:change=move |ev| set_time_control.set(event_target_value(&ev))>
+                    <option value="1+0">"Bullet 1+0"</option>
+                    <option value="3+2">"Blitz 3+2"</option>
+                    <option value="5+0">"Blitz 5+0"</option>
+                    <option value="10+0" selected>"Rapid 10+0"</option>
+                    <option value="15+10">"Rapid 15+10"</option>
+                    <option value="30+0">"Classical 30+0"</option>
+                    <option value="90/40+30">"Classical 90/40+30"</option>
+                    <option value="untimed">"Untimed"</option>
+                </select>
+            </label>
+            <label class="room-option">
+                "Delay"
+                <select on:change=move |ev| set_delay_mode.set(event_target_value(&ev))>
+                    <option value="none" selected>"None"</option>
+                    <option value="simple">"Simple"</option>
+                    <option value="bronstein">"Bronstein"</option>
+                </select>
+                <input
+                    type="number"
+                    min="1"
+                    class="delay-input"
+                    prop:value=delay_secs
+                    disabled=move || delay_mode.get() == "none"
+                    on:input=move |ev| set_delay_secs.set(event_target_value(&ev))
+                />
+                "s"
+            </label>
+            <label class="room-option">
+                <input
+                    type="checkbox"
+                    prop:checked=allow_takebacks
+                    on:change=move |ev| set_allow_takebacks.set(event_target_checked(&ev))
+                />
+                "Allow takebacks"
+            </label>
+            <Show when=move || account.get().is_some()>
+                <label class="room-option">
+                    <input
+                        type="checkbox"
+                        prop:checked=rated
+                        on:change=move |ev| set_rated.set(event_target_checked(&ev))
+                    />
+                    "Rated"
+                </label>
+            </Show>
+            <label class="room-option">
+                "Variant"
+                <select on:change=move |ev| set_variant.set(event_target_value(&ev))>
+                    <option value="standard" selected>"Standard"</option>
+                    <option value="chess960">"Chess960"</option>
+                    <option value="kingofthehill">"King of the Hill"</option>
+                    <option value="threecheck">"Three-check"</option>
+                    <option value="racingkings">"Racing Kings"</option>
+                    <option value="crazyhouse">"Crazyhouse"</option>
+                    <option value="atomic">"Atomic"</option>
+                    <option value="antichess">"Antichess"</option>
+                </select>
+                <input
+                    type="number"
+                    min="0"
+                    max="959"
+                    class="delay-input"
+                    placeholder="Random"
+                    prop:value=chess960_position
+                    disabled=move || variant.get() != "chess960"
+                    on:input=move |ev| set_chess960_position.set(event_target_value(&ev))
+                />
+            </label>
+            <Show when=allows_start_fen>
+                <label class="room-option">
+                    "Starting position (FEN, optional)"
+                    <input
+                        type="text"
+                        class="fen-input"
+                        placeholder="Standard starting position"
+                        prop:value=start_fen
+                        on:input=update_start_fen
+                    />
+                </label>
+                <Show when=move || !start_fen.get().trim().is_empty()>
+                    <Show
+                        when=fen_is_valid
+                        fallback=|| view! { <p class="fen-error">"Not a valid FEN"</p> }
+                    >
+                        <div class="fen-preview">
+                            <Board
+                                fen=preview_fen
+                                player_color=preview_color
+                                current_turn=preview_turn
+                                on_move=|_: String, _: String, _: Option<String>| {}
+                                game_over=preview_only
+                            />
+                        </div>
+                    </Show>
+                </Show>
+            </Show>
             <button on:click=create_game>"Create New Game"</button>
+            <SeekPanel time_control=match_time_control rated=match_rated />
+            <Show when=move || account.get().is_some()>
+                <Challenges time_control=match_time_control variant=match_variant rated=match_rated />
+            </Show>
             <input
                 type="text"
                 placeholder="Room Code"
@@ -31,6 +239,10 @@ pub fn Home() -> impl IntoView {
                 on:input=move |ev| set_room_code.set(event_target_value(&ev))
             />
             <button on:click=join_game>"Join Game"</button>
+            <button on:click=watch_game>"Watch Game"</button>
+            <Lobby time_control=match_time_control variant=match_variant rated=match_rated />
+            <a class="analysis-link" href="/analysis">"Analyse a PGN"</a>
+            <a class="analysis-link" href="/games">"My games"</a>
         </div>
     }
 }
@@ -44,3 +256,19 @@ fn generate_room_code() -> String {
         })
         .collect()
 }
+
+fn encode_query_value(value: &str) -> String {
+    value
+        .replace('+', "%2B")
+        .replace('/', "%2F")
+        .replace(' ', "%20")
+}
+
+// Atomic and Antichess positions may lack kings the chess crate insists on
+fn is_valid_fen(variant: &str, fen: &str) -> bool {
+    match variant {
+        "atomic" => Position::from_fen(fen, MoveRules::Atomic).is_ok(),
+        "antichess" => Position::from_fen(fen, MoveRules::Antichess).is_ok(),
+        _ => chess::Board::from_str(fen).is_ok(),
+    }
+}
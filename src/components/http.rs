@@ -0,0 +1,56 @@
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::XmlHttpRequest;
+
+// Fetches `url` and hands its body to `on_done`; an error status is reported
+// with the body the server sent along
+pub fn get_text(url: &str, on_done: impl FnOnce(Result<String, String>) + 'static) {
+    send("GET", url, None, on_done);
+}
+
+pub fn post_json(url: &str, body: &str, on_done: impl FnOnce(Result<String, String>) + 'static) {
+    send("POST", url, Some(body), on_done);
+}
+
+fn send(
+    method: &str,
+    url: &str,
+    body: Option<&str>,
+    on_done: impl FnOnce(Result<String, String>) + 'static,
+) {
+    let request = match XmlHttpRequest::new() {
+        Ok(request) if request.open(method, url).is_ok() => request,
+        _ => {
+            on_done(Err("Could not send the request".to_string()));
+            return;
+        }
+    };
+
+    let request_clone = request.clone();
+    let mut on_done = Some(on_done);
+    let onloadend = Closure::wrap(Box::new(move || {
+        let Some(on_done) = on_done.take() else {
+            return;
+        };
+        let body = request_clone
+            .response_text()
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        match request_clone.status() {
+            Ok(200..=299) => on_done(Ok(body)),
+            Ok(0) | Err(_) => on_done(Err("Could not reach the server".to_string())),
+            Ok(status) if body.is_empty() => on_done(Err(format!("Request failed ({})", status))),
+            Ok(_) => on_done(Err(body)),
+        }
+    }) as Box<dyn FnMut()>);
+    request.set_onloadend(Some(onloadend.as_ref().unchecked_ref()));
+    onloadend.forget();
+
+    let _ = match body {
+        Some(body) => request
+            .set_request_header("Content-Type", "application/json")
+            .and_then(|_| request.send_with_opt_str(Some(body))),
+        None => request.send(),
+    };
+}
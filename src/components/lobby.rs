@@ -0,0 +1,288 @@
+use super::game::{load_player_name, socket_url, store_rejoin_token};
+use crate::shared::{
+    ClientMessage, ColorPreference, OpenChallenge, ServerMessage, TimeControl, Variant,
+};
+use leptos::prelude::*;
+use leptos_router::hooks::use_navigate;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{MessageEvent, WebSocket};
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortBy {
+    Player,
+    Rating,
+    TimeControl,
+    Variant,
+    Rated,
+}
+
+// Open challenges anyone may accept, kept up to date by the server, and a
+// form to post one with the options chosen above
+#[component]
+pub fn Lobby(
+    time_control: Signal<Option<TimeControl>>,
+    variant: Signal<Variant>,
+    rated: Signal<bool>,
+) -> impl IntoView {
+    let (ws, set_ws) = signal_local::<Option<WebSocket>>(None);
+    let (challenges, set_challenges) = signal::<Vec<OpenChallenge>>(Vec::new());
+    let (own_challenge, set_own_challenge) = signal::<Option<String>>(None);
+    let (color, set_color) = signal(ColorPreference::Random);
+    let (sort_by, set_sort_by) = signal(SortBy::Rating);
+    let (ascending, set_ascending) = signal(false);
+    let (error, set_error) = signal::<Option<String>>(None);
+    let navigate = use_navigate();
+
+    Effect::new(move |_| {
+        let Ok(socket) = WebSocket::new(&socket_url()) else {
+            set_error.set(Some("Failed to connect to the lobby".to_string()));
+            return;
+        };
+
+        let socket_clone = socket.clone();
+        let onopen = Closure::wrap(Box::new(move || {
+            if let Ok(json) = serde_json::to_string(&ClientMessage::WatchLobby) {
+                let _ = socket_clone.send_with_str(&json);
+            }
+        }) as Box<dyn FnMut()>);
+        socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+        onopen.forget();
+
+        let navigate = navigate.clone();
+        let socket_clone = socket.clone();
+        let onmessage = Closure::wrap(Box::new(move |e: MessageEvent| {
+            let Some(msg) = e
+                .data()
+                .as_string()
+                .and_then(|txt| serde_json::from_str::<ServerMessage>(&txt).ok())
+            else {
+                return;
+            };
+            match msg {
+                ServerMessage::Lobby { challenges } => set_challenges.set(challenges),
+                ServerMessage::OpenChallengeAdded { challenge } => {
+                    set_challenges.update(|challenges| challenges.push(challenge));
+                }
+                ServerMessage::OpenChallengeRemoved { id } => {
+                    set_challenges.update(|challenges| challenges.retain(|c| c.id != id));
+                    if own_challenge.get_untracked().as_ref() == Some(&id) {
+                        set_own_challenge.set(None);
+                    }
+                }
+                ServerMessage::OpenChallengePosted { id } => {
+                    set_own_challenge.set(Some(id));
+                    set_error.set(None);
+                }
+                ServerMessage::MatchFound {
+                    room_code,
+                    rejoin_token,
+                    ..
+                } => {
+                    store_rejoin_token(&room_code, &rejoin_token);
+                    socket_clone.set_onclose(None);
+                    let _ = socket_clone.close();
+                    navigate(&format!("/game/{}", room_code), Default::default());
+                }
+                ServerMessage::Error { message } => set_error.set(Some(message)),
+                _ => {}
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        onmessage.forget();
+
+        let onclose = Closure::wrap(Box::new(move || {
+            set_challenges.set(Vec::new());
+            set_own_challenge.set(None);
+            set_error.set(Some("Lost the connection to the lobby".to_string()));
+        }) as Box<dyn FnMut()>);
+        socket.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+        onclose.forget();
+
+        set_ws.set(Some(socket));
+    });
+
+    on_cleanup(move || {
+        if let Some(socket) = ws.get_untracked() {
+            socket.set_onclose(None);
+            let _ = socket.close();
+        }
+    });
+
+    let send = move |msg: ClientMessage| {
+        if let (Some(socket), Ok(json)) = (ws.get_untracked(), serde_json::to_string(&msg)) {
+            let _ = socket.send_with_str(&json);
+        }
+    };
+
+    let post = move |_| {
+        send(ClientMessage::PostOpenChallenge {
+            time_control: time_control.get_untracked(),
+            variant: variant.get_untracked(),
+            rated: rated.get_untracked(),
+            color: color.get_untracked(),
+            name: load_player_name(),
+        });
+    };
+
+    let sort = move |by: SortBy| {
+        if sort_by.get_untracked() == by {
+            set_ascending.update(|ascending| *ascending = !*ascending);
+        } else {
+            set_sort_by.set(by);
+            set_ascending.set(true);
+        }
+    };
+
+    let sorted = move || {
+        let (by, ascending) = (sort_by.get(), ascending.get());
+        let mut challenges = challenges.get();
+        challenges.sort_by(|a, b| {
+            let order = match by {
+                SortBy::Player => a.name.cmp(&b.name),
+                SortBy::Rating => a.rating.cmp(&b.rating),
+                SortBy::TimeControl => {
+                    expected_length(&a.time_control).cmp(&expected_length(&b.time_control))
+                }
+                SortBy::Variant => a.variant.label().cmp(&b.variant.label()),
+                SortBy::Rated => a.rated.cmp(&b.rated),
+            };
+            if ascending {
+                order
+            } else {
+                order.reverse()
+            }
+        });
+        challenges
+    };
+
+    let header = move |label: &'static str, by: SortBy| {
+        view! {
+            <th class="sortable" on:click=move |_| sort(by)>
+                {label}
+                {move || {
+                    (sort_by.get() == by).then(|| if ascending.get() { " ▲" } else { " ▼" })
+                }}
+            </th>
+        }
+    };
+
+    view! {
+        <div class="lobby">
+            <h2>"Lobby"</h2>
+            <div class="lobby-post">
+                <select on:change=move |ev| {
+                    set_color
+                        .set(
+                            match event_target_value(&ev).as_str() {
+                                "white" => ColorPreference::White,
+                                "black" => ColorPreference::Black,
+                                _ => ColorPreference::Random,
+                            },
+                        )
+                }>
+                    <option value="random" selected>"Random color"</option>
+                    <option value="white">"White"</option>
+                    <option value="black">"Black"</option>
+                </select>
+                <Show
+                    when=move || own_challenge.get().is_some()
+                    fallback=move || {
+                        view! {
+                            <button class="btn btn-secondary" on:click=post>
+                                "Post Challenge"
+                            </button>
+                        }
+                    }
+                >
+                    <button
+                        class="btn btn-secondary"
+                        on:click=move |_| send(ClientMessage::CancelOpenChallenge)
+                    >
+                        "Cancel Challenge"
+                    </button>
+                </Show>
+            </div>
+            {move || error.get().map(|message| view! { <p class="error">{message}</p> })}
+            <table class="games-table lobby-table">
+                <thead>
+                    <tr>
+                        {header("Player", SortBy::Player)}
+                        {header("Rating", SortBy::Rating)}
+                        {header("Time", SortBy::TimeControl)}
+                        {header("Variant", SortBy::Variant)}
+                        {header("Mode", SortBy::Rated)}
+                        <th>"Color"</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    <For
+                        each=sorted
+                        key=|challenge| challenge.id.clone()
+                        children=move |challenge| {
+                            let id = challenge.id.clone();
+                            let own = own_challenge.get_untracked().as_ref() == Some(&id);
+                            view! {
+                                <tr>
+                                    <td>{challenge.name.unwrap_or_else(|| "Anonymous".to_string())}</td>
+                                    <td>
+                                        {challenge
+                                            .rating
+                                            .map_or("-".to_string(), |rating| rating.to_string())}
+                                    </td>
+                                    <td>
+                                        {challenge
+                                            .time_control
+                                            .map_or("Untimed".to_string(), |tc| tc.label())}
+                                    </td>
+                                    <td>{challenge.variant.label()}</td>
+                                    <td>{if challenge.rated { "Rated" } else { "Casual" }}</td>
+                                    <td>
+                                        {match challenge.color {
+                                            ColorPreference::Random => "Random",
+                                            ColorPreference::White => "White",
+                                            ColorPreference::Black => "Black",
+                                        }}
+                                    </td>
+                                    <td>
+                                        <Show
+                                            when=move || !own
+                                            fallback=|| view! { <span>"Yours"</span> }
+                                        >
+                                            <button
+                                                class="btn btn-secondary"
+                                                on:click={
+                                                    let id = id.clone();
+                                                    move |_| {
+                                                        send(ClientMessage::AcceptOpenChallenge {
+                                                            id: id.clone(),
+                                                            name: load_player_name(),
+                                                        })
+                                                    }
+                                                }
+                                            >
+                                                "Accept"
+                                            </button>
+                                        </Show>
+                                    </td>
+                                </tr>
+                            }
+                        }
+                    />
+                </tbody>
+            </table>
+            <Show when=move || challenges.with(|challenges| challenges.is_empty())>
+                <p class="no-games">"No open challenges"</p>
+            </Show>
+        </div>
+    }
+}
+
+// Base time plus 40 increments, so challenges sort from bullet to classical;
+// untimed games last longest
+fn expected_length(time_control: &Option<TimeControl>) -> u64 {
+    time_control
+        .as_ref()
+        .map_or(u64::MAX, |tc| tc.base_ms + 40 * tc.increment_ms)
+}
@@ -1,7 +1,20 @@
+mod account;
+mod analysis;
 mod board;
+mod challenges;
 mod game;
 mod home;
+mod http;
+mod lobby;
+mod my_games;
+mod seek;
 
+pub use account::AccountPanel;
+pub use analysis::Analysis;
 pub use board::Board;
+pub use challenges::Challenges;
 pub use game::Game;
 pub use home::Home;
+pub use lobby::Lobby;
+pub use my_games::MyGames;
+pub use seek::SeekPanel;
@@ -0,0 +1,229 @@
+use super::account::parse_account;
+use super::http::get_text;
+use crate::shared::{GamePage, GameSummary};
+use leptos::prelude::*;
+use wasm_bindgen::JsValue;
+
+const PER_PAGE: usize = 20;
+
+#[component]
+pub fn MyGames() -> impl IntoView {
+    // The server fills in the signed-in account, which replaces the player
+    let (mine, set_mine) = signal(false);
+    let (signed_in, set_signed_in) = signal(false);
+    let (player, set_player) = signal(String::new());
+    let (result, set_result) = signal(String::new());
+    let (eco, set_eco) = signal(String::new());
+    let (from, set_from) = signal(String::new());
+    let (to, set_to) = signal(String::new());
+    let (time_control, set_time_control) = signal(String::new());
+    let (page, set_page) = signal(1usize);
+    // Bumped to run the search again with the filters as they are now
+    let (search, set_search) = signal(0u32);
+    let (found, set_found) = signal::<Option<GamePage>>(None);
+    let (error, set_error) = signal::<Option<String>>(None);
+
+    // Signed-in players see their own games by default
+    Effect::new(move |_| {
+        get_text("/api/me", move |response| {
+            if response.and_then(|body| parse_account(&body)).is_ok() {
+                set_signed_in.set(true);
+                set_mine.set(true);
+                set_search.update(|n| *n += 1);
+            }
+        });
+    });
+
+    Effect::new(move |_| {
+        search.track();
+        let mut url = format!(
+            "/api/games?page={}&per_page={}",
+            page.get_untracked(),
+            PER_PAGE
+        );
+        if mine.get_untracked() {
+            url.push_str("&mine=true");
+        }
+        let filters = [
+            ("player", player),
+            ("result", result),
+            ("eco", eco),
+            ("from", from),
+            ("to", to),
+            ("time_control", time_control),
+        ];
+        for (key, value) in filters {
+            let value = value.get_untracked();
+            if !value.trim().is_empty() {
+                let value = String::from(js_sys::encode_uri_component(value.trim()));
+                url.push_str(&format!("&{}={}", key, value));
+            }
+        }
+
+        get_text(&url, move |response| {
+            let page = response.and_then(|body| {
+                serde_json::from_str::<GamePage>(&body).map_err(|err| err.to_string())
+            });
+            match page {
+                Ok(page) => {
+                    set_found.set(Some(page));
+                    set_error.set(None);
+                }
+                Err(message) => set_error.set(Some(message)),
+            }
+        });
+    });
+
+    let run_search = move |_| {
+        set_page.set(1);
+        set_search.update(|n| *n += 1);
+    };
+    let turn_page = move |delta: isize| {
+        set_page.update(|page| *page = page.saturating_add_signed(delta).max(1));
+        set_search.update(|n| *n += 1);
+    };
+
+    let page_count = move || {
+        found.with(|found| {
+            found
+                .as_ref()
+                .map_or(1, |found| found.total.div_ceil(found.per_page).max(1))
+        })
+    };
+
+    view! {
+        <div class="my-games">
+            <h2>"My games"</h2>
+            <div class="game-filters">
+                <Show when=move || signed_in.get()>
+                    <label>
+                        <input
+                            type="checkbox"
+                            prop:checked=mine
+                            on:change=move |ev| set_mine.set(event_target_checked(&ev))
+                        />
+                        "Only mine"
+                    </label>
+                </Show>
+                <input
+                    type="text"
+                    placeholder="Player's username"
+                    prop:value=player
+                    disabled=move || mine.get()
+                    on:input=move |ev| set_player.set(event_target_value(&ev))
+                />
+                <select on:change=move |ev| set_result.set(event_target_value(&ev))>
+                    <option value="" selected>"Any result"</option>
+                    <option value="1-0">"1-0"</option>
+                    <option value="0-1">"0-1"</option>
+                    <option value="1/2-1/2">"½-½"</option>
+                </select>
+                <input
+                    type="text"
+                    class="eco-input"
+                    placeholder="ECO"
+                    prop:value=eco
+                    on:input=move |ev| set_eco.set(event_target_value(&ev))
+                />
+                <input
+                    type="text"
+                    class="eco-input"
+                    placeholder="e.g. 180+2"
+                    prop:value=time_control
+                    on:input=move |ev| set_time_control.set(event_target_value(&ev))
+                />
+                <label>
+                    "From"
+                    <input
+                        type="date"
+                        prop:value=from
+                        on:input=move |ev| set_from.set(event_target_value(&ev))
+                    />
+                </label>
+                <label>
+                    "To"
+                    <input
+                        type="date"
+                        prop:value=to
+                        on:input=move |ev| set_to.set(event_target_value(&ev))
+                    />
+                </label>
+                <button class="btn btn-secondary" on:click=run_search>
+                    "Search"
+                </button>
+            </div>
+            {move || error.get().map(|message| view! { <p class="error">{message}</p> })}
+            <table class="games-table">
+                <thead>
+                    <tr>
+                        <th>"Date"</th>
+                        <th>"White"</th>
+                        <th>"Black"</th>
+                        <th>"Result"</th>
+                        <th>"Termination"</th>
+                        <th>"Opening"</th>
+                        <th>"Time control"</th>
+                        <th>"Variant"</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    <For
+                        each=move || found.get().map(|found| found.games).unwrap_or_default()
+                        key=|game| game.id.clone()
+                        children=game_row
+                    />
+                </tbody>
+            </table>
+            <Show when=move || found.with(|found| found.as_ref().is_some_and(|f| f.total == 0))>
+                <p class="no-games">"No games found"</p>
+            </Show>
+            <div class="pagination">
+                <button
+                    class="btn btn-secondary"
+                    disabled=move || page.get() <= 1
+                    on:click=move |_| turn_page(-1)
+                >
+                    "Previous"
+                </button>
+                <span>{move || format!("Page {} of {}", page.get(), page_count())}</span>
+                <button
+                    class="btn btn-secondary"
+                    disabled=move || page.get() >= page_count()
+                    on:click=move |_| turn_page(1)
+                >
+                    "Next"
+                </button>
+            </div>
+            <a class="analysis-link" href="/">"Back"</a>
+        </div>
+    }
+}
+
+fn game_row(game: GameSummary) -> impl IntoView {
+    let opening = match (&game.eco, &game.opening) {
+        (Some(eco), Some(name)) => format!("{} {}", eco, name),
+        _ => "-".to_string(),
+    };
+    view! {
+        <tr>
+            <td>{format_date(game.started_at)}</td>
+            <td>{game.white.unwrap_or_else(|| "Anonymous".to_string())}</td>
+            <td>{game.black.unwrap_or_else(|| "Anonymous".to_string())}</td>
+            <td>{game.result}</td>
+            <td>{game.termination}</td>
+            <td>{opening}</td>
+            <td>{game.time_control}</td>
+            <td>{game.variant}</td>
+            <td>
+                <a href=format!("/analysis?game={}", game.id)>"Replay"</a>
+            </td>
+        </tr>
+    }
+}
+
+// "YYYY-MM-DD" in UTC, as the date filters are
+fn format_date(ms: u64) -> String {
+    let iso = String::from(js_sys::Date::new(&JsValue::from_f64(ms as f64)).to_iso_string());
+    iso.get(..10).unwrap_or_default().to_string()
+}
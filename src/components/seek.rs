@@ -0,0 +1,148 @@
+use super::game::{load_player_name, socket_url, store_rejoin_token};
+use crate::shared::{ClientMessage, ColorPreference, ServerMessage, TimeControl};
+use leptos::prelude::*;
+use leptos_router::hooks::use_navigate;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{MessageEvent, WebSocket};
+
+// Finds an opponent with the chosen time control. Once matched, the game
+// page takes the seat with the rejoin token the server hands out
+#[component]
+pub fn SeekPanel(time_control: Signal<Option<TimeControl>>, rated: Signal<bool>) -> impl IntoView {
+    let (color_preference, set_color_preference) = signal(ColorPreference::Random);
+    let (rating_range, set_rating_range) = signal::<Option<u32>>(None);
+    let (ws, set_ws) = signal_local::<Option<WebSocket>>(None);
+    let (seeking, set_seeking) = signal(false);
+    let (error, set_error) = signal::<Option<String>>(None);
+    let navigate = use_navigate();
+
+    let send = move |msg: &ClientMessage| {
+        if let (Some(socket), Ok(json)) = (ws.get_untracked(), serde_json::to_string(msg)) {
+            let _ = socket.send_with_str(&json);
+        }
+    };
+
+    let close = move || {
+        if let Some(socket) = ws.get_untracked() {
+            socket.set_onclose(None);
+            let _ = socket.close();
+        }
+        set_ws.set(None);
+        set_seeking.set(false);
+    };
+    on_cleanup(close);
+
+    let seek = move |_| {
+        close();
+        set_error.set(None);
+        let Ok(socket) = WebSocket::new(&socket_url()) else {
+            set_error.set(Some("Failed to connect".to_string()));
+            return;
+        };
+
+        let msg = ClientMessage::Seek {
+            time_control: time_control.get_untracked(),
+            rated: rated.get_untracked(),
+            rating_range: rating_range.get_untracked(),
+            color_preference: color_preference.get_untracked(),
+            name: load_player_name(),
+        };
+        let socket_clone = socket.clone();
+        let onopen = Closure::wrap(Box::new(move || {
+            if let Ok(json) = serde_json::to_string(&msg) {
+                let _ = socket_clone.send_with_str(&json);
+            }
+        }) as Box<dyn FnMut()>);
+        socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+        onopen.forget();
+
+        let navigate = navigate.clone();
+        let onmessage = Closure::wrap(Box::new(move |e: MessageEvent| {
+            let Some(msg) = e
+                .data()
+                .as_string()
+                .and_then(|txt| serde_json::from_str::<ServerMessage>(&txt).ok())
+            else {
+                return;
+            };
+            match msg {
+                ServerMessage::Seeking => set_seeking.set(true),
+                ServerMessage::SeekCancelled => set_seeking.set(false),
+                ServerMessage::MatchFound {
+                    room_code,
+                    rejoin_token,
+                    ..
+                } => {
+                    store_rejoin_token(&room_code, &rejoin_token);
+                    close();
+                    navigate(&format!("/game/{}", room_code), Default::default());
+                }
+                ServerMessage::Error { message } => {
+                    set_error.set(Some(message));
+                    close();
+                }
+                _ => {}
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        onmessage.forget();
+
+        let onclose = Closure::wrap(Box::new(move || {
+            set_seeking.set(false);
+            set_ws.set(None);
+        }) as Box<dyn FnMut()>);
+        socket.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+        onclose.forget();
+
+        set_ws.set(Some(socket));
+    };
+
+    let cancel = move |_| {
+        send(&ClientMessage::CancelSeek);
+        close();
+    };
+
+    view! {
+        <div class="seek-panel">
+            <label class="room-option">
+                "Color"
+                <select on:change=move |ev| {
+                    set_color_preference
+                        .set(
+                            match event_target_value(&ev).as_str() {
+                                "white" => ColorPreference::White,
+                                "black" => ColorPreference::Black,
+                                _ => ColorPreference::Random,
+                            },
+                        )
+                }>
+                    <option value="random" selected>"Random"</option>
+                    <option value="white">"White"</option>
+                    <option value="black">"Black"</option>
+                </select>
+            </label>
+            <label class="room-option">
+                "Opponent rating"
+                <select on:change=move |ev| {
+                    set_rating_range.set(event_target_value(&ev).parse().ok())
+                }>
+                    <option value="any" selected>"Any"</option>
+                    <option value="100">"± 100"</option>
+                    <option value="200">"± 200"</option>
+                    <option value="400">"± 400"</option>
+                </select>
+            </label>
+            <Show
+                when=move || seeking.get()
+                fallback=move || view! { <button on:click=seek>"Find Opponent"</button> }
+            >
+                <p class="seek-status">"Looking for an opponent..."</p>
+                <button class="btn btn-secondary" on:click=cancel>
+                    "Cancel"
+                </button>
+            </Show>
+            {move || error.get().map(|message| view! { <p class="error">{message}</p> })}
+        </div>
+    }
+}
@@ -0,0 +1,210 @@
+#[cfg(feature = "ssr")]
+use crate::shared::pgn::piece_char;
+#[cfg(feature = "ssr")]
+use crate::shared::{Pockets, POCKET_PIECES};
+#[cfg(feature = "ssr")]
+use crate::variants::player_color;
+#[cfg(feature = "ssr")]
+use chess::{BitBoard, Board, BoardBuilder, ChessMove, Piece, Rank, Square, EMPTY};
+
+// Pockets plus the squares holding promoted pieces, which go back into a
+// pocket as pawns when captured
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reserves {
+    pub pockets: Pockets,
+    promoted: BitBoard,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for Reserves {
+    fn default() -> Self {
+        Self {
+            pockets: Pockets::default(),
+            promoted: EMPTY,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl Reserves {
+    // Pockets the piece taken by a move, played before the move is applied
+    pub fn record_move(&mut self, board: &Board, chess_move: ChessMove) {
+        let (from, to) = (chess_move.get_source(), chess_move.get_dest());
+        let mover = player_color(board.side_to_move());
+
+        let en_passant = board.piece_on(from) == Some(Piece::Pawn)
+            && from.get_file() != to.get_file()
+            && board.piece_on(to).is_none();
+        let captured = if en_passant {
+            Some((
+                Piece::Pawn,
+                Square::make_square(from.get_rank(), to.get_file()),
+            ))
+        } else {
+            board.piece_on(to).map(|piece| (piece, to))
+        };
+
+        if let Some((piece, square)) = captured {
+            let piece = if self.promoted & BitBoard::from_square(square) != EMPTY {
+                Piece::Pawn
+            } else {
+                piece
+            };
+            self.pockets.get_mut(mover).add(piece_char(piece));
+            self.promoted &= !BitBoard::from_square(square);
+        }
+
+        if self.promoted & BitBoard::from_square(from) != EMPTY
+            || chess_move.get_promotion().is_some()
+        {
+            self.promoted &= !BitBoard::from_square(from);
+            self.promoted |= BitBoard::from_square(to);
+        }
+    }
+
+    // The position after the side to move drops `piece` on `square`, taking it
+    // from their pocket
+    pub fn drop_piece(
+        &mut self,
+        board: &Board,
+        piece: Piece,
+        square: Square,
+    ) -> Result<Board, String> {
+        let mover = player_color(board.side_to_move());
+        if self.pockets.get(mover).count(piece_char(piece)) == 0 {
+            return Err("You have no such piece to drop".to_string());
+        }
+        let next = drop_board(board, piece, square).ok_or("Illegal drop")?;
+
+        self.pockets.get_mut(mover).take(piece_char(piece));
+        Ok(next)
+    }
+
+    // Whether the side to move has any legal drop, e.g. to block a check
+    pub fn has_drop(&self, board: &Board) -> bool {
+        let pocket = self.pockets.get(player_color(board.side_to_move()));
+        POCKET_PIECES
+            .iter()
+            .filter(|&&letter| pocket.count(letter) > 0)
+            .filter_map(|&letter| piece_from_char(letter))
+            .any(|piece| {
+                (!*board.combined()).any(|square| drop_board(board, piece, square).is_some())
+            })
+    }
+
+    // Mixed into position hashes so repetitions respect what is in hand
+    pub fn hash(&self) -> u64 {
+        self.pockets
+            .white
+            .counts
+            .iter()
+            .chain(&self.pockets.black.counts)
+            .fold(0u64, |hash, &count| hash.rotate_left(5) ^ u64::from(count))
+            .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    }
+}
+
+// Drops may go on any empty square except pawns on the first or last rank,
+// and may not leave the dropping side in check
+#[cfg(feature = "ssr")]
+fn drop_board(board: &Board, piece: Piece, square: Square) -> Option<Board> {
+    if board.piece_on(square).is_some() {
+        return None;
+    }
+    if piece == Piece::Pawn && matches!(square.get_rank(), Rank::First | Rank::Eighth) {
+        return None;
+    }
+
+    let color = board.side_to_move();
+    let mut builder = BoardBuilder::from(board);
+    builder
+        .piece(square, piece, color)
+        .side_to_move(!color)
+        .en_passant(None);
+    Board::try_from(&builder).ok()
+}
+
+// Drop notation such as "N@f3" or "P@e6", without a check suffix
+#[cfg(feature = "ssr")]
+pub fn drop_san(piece: Piece, square: Square) -> String {
+    format!("{}@{}", piece_char(piece), square)
+}
+
+#[cfg(feature = "ssr")]
+pub fn piece_from_char(letter: char) -> Option<Piece> {
+    match letter.to_ascii_lowercase() {
+        'p' => Some(Piece::Pawn),
+        'n' => Some(Piece::Knight),
+        'b' => Some(Piece::Bishop),
+        'r' => Some(Piece::Rook),
+        'q' => Some(Piece::Queen),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::PlayerColor;
+    use std::str::FromStr;
+
+    fn board(fen: &str) -> Board {
+        Board::from_str(fen).unwrap()
+    }
+
+    fn square(name: &str) -> Square {
+        Square::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn pawns_cannot_be_dropped_on_the_first_or_last_rank() {
+        let board = board("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert!(drop_board(&board, Piece::Pawn, square("a1")).is_none());
+        assert!(drop_board(&board, Piece::Pawn, square("h8")).is_none());
+        assert!(drop_board(&board, Piece::Pawn, square("a4")).is_some());
+        assert!(drop_board(&board, Piece::Knight, square("h8")).is_some());
+    }
+
+    #[test]
+    fn drops_must_answer_a_check() {
+        let board = board("4k3/8/8/8/8/8/8/r3K3 w - - 0 1");
+        assert!(drop_board(&board, Piece::Knight, square("d1")).is_some());
+        assert!(drop_board(&board, Piece::Knight, square("d4")).is_none());
+
+        let mut reserves = Reserves::default();
+        assert!(reserves
+            .drop_piece(&board, Piece::Knight, square("d1"))
+            .is_err());
+        reserves.pockets.white.add('n');
+        assert!(reserves
+            .drop_piece(&board, Piece::Knight, square("d4"))
+            .is_err());
+        assert!(reserves
+            .drop_piece(&board, Piece::Knight, square("d1"))
+            .is_ok());
+        assert_eq!(reserves.pockets.white.count('n'), 0);
+    }
+
+    #[test]
+    fn promoted_pieces_are_pocketed_as_pawns() {
+        let mut reserves = Reserves::default();
+        let start = board("1n2k3/P7/8/8/8/8/1r6/4K3 w - - 0 1");
+        let promotion = ChessMove::new(square("a7"), square("b8"), Some(Piece::Queen));
+        reserves.record_move(&start, promotion);
+        assert_eq!(reserves.pockets.get(PlayerColor::White).count('n'), 1);
+
+        let promoted = start.make_move_new(promotion);
+        let capture = ChessMove::new(square("b2"), square("b8"), None);
+        reserves.record_move(&promoted, capture);
+        let black = reserves.pockets.get(PlayerColor::Black);
+        assert_eq!(black.count('p'), 1);
+        assert_eq!(black.count('q'), 0);
+    }
+
+    #[test]
+    fn drop_san_names_the_piece_and_square() {
+        assert_eq!(drop_san(Piece::Knight, square("f3")), "N@f3");
+        assert_eq!(drop_san(Piece::Pawn, square("e6")), "P@e6");
+    }
+}
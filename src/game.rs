@@ -1,5 +1,20 @@
 #[cfg(feature = "ssr")]
-use crate::shared::{GameResult, MoveRecord, PlayerColor};
+use crate::chess960::{self, CastlingRights};
+#[cfg(feature = "ssr")]
+use crate::crazyhouse::{self, Reserves};
+#[cfg(feature = "ssr")]
+use crate::shared::pgn::{move_to_san, STARTING_FEN};
+#[cfg(feature = "ssr")]
+use crate::shared::position::{MoveRules, Position, ANTICHESS_FEN};
+#[cfg(feature = "ssr")]
+use crate::shared::{
+    CheckCount, Delay, GameResult, MoveRecord, PlayerColor, RoomConfig, ServerMessage, TimeControl,
+    Variant,
+};
+#[cfg(feature = "ssr")]
+use crate::storage::{StoredGame, StoredMove};
+#[cfg(feature = "ssr")]
+use crate::variants::{self, Rules};
 #[cfg(feature = "ssr")]
 use chess::{Board, ChessMove, Color, Piece, Square};
 #[cfg(feature = "ssr")]
@@ -9,27 +24,169 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 #[cfg(feature = "ssr")]
 pub struct GameState {
+    pub id: String, // tells rematches in the same room apart in storage
     pub board: Board,
+    pub variant: Variant,
+    pub castling: Option<CastlingRights>, // tracked here for Chess960, by the board otherwise
+    pub checks: CheckCount,
+    pub reserves: Option<Reserves>, // Crazyhouse pockets
+    pub position: Option<Position>, // Atomic and Antichess, which `board` cannot hold
     pub moves: Vec<MoveRecord>,
+    pub time_control: Option<TimeControl>,
+    pub start_fen: Option<String>, // None for the standard starting position
+    pub first_move_number: u32,
+    pub start_turn: PlayerColor,
     pub white_time_ms: u64,
     pub black_time_ms: u64,
     pub last_move_time: u64,
+    pub started_at: u64,
     pub game_over: bool,
     pub result: Option<GameResult>,
+    pub position_history: Vec<u64>,
+    pub halfmove_clock: u32,
+    pub draw_offer: Option<PlayerColor>,
+    pub white_last_draw_offer: Option<usize>,
+    pub black_last_draw_offer: Option<usize>,
+    pub takeback_request: Option<PlayerColor>,
+    pub snapshots: Vec<Snapshot>,
+    pub abandoned_by: Option<PlayerColor>,
+}
+
+// Position and clocks as they stood before a ply, kept so it can be taken back
+#[cfg(feature = "ssr")]
+pub struct Snapshot {
+    pub board: Board,
+    pub white_time_ms: u64,
+    pub black_time_ms: u64,
+    pub halfmove_clock: u32,
+    pub castling: Option<CastlingRights>,
+    pub checks: CheckCount,
+    pub reserves: Option<Reserves>,
+    pub position: Option<Position>,
 }
 
+// Minimum number of plies between two draw offers from the same player
+#[cfg(feature = "ssr")]
+const DRAW_OFFER_INTERVAL_PLIES: usize = 10;
+
 #[cfg(feature = "ssr")]
 impl GameState {
-    pub fn new(time_control_ms: u64) -> Self {
-        Self {
-            board: Board::default(),
+    pub fn new(config: &RoomConfig) -> Result<Self, String> {
+        let (board, halfmove_clock, first_move_number) = match (config.variant, &config.start_fen) {
+            (Variant::Chess960 { .. }, Some(_)) => {
+                return Err("A custom starting position cannot be used with Chess960".to_string());
+            }
+            (Variant::Chess960 { position }, None) => {
+                let position = position.unwrap_or(chess960::STANDARD_POSITION);
+                if position >= chess960::POSITIONS {
+                    return Err(format!(
+                        "Chess960 positions are numbered 0 to {}",
+                        chess960::POSITIONS - 1
+                    ));
+                }
+                (chess960::start_board(position), 0, 1)
+            }
+            (Variant::RacingKings, Some(_)) => {
+                return Err(
+                    "A custom starting position cannot be used with Racing Kings".to_string(),
+                );
+            }
+            (Variant::Atomic | Variant::Antichess, Some(fen)) => {
+                let (halfmove_clock, fullmove_number) = parse_move_counters(fen)?;
+                (Board::default(), halfmove_clock, fullmove_number)
+            }
+            (_, Some(fen)) => parse_start_fen(fen)?,
+            (variant, None) => (variants::rules(variant).start_board(), 0, 1),
+        };
+        let castling = match config.variant {
+            Variant::Chess960 { .. } => Some(CastlingRights::initial(&board)),
+            _ => None,
+        };
+        let reserves = (config.variant == Variant::Crazyhouse).then(Reserves::default);
+        let position = match config.variant {
+            Variant::Atomic => Some(parse_start_position(
+                config.start_fen.as_deref().unwrap_or(STARTING_FEN),
+                MoveRules::Atomic,
+            )?),
+            Variant::Antichess => Some(parse_start_position(
+                config.start_fen.as_deref().unwrap_or(ANTICHESS_FEN),
+                MoveRules::Antichess,
+            )?),
+            _ => None,
+        };
+        let time_control = config.time_control.clone();
+        let base_ms = time_control.as_ref().map_or(0, |tc| tc.base_ms);
+        let start_turn = match position.map_or(board.side_to_move(), |p| p.side_to_move()) {
+            Color::White => PlayerColor::White,
+            Color::Black => PlayerColor::Black,
+        };
+
+        let mut game = Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            board,
+            variant: config.variant,
+            castling,
+            checks: CheckCount::default(),
+            reserves,
+            position,
             moves: Vec::new(),
-            white_time_ms: time_control_ms,
-            black_time_ms: time_control_ms,
+            time_control,
+            start_fen: None,
+            first_move_number,
+            start_turn,
+            white_time_ms: base_ms,
+            black_time_ms: base_ms,
             last_move_time: Self::current_time_ms(),
+            started_at: Self::current_time_ms(),
             game_over: false,
             result: None,
+            position_history: Vec::new(),
+            halfmove_clock,
+            draw_offer: None,
+            white_last_draw_offer: None,
+            black_last_draw_offer: None,
+            takeback_request: None,
+            snapshots: Vec::new(),
+            abandoned_by: None,
+        };
+
+        if config.start_fen.is_some() || board != Board::default() {
+            game.start_fen = Some(game.get_fen());
         }
+        game.position_history.push(game.position_key());
+        Ok(game)
+    }
+
+    // Rebuilds a stored game by replaying its moves, restoring the clocks and
+    // move times recorded with each. The side to move's clock resumes from now,
+    // so time the server spent down is not charged to anyone
+    pub fn restore(stored: &StoredGame, moves: &[StoredMove]) -> Result<Self, String> {
+        let mut game = Self::new(&stored.config)?;
+        game.id = stored.id.clone();
+
+        for stored_move in moves {
+            let record = &stored_move.record;
+            match record.from.strip_suffix('@') {
+                Some(piece) => game.drop_piece(piece, &record.to)?,
+                None => {
+                    let promotion = record
+                        .san
+                        .split_once('=')
+                        .and_then(|(_, piece)| piece.chars().next())
+                        .map(|piece| piece.to_ascii_lowercase().to_string());
+                    game.make_move(&record.from, &record.to, promotion.as_deref())?
+                }
+            };
+
+            game.white_time_ms = stored_move.white_time_ms;
+            game.black_time_ms = stored_move.black_time_ms;
+            if let Some(last) = game.moves.last_mut() {
+                last.timestamp = record.timestamp;
+            }
+        }
+
+        game.started_at = stored.started_at;
+        Ok(game)
     }
 
     pub fn make_move(
@@ -38,11 +195,7 @@ impl GameState {
         to_str: &str,
         promotion: Option<&str>,
     ) -> Result<String, String> {
-        if self.game_over {
-            return Err("Game is over".to_string());
-        }
-
-        self.update_time();
+        let snapshot = self.begin_move()?;
 
         let from = Square::from_str(from_str).map_err(|_| "Invalid from square")?;
         let to = Square::from_str(to_str).map_err(|_| "Invalid to square")?;
@@ -53,6 +206,7 @@ impl GameState {
                 "r" => Some(Piece::Rook),
                 "b" => Some(Piece::Bishop),
                 "n" => Some(Piece::Knight),
+                "k" => Some(Piece::King), // Antichess only
                 _ => None,
             }
         } else {
@@ -61,135 +215,806 @@ impl GameState {
 
         let chess_move = ChessMove::new(from, to, promotion_piece);
 
-        if !self.board.legal(chess_move) {
-            return Err("Illegal move".to_string());
+        if let Some(position) = self.position {
+            if !position.legal_moves().contains(&chess_move) {
+                return Err("Illegal move".to_string());
+            }
+            let resets_clock =
+                position.piece_on(from) == Some(Piece::Pawn) || position.is_capture(chess_move);
+            let san = position.san(chess_move);
+            self.position = Some(position.make_move(chess_move));
+            let board = self.board;
+            return Ok(self.finish_move(snapshot, board, san, resets_clock, from_str, to_str));
+        }
+
+        // Chess960 castling is not known to the board: the king is dropped on
+        // its own rook, or on its destination when that is no ordinary move
+        let castle = self.castling.as_ref().and_then(|castling| {
+            let onto_own_rook = self.board.piece_on(to) == Some(Piece::Rook)
+                && self.board.color_on(to) == Some(self.board.side_to_move());
+            if onto_own_rook || !self.board.legal(chess_move) {
+                castling.castle(&self.board, from, to)
+            } else {
+                None
+            }
+        });
+
+        let (next, san, resets_clock) = match castle {
+            Some((next, side)) => (next, chess960::castle_san(side, &next), false),
+            None => {
+                if !self.rules().is_legal(&self.board, chess_move) {
+                    return Err("Illegal move".to_string());
+                }
+                let resets_clock = self.board.piece_on(from) == Some(Piece::Pawn)
+                    || self.board.piece_on(to).is_some();
+                if let Some(reserves) = &mut self.reserves {
+                    reserves.record_move(&self.board, chess_move);
+                }
+                (
+                    self.board.make_move_new(chess_move),
+                    move_to_san(&self.board, &chess_move),
+                    resets_clock,
+                )
+            }
+        };
+
+        if let Some(castling) = &mut self.castling {
+            castling.update(&self.board, from, to);
+        }
+
+        Ok(self.finish_move(snapshot, next, san, resets_clock, from_str, to_str))
+    }
+
+    // Crazyhouse: places a piece from the mover's pocket on an empty square
+    pub fn drop_piece(&mut self, piece: &str, square_str: &str) -> Result<String, String> {
+        let snapshot = self.begin_move()?;
+
+        let Some(reserves) = &mut self.reserves else {
+            return Err("Pieces can only be dropped in Crazyhouse".to_string());
+        };
+        let piece = piece
+            .chars()
+            .next()
+            .and_then(crazyhouse::piece_from_char)
+            .ok_or("Invalid piece")?;
+        let square = Square::from_str(square_str).map_err(|_| "Invalid square")?;
+
+        let next = reserves.drop_piece(&self.board, piece, square)?;
+        let san = crazyhouse::drop_san(piece, square);
+        let from = format!("{}@", &san[..1]);
+
+        Ok(self.finish_move(snapshot, next, san, true, &from, square_str))
+    }
+
+    // Checks a move may be played at all and snapshots the position before it
+    fn begin_move(&mut self) -> Result<Snapshot, String> {
+        if self.game_over {
+            return Err("Game is over".to_string());
+        }
+
+        let snapshot = Snapshot {
+            board: self.board,
+            white_time_ms: self.white_time_ms,
+            black_time_ms: self.black_time_ms,
+            halfmove_clock: self.halfmove_clock,
+            castling: self.castling,
+            checks: self.checks,
+            reserves: self.reserves,
+            position: self.position,
+        };
+
+        self.update_time();
+        if self.game_over {
+            return Err("Time is up".to_string());
         }
 
-        let san = self.move_to_san(&chess_move);
+        Ok(snapshot)
+    }
 
-        self.board = self.board.make_move_new(chess_move);
+    // Records a validated move or drop leading to `next`, which is the board
+    // unchanged when a `Position` is played instead; `from` and `to` together
+    // give the move in UCI form. Returns the SAN with its check suffix
+    fn finish_move(
+        &mut self,
+        snapshot: Snapshot,
+        next: Board,
+        san: String,
+        resets_clock: bool,
+        from: &str,
+        to: &str,
+    ) -> String {
+        self.settle_clock();
+
+        if self.draw_offer == Some(self.current_turn().opponent()) {
+            self.draw_offer = None;
+        }
+        self.takeback_request = None;
+        self.snapshots.push(snapshot);
+
+        if resets_clock {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
+        self.board = next;
+        self.position_history.push(self.position_key());
+
+        let gives_check = self.in_check();
+        if gives_check {
+            match self.current_turn() {
+                PlayerColor::White => self.checks.black += 1,
+                PlayerColor::Black => self.checks.white += 1,
+            }
+        }
+
+        // Whether a check is mate depends on the variant, e.g. on drops;
+        // a `Position` already marks its own checks
+        let mut san = san;
+        if self.position.is_none() {
+            san.truncate(san.trim_end_matches(['+', '#']).len());
+        }
+        if gives_check && self.position.is_none() {
+            san.push(if self.rules().has_legal_move(self) {
+                '+'
+            } else {
+                '#'
+            });
+        }
 
         let move_record = MoveRecord {
             san: san.clone(),
-            from: from_str.to_string(),
-            to: to_str.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
             timestamp: Self::current_time_ms(),
         };
 
         self.moves.push(move_record);
         self.last_move_time = Self::current_time_ms();
 
-        if self.board.status() != chess::BoardStatus::Ongoing {
+        if let Some(result) = self.determine_result() {
             self.game_over = true;
-            self.result = Some(self.determine_result());
+            self.result = Some(result);
+        }
+
+        san
+    }
+
+    // Ends the game as a draw if the side claiming it is entitled to by
+    // threefold repetition, the fifty-move rule or an abandoning opponent
+    pub fn claim_draw(&mut self, color: PlayerColor) -> Result<GameResult, String> {
+        if self.game_over {
+            return Err("Game is over".to_string());
         }
 
-        Ok(san)
+        let result = if self.abandoned_by == Some(color.opponent()) {
+            GameResult::DrawByAbandonment
+        } else if self.repetition_count() >= 3 {
+            GameResult::Repetition
+        } else if self.halfmove_clock >= 100 {
+            GameResult::FiftyMoveRule
+        } else {
+            return Err("No draw can be claimed in this position".to_string());
+        };
+
+        self.game_over = true;
+        self.result = Some(result.clone());
+        Ok(result)
+    }
+
+    pub fn resign(&mut self, color: PlayerColor) -> Result<GameResult, String> {
+        self.end_game(GameResult::Resignation {
+            winner: color.opponent(),
+        })
+    }
+
+    // Called once a disconnected player's grace period has run out
+    pub fn mark_abandoned(&mut self, color: PlayerColor) -> bool {
+        if self.game_over {
+            return false;
+        }
+
+        self.abandoned_by = Some(color);
+        true
+    }
+
+    pub fn claim_victory(&mut self, color: PlayerColor) -> Result<GameResult, String> {
+        if self.abandoned_by != Some(color.opponent()) {
+            return Err("Your opponent has not abandoned the game".to_string());
+        }
+
+        self.end_game(GameResult::Abandoned { winner: color })
     }
 
+    fn end_game(&mut self, result: GameResult) -> Result<GameResult, String> {
+        if self.game_over {
+            return Err("Game is over".to_string());
+        }
+
+        self.game_over = true;
+        self.result = Some(result.clone());
+        Ok(result)
+    }
+
+    pub fn offer_draw(&mut self, color: PlayerColor) -> Result<(), String> {
+        if self.game_over {
+            return Err("Game is over".to_string());
+        }
+        if self.draw_offer.is_some() {
+            return Err("A draw offer is already pending".to_string());
+        }
+
+        let ply = self.moves.len();
+        let last_offer = match color {
+            PlayerColor::White => &mut self.white_last_draw_offer,
+            PlayerColor::Black => &mut self.black_last_draw_offer,
+        };
+        if last_offer.is_some_and(|last| ply < last + DRAW_OFFER_INTERVAL_PLIES) {
+            return Err("You offered a draw too recently".to_string());
+        }
+
+        *last_offer = Some(ply);
+        self.draw_offer = Some(color);
+        Ok(())
+    }
+
+    pub fn accept_draw(&mut self, color: PlayerColor) -> Result<GameResult, String> {
+        if self.game_over {
+            return Err("Game is over".to_string());
+        }
+        if self.draw_offer != Some(color.opponent()) {
+            return Err("There is no draw offer to accept".to_string());
+        }
+
+        self.draw_offer = None;
+        self.game_over = true;
+        self.result = Some(GameResult::DrawByAgreement);
+        Ok(GameResult::DrawByAgreement)
+    }
+
+    pub fn decline_draw(&mut self, color: PlayerColor) -> Result<(), String> {
+        if self.draw_offer != Some(color.opponent()) {
+            return Err("There is no draw offer to decline".to_string());
+        }
+
+        self.draw_offer = None;
+        Ok(())
+    }
+
+    pub fn request_takeback(&mut self, color: PlayerColor) -> Result<(), String> {
+        if self.game_over {
+            return Err("Game is over".to_string());
+        }
+        if self.takeback_request.is_some() {
+            return Err("A takeback request is already pending".to_string());
+        }
+        if self.takeback_plies(color) == 0 {
+            return Err("You have no move to take back".to_string());
+        }
+
+        self.takeback_request = Some(color);
+        Ok(())
+    }
+
+    pub fn accept_takeback(&mut self, color: PlayerColor) -> Result<(), String> {
+        if self.game_over {
+            return Err("Game is over".to_string());
+        }
+        if self.takeback_request != Some(color.opponent()) {
+            return Err("There is no takeback request to accept".to_string());
+        }
+
+        let plies = self.takeback_plies(color.opponent());
+        self.undo(plies);
+        Ok(())
+    }
+
+    pub fn decline_takeback(&mut self, color: PlayerColor) -> Result<(), String> {
+        if self.takeback_request != Some(color.opponent()) {
+            return Err("There is no takeback request to decline".to_string());
+        }
+
+        self.takeback_request = None;
+        Ok(())
+    }
+
+    // Plies to undo so that it is `color`'s turn again, before their last move
+    fn takeback_plies(&self, color: PlayerColor) -> usize {
+        let plies = if self.current_turn() == color { 2 } else { 1 };
+        if self.snapshots.len() >= plies {
+            plies
+        } else {
+            0
+        }
+    }
+
+    fn undo(&mut self, plies: usize) {
+        let keep = self.snapshots.len() - plies;
+        let Some(snapshot) = self.snapshots.drain(keep..).next() else {
+            return;
+        };
+
+        self.board = snapshot.board;
+        self.white_time_ms = snapshot.white_time_ms;
+        self.black_time_ms = snapshot.black_time_ms;
+        self.halfmove_clock = snapshot.halfmove_clock;
+        self.castling = snapshot.castling;
+        self.checks = snapshot.checks;
+        self.reserves = snapshot.reserves;
+        self.position = snapshot.position;
+        self.moves.truncate(keep);
+        self.position_history.truncate(keep + 1);
+        self.last_move_time = Self::current_time_ms();
+        self.draw_offer = None;
+        self.takeback_request = None;
+    }
+
+    pub fn repetition_count(&self) -> usize {
+        let current = self.position_key();
+        self.position_history
+            .iter()
+            .filter(|&&hash| hash == current)
+            .count()
+    }
+
+    // Flags the side to move if its clock has run out
     pub fn update_time(&mut self) {
-        let current_time = Self::current_time_ms();
-        let elapsed = current_time.saturating_sub(self.last_move_time);
-
-        match self.board.side_to_move() {
-            Color::White => {
-                if self.white_time_ms > elapsed {
-                    self.white_time_ms -= elapsed;
-                } else {
-                    self.white_time_ms = 0;
-                    self.game_over = true;
-                    self.result = Some(GameResult::Timeout {
-                        winner: PlayerColor::Black,
-                    });
-                }
-            }
-            Color::Black => {
-                if self.black_time_ms > elapsed {
-                    self.black_time_ms -= elapsed;
-                } else {
-                    self.black_time_ms = 0;
-                    self.game_over = true;
-                    self.result = Some(GameResult::Timeout {
-                        winner: PlayerColor::White,
-                    });
-                }
+        if self.game_over || self.time_control.is_none() {
+            return;
+        }
+
+        let turn = self.current_turn();
+        if self.remaining_time(turn) == 0 {
+            match turn {
+                PlayerColor::White => self.white_time_ms = 0,
+                PlayerColor::Black => self.black_time_ms = 0,
             }
+            let winner = turn.opponent();
+            self.game_over = true;
+            self.result = if self.rules().can_win(self, to_chess_color(winner)) {
+                Some(GameResult::Timeout { winner })
+            } else {
+                Some(GameResult::TimeoutVsInsufficientMaterial)
+            };
         }
     }
 
-    pub fn get_fen(&self) -> String {
-        format!("{}", self.board)
+    // Milliseconds until the side to move runs out of time, if the clock is running
+    pub fn time_until_flag(&self) -> Option<u64> {
+        let time_control = self.time_control.as_ref()?;
+        if self.game_over {
+            return None;
+        }
+
+        let stored = match self.current_turn() {
+            PlayerColor::White => self.white_time_ms,
+            PlayerColor::Black => self.black_time_ms,
+        };
+        let delay = match time_control.delay {
+            Some(Delay::Simple { ms }) => ms,
+            _ => 0,
+        };
+        let elapsed = Self::current_time_ms().saturating_sub(self.last_move_time);
+        Some((stored + delay).saturating_sub(elapsed))
     }
 
-    pub fn current_turn(&self) -> PlayerColor {
-        match self.board.side_to_move() {
-            Color::White => PlayerColor::White,
-            Color::Black => PlayerColor::Black,
-        }
+    // Restarts the running clock, e.g. once the second player has joined
+    pub fn start_clock(&mut self) {
+        self.last_move_time = Self::current_time_ms();
+        self.started_at = self.last_move_time;
     }
 
-    fn determine_result(&self) -> GameResult {
-        match self.board.status() {
-            chess::BoardStatus::Checkmate => match self.board.side_to_move() {
-                Color::White => GameResult::BlackWins,
-                Color::Black => GameResult::WhiteWins,
-            },
-            chess::BoardStatus::Stalemate => GameResult::Draw,
-            _ => GameResult::Draw,
+    // Clock of `color` right now; the stored value is only brought up to date
+    // when that player completes a move
+    pub fn remaining_time(&self, color: PlayerColor) -> u64 {
+        let stored = match color {
+            PlayerColor::White => self.white_time_ms,
+            PlayerColor::Black => self.black_time_ms,
+        };
+        if color != self.current_turn() || self.game_over {
+            return stored;
         }
+
+        let Some(time_control) = &self.time_control else {
+            return stored;
+        };
+        let elapsed = Self::current_time_ms().saturating_sub(self.last_move_time);
+        let charged = match time_control.delay {
+            Some(Delay::Simple { ms }) => elapsed.saturating_sub(ms),
+            _ => elapsed,
+        };
+        stored.saturating_sub(charged)
     }
 
-    // Simplified SAN notation generator
-    fn move_to_san(&self, chess_move: &ChessMove) -> String {
-        let piece = self.board.piece_on(chess_move.get_source());
-        let capture = self.board.piece_on(chess_move.get_dest()).is_some();
+    // Charges the mover for the time used and applies delay refunds,
+    // increments and stage bonuses
+    fn settle_clock(&mut self) {
+        let Some(time_control) = &self.time_control else {
+            return;
+        };
 
-        let mut san = String::new();
+        let mover = self.current_turn();
+        let elapsed = Self::current_time_ms().saturating_sub(self.last_move_time);
+        let stored = match mover {
+            PlayerColor::White => self.white_time_ms,
+            PlayerColor::Black => self.black_time_ms,
+        };
+        let clock = clock_after_move(time_control, stored, elapsed, self.moves.len());
 
-        if let Some(p) = piece {
-            match p {
-                Piece::King => san.push('K'),
-                Piece::Queen => san.push('Q'),
-                Piece::Rook => san.push('R'),
-                Piece::Bishop => san.push('B'),
-                Piece::Knight => san.push('N'),
-                Piece::Pawn => {
-                    if capture {
-                        san.push(
-                            format!("{}", chess_move.get_source())
-                                .chars()
-                                .next()
-                                .unwrap(),
-                        );
-                    }
-                }
+        match mover {
+            PlayerColor::White => self.white_time_ms = clock,
+            PlayerColor::Black => self.black_time_ms = clock,
+        }
+    }
+
+    pub fn get_fen(&self) -> String {
+        let position = match &self.position {
+            Some(position) => position.to_string(),
+            None => self.board.to_string(),
+        };
+        let fen = fen_with_counters(&position, self.halfmove_clock, self.fullmove_number());
+        match &self.castling {
+            Some(castling) => {
+                let mut fields: Vec<String> = fen.split(' ').map(str::to_string).collect();
+                fields[2] = castling.xfen(&self.board);
+                fields.join(" ")
             }
+            None => fen,
         }
+    }
 
-        if capture {
-            san.push('x');
+    // The position, clocks and variant counters sent to everyone in the room
+    pub fn state_message(&self) -> ServerMessage {
+        ServerMessage::GameState {
+            fen: self.get_fen(),
+            moves: self.moves.clone(),
+            white_time: self.remaining_time(PlayerColor::White),
+            black_time: self.remaining_time(PlayerColor::Black),
+            current_turn: self.current_turn(),
+            checks: (self.variant == Variant::ThreeCheck).then_some(self.checks),
+            pockets: self.reserves.map(|reserves| reserves.pockets),
         }
+    }
 
-        san.push_str(&format!("{}", chess_move.get_dest()));
+    // Identifies a position for repetition, including castling rights and
+    // pockets the board does not track itself
+    fn position_key(&self) -> u64 {
+        self.position
+            .map_or(self.board.get_hash(), |position| position.hash())
+            ^ self.castling.map_or(0, |castling| castling.hash())
+            ^ self.reserves.map_or(0, |reserves| reserves.hash())
+    }
+
+    // Fullmove number of the current position, counting on from the start FEN
+    pub fn fullmove_number(&self) -> u32 {
+        let plies = self.moves.len() as u32 + u32::from(self.start_turn == PlayerColor::Black);
+        self.first_move_number + plies / 2
+    }
 
-        if let Some(promo) = chess_move.get_promotion() {
-            san.push('=');
-            match promo {
-                Piece::Queen => san.push('Q'),
-                Piece::Rook => san.push('R'),
-                Piece::Bishop => san.push('B'),
-                Piece::Knight => san.push('N'),
-                _ => {}
+    pub fn current_turn(&self) -> PlayerColor {
+        let side = self
+            .position
+            .map_or(self.board.side_to_move(), |p| p.side_to_move());
+        match side {
+            Color::White => PlayerColor::White,
+            Color::Black => PlayerColor::Black,
+        }
+    }
+
+    pub fn rules(&self) -> &'static dyn Rules {
+        variants::rules(self.variant)
+    }
+
+    // Whether the side to move is in check
+    pub fn in_check(&self) -> bool {
+        match &self.position {
+            Some(position) => position.in_check(),
+            None => self.board.checkers().popcnt() > 0,
+        }
+    }
+
+    fn determine_result(&self) -> Option<GameResult> {
+        let rules = self.rules();
+        if let Some(result) = rules.outcome(self) {
+            return Some(result);
+        }
+
+        if !rules.has_legal_move(self) {
+            if !self.in_check() {
+                return Some(GameResult::Stalemate);
             }
+            return match self.current_turn() {
+                PlayerColor::White => Some(GameResult::BlackWins),
+                PlayerColor::Black => Some(GameResult::WhiteWins),
+            };
         }
 
-        san
+        if rules.insufficient_material(self) {
+            Some(GameResult::InsufficientMaterial)
+        } else if self.repetition_count() >= 5 {
+            Some(GameResult::Repetition)
+        } else if self.halfmove_clock >= 150 {
+            Some(GameResult::FiftyMoveRule)
+        } else {
+            None
+        }
     }
 
-    fn current_time_ms() -> u64 {
+    pub fn current_time_ms() -> u64 {
         SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_millis() as u64
     }
 }
+
+// Validates a custom starting position, returning it with its halfmove clock
+// and fullmove number
+#[cfg(feature = "ssr")]
+fn parse_start_fen(fen: &str) -> Result<(Board, u32, u32), String> {
+    let fen = fen.trim();
+    let board = Board::from_str(fen).map_err(|err| match err {
+        chess::Error::InvalidBoard => {
+            "Invalid starting position: each side needs one king and the side not to move \
+             cannot be in check"
+                .to_string()
+        }
+        _ => format!("Invalid starting position: {} is not a valid FEN", fen),
+    })?;
+
+    let pawns = *board.pieces(Piece::Pawn);
+    let back_ranks = chess::get_rank(chess::Rank::First) | chess::get_rank(chess::Rank::Eighth);
+    if pawns & back_ranks != chess::EMPTY {
+        return Err("Invalid starting position: pawns on the first or last rank".to_string());
+    }
+    if board.status() != chess::BoardStatus::Ongoing {
+        return Err("Invalid starting position: the game is already over".to_string());
+    }
+
+    let (halfmove_clock, fullmove_number) = parse_move_counters(fen)?;
+    Ok((board, halfmove_clock, fullmove_number))
+}
+
+// The halfmove clock and fullmove number of a FEN, defaulting to "0 1"
+#[cfg(feature = "ssr")]
+fn parse_move_counters(fen: &str) -> Result<(u32, u32), String> {
+    let mut counters = fen.split_whitespace().skip(4);
+    let halfmove_clock = match counters.next() {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("Invalid halfmove clock in starting position: {}", value))?,
+        None => 0,
+    };
+    let fullmove_number =
+        match counters.next() {
+            Some(value) => value.parse().ok().filter(|&n| n > 0).ok_or_else(|| {
+                format!("Invalid fullmove number in starting position: {}", value)
+            })?,
+            None => 1,
+        };
+
+    Ok((halfmove_clock, fullmove_number))
+}
+
+// Validates a custom Atomic or Antichess starting position
+#[cfg(feature = "ssr")]
+fn parse_start_position(fen: &str, rules: MoveRules) -> Result<Position, String> {
+    let position = Position::from_fen(fen, rules)
+        .map_err(|err| format!("Invalid starting position: {}", err))?;
+    if position.legal_moves().is_empty() {
+        return Err("Invalid starting position: the game is already over".to_string());
+    }
+    Ok(position)
+}
+
+// Boards always write "0 1" for the move counters
+#[cfg(feature = "ssr")]
+fn fen_with_counters(fen: &str, halfmove_clock: u32, fullmove_number: u32) -> String {
+    let position: Vec<&str> = fen.split_whitespace().take(4).collect();
+    format!(
+        "{} {} {}",
+        position.join(" "),
+        halfmove_clock,
+        fullmove_number
+    )
+}
+
+// Clock of the player who just made ply `ply` (0-based) after spending
+// `elapsed` ms on it, with delay refunds, increments and stage bonuses applied
+#[cfg(feature = "ssr")]
+pub fn clock_after_move(time_control: &TimeControl, stored: u64, elapsed: u64, ply: usize) -> u64 {
+    let charged = match time_control.delay {
+        Some(Delay::Simple { ms }) => elapsed.saturating_sub(ms),
+        _ => elapsed,
+    };
+    let mut clock = stored.saturating_sub(charged);
+
+    if let Some(Delay::Bronstein { ms }) = time_control.delay {
+        clock += elapsed.min(ms);
+    }
+    clock += time_control.increment_ms;
+
+    let moves_made = (ply as u32 + 2) / 2;
+    for stage in &time_control.stages {
+        if stage.after_moves == moves_made {
+            clock += stage.added_ms;
+        }
+    }
+
+    clock
+}
+
+// Neither side can ever deliver mate: bare kings, a single minor piece, or
+// bishops that all stand on squares of the same colour
+#[cfg(feature = "ssr")]
+pub(crate) fn insufficient_material(board: &Board) -> bool {
+    let heavy = board.pieces(Piece::Pawn) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen);
+    if heavy.popcnt() > 0 {
+        return false;
+    }
+
+    let knights = board.pieces(Piece::Knight).popcnt();
+    let bishops = *board.pieces(Piece::Bishop);
+
+    if knights + bishops.popcnt() <= 1 {
+        return true;
+    }
+
+    let light_squares = bishops
+        .filter(|sq| (sq.get_file().to_index() + sq.get_rank().to_index()) % 2 == 1)
+        .count();
+    knights == 0 && (light_squares == 0 || light_squares == bishops.popcnt() as usize)
+}
+
+// Whether `color` still has enough material to mate at all: anything beyond
+// a lone king or a king with a single minor piece
+#[cfg(feature = "ssr")]
+pub(crate) fn can_mate(board: &Board, color: Color) -> bool {
+    let own = board.color_combined(color);
+    let minors = (board.pieces(Piece::Knight) | board.pieces(Piece::Bishop)) & own;
+    let others =
+        (board.pieces(Piece::Pawn) | board.pieces(Piece::Rook) | board.pieces(Piece::Queen)) & own;
+    others.popcnt() > 0 || minors.popcnt() > 1
+}
+
+#[cfg(feature = "ssr")]
+fn to_chess_color(color: PlayerColor) -> Color {
+    match color {
+        PlayerColor::White => Color::White,
+        PlayerColor::Black => Color::Black,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::{RoomConfig, Variant};
+
+    fn game(start_fen: Option<&str>) -> GameState {
+        GameState::new(&RoomConfig {
+            allow_takebacks: false,
+            time_control: None,
+            start_fen: start_fen.map(str::to_string),
+            variant: Variant::Standard,
+        })
+        .unwrap()
+    }
+
+    fn play(game: &mut GameState, moves: &[(&str, &str)]) {
+        for (from, to) in moves {
+            game.make_move(from, to, None).unwrap();
+        }
+    }
+
+    const KNIGHT_SHUFFLE: [(&str, &str); 4] =
+        [("g1", "f3"), ("g8", "f6"), ("f3", "g1"), ("f6", "g8")];
+
+    #[test]
+    fn threefold_repetition_can_be_claimed() {
+        let mut game = game(None);
+        play(&mut game, &KNIGHT_SHUFFLE);
+        assert_eq!(game.repetition_count(), 2);
+        assert!(game.claim_draw(PlayerColor::White).is_err());
+
+        play(&mut game, &KNIGHT_SHUFFLE);
+        assert_eq!(game.repetition_count(), 3);
+        assert!(!game.game_over);
+        assert!(matches!(
+            game.claim_draw(PlayerColor::Black),
+            Ok(GameResult::Repetition)
+        ));
+        assert!(game.claim_draw(PlayerColor::White).is_err());
+    }
+
+    #[test]
+    fn fivefold_repetition_ends_the_game() {
+        let mut game = game(None);
+        for _ in 0..3 {
+            play(&mut game, &KNIGHT_SHUFFLE);
+        }
+        assert!(!game.game_over);
+        play(&mut game, &KNIGHT_SHUFFLE);
+        assert!(matches!(game.result, Some(GameResult::Repetition)));
+    }
+
+    #[test]
+    fn repetition_counts_castling_rights() {
+        let mut game = game(Some("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"));
+        let rooks = [("h1", "h2"), ("h8", "h7"), ("h2", "h1"), ("h7", "h8")];
+        play(&mut game, &rooks);
+        play(&mut game, &rooks);
+        // The first time round both sides could still castle short
+        assert_eq!(game.repetition_count(), 2);
+    }
+
+    #[test]
+    fn repetition_counts_en_passant_rights() {
+        let mut game = game(Some("4k3/8/8/8/1p6/8/P7/4K3 w - - 0 1"));
+        play(&mut game, &[("a2", "a4")]);
+        let kings = [("e8", "d8"), ("e1", "d1"), ("d8", "e8"), ("d1", "e1")];
+        play(&mut game, &kings);
+        // After a4 Black could take en passant, so that position differs
+        assert_eq!(game.repetition_count(), 1);
+    }
+
+    #[test]
+    fn fifty_move_rule_can_be_claimed() {
+        let mut game = game(Some("4k3/8/8/8/8/8/R7/4K3 w - - 98 80"));
+        play(&mut game, &[("a2", "a3")]);
+        assert!(game.claim_draw(PlayerColor::White).is_err());
+        play(&mut game, &[("e8", "d8")]);
+        assert!(matches!(
+            game.claim_draw(PlayerColor::White),
+            Ok(GameResult::FiftyMoveRule)
+        ));
+    }
+
+    #[test]
+    fn seventy_five_move_rule_ends_the_game() {
+        let mut game = game(Some("4k3/8/8/8/8/8/R7/4K3 w - - 148 80"));
+        play(&mut game, &[("a2", "a3")]);
+        assert!(!game.game_over);
+        play(&mut game, &[("e8", "d8")]);
+        assert!(matches!(game.result, Some(GameResult::FiftyMoveRule)));
+    }
+
+    #[test]
+    fn insufficient_material_positions() {
+        let drawn = [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+            "2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+        ];
+        let playable = [
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "3bk3/8/8/8/8/8/8/4KB2 w - - 0 1",
+            "4kn2/8/8/8/8/8/8/4KN2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KBN1 w - - 0 1",
+        ];
+        for fen in drawn {
+            assert!(
+                insufficient_material(&Board::from_str(fen).unwrap()),
+                "{}",
+                fen
+            );
+        }
+        for fen in playable {
+            assert!(
+                !insufficient_material(&Board::from_str(fen).unwrap()),
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn capturing_down_to_insufficient_material_draws() {
+        let mut game = game(Some("4k3/8/8/8/8/8/3r4/4KB2 w - - 0 1"));
+        play(&mut game, &[("e1", "d2")]);
+        assert!(matches!(
+            game.result,
+            Some(GameResult::InsufficientMaterial)
+        ));
+    }
+}
@@ -13,7 +13,7 @@ mod components;
 pub mod shared;
 
 #[cfg(feature = "hydrate")]
-use components::{Game, Home};
+use components::{Analysis, Game, Home, MyGames};
 
 #[cfg(feature = "hydrate")]
 #[component]
@@ -23,6 +23,8 @@ pub fn App() -> impl IntoView {
             <main>
                 <Routes fallback=|| "Not found">
                     <Route path=StaticSegment("") view=Home />
+                    <Route path=StaticSegment("analysis") view=Analysis />
+                    <Route path=StaticSegment("games") view=MyGames />
                     <Route path=(StaticSegment("game"), ParamSegment("room_code")) view=Game />
                 </Routes>
             </main>
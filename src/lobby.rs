@@ -0,0 +1,84 @@
+#[cfg(feature = "ssr")]
+use crate::matchmaking::MatchPlayer;
+#[cfg(feature = "ssr")]
+use crate::shared::{DirectChallenge, OpenChallenge};
+#[cfg(feature = "ssr")]
+use std::collections::HashSet;
+
+// A challenge in the lobby and who posted it
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub struct PostedChallenge {
+    pub poster: MatchPlayer,
+    pub challenge: OpenChallenge,
+}
+
+// A direct challenge waiting for its recipient's answer
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub struct PendingChallenge {
+    pub challenger: MatchPlayer,
+    pub recipient_id: String, // account id
+    pub challenge: DirectChallenge,
+}
+
+// Open challenges, oldest first, and the sockets watching them come and go
+#[cfg(feature = "ssr")]
+#[derive(Debug, Default)]
+pub struct Lobby {
+    challenges: Vec<PostedChallenge>,
+    watchers: HashSet<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl Lobby {
+    pub fn challenges(&self) -> Vec<OpenChallenge> {
+        self.challenges
+            .iter()
+            .map(|posted| posted.challenge.clone())
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&PostedChallenge> {
+        self.challenges
+            .iter()
+            .find(|posted| posted.challenge.id == id)
+    }
+
+    // A player has one challenge at a time; posting again replaces it. Returns
+    // the id of the replaced challenge
+    pub fn post(&mut self, posted: PostedChallenge) -> Option<String> {
+        let replaced = self.withdraw(&posted.poster.player_id);
+        self.challenges.push(posted);
+        replaced
+    }
+
+    pub fn take(&mut self, id: &str) -> Option<PostedChallenge> {
+        let index = self
+            .challenges
+            .iter()
+            .position(|posted| posted.challenge.id == id)?;
+        Some(self.challenges.remove(index))
+    }
+
+    // Removes the player's challenge, returning its id
+    pub fn withdraw(&mut self, player_id: &str) -> Option<String> {
+        let index = self
+            .challenges
+            .iter()
+            .position(|posted| posted.poster.player_id == player_id)?;
+        Some(self.challenges.remove(index).challenge.id)
+    }
+
+    pub fn watch(&mut self, player_id: &str) {
+        self.watchers.insert(player_id.to_string());
+    }
+
+    pub fn unwatch(&mut self, player_id: &str) {
+        self.watchers.remove(player_id);
+    }
+
+    pub fn watchers(&self) -> Vec<String> {
+        self.watchers.iter().cloned().collect()
+    }
+}
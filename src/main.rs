@@ -1,10 +1,17 @@
 #[cfg(feature = "ssr")]
 use axum::extract::{
     ws::{Message, WebSocket},
-    State, WebSocketUpgrade,
+    FromRef, Path, Query, State, WebSocketUpgrade,
 };
 #[cfg(feature = "ssr")]
-use axum::{response::IntoResponse, routing::get, Router};
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+#[cfg(feature = "ssr")]
+use axum_extra::extract::cookie::{Cookie, Key, SameSite, SignedCookieJar};
 #[cfg(feature = "ssr")]
 use futures_util::{SinkExt, StreamExt};
 #[cfg(feature = "ssr")]
@@ -12,48 +19,181 @@ use std::collections::HashMap;
 #[cfg(feature = "ssr")]
 use std::sync::Arc;
 #[cfg(feature = "ssr")]
+use std::time::Duration;
+#[cfg(feature = "ssr")]
 use tokio::sync::RwLock;
 #[cfg(feature = "ssr")]
 use tower_http::services::ServeDir;
 
+#[cfg(feature = "ssr")]
+mod accounts;
+#[cfg(feature = "ssr")]
+mod chat;
+#[cfg(feature = "ssr")]
+mod chess960;
+#[cfg(feature = "ssr")]
+mod crazyhouse;
+#[cfg(feature = "ssr")]
+mod eco;
 #[cfg(feature = "ssr")]
 mod game;
 #[cfg(feature = "ssr")]
+mod lobby;
+#[cfg(feature = "ssr")]
+mod matchmaking;
+#[cfg(feature = "ssr")]
+mod pgn;
+#[cfg(feature = "ssr")]
+mod rating;
+#[cfg(feature = "ssr")]
 pub mod shared;
+#[cfg(feature = "ssr")]
+mod storage;
+#[cfg(feature = "ssr")]
+mod variants;
 
+#[cfg(feature = "ssr")]
+use crate::chat::ChatLog;
 #[cfg(feature = "ssr")]
 use crate::game::GameState;
 #[cfg(feature = "ssr")]
+use crate::lobby::{Lobby, PendingChallenge, PostedChallenge};
+#[cfg(feature = "ssr")]
+use crate::matchmaking::{MatchPlayer, Seek, SeekQueue};
+#[cfg(feature = "ssr")]
+use crate::rating::Rating;
+#[cfg(feature = "ssr")]
 use crate::shared::*;
+#[cfg(feature = "ssr")]
+use crate::storage::{
+    ArchiveQuery, ArchivedGame, MemoryStorage, SqliteStorage, Storage, StorageWrite, StorageWriter,
+    StoredAccount, StoredGame, StoredMove, StoredRoom,
+};
 
 #[cfg(feature = "ssr")]
 type GameRooms = Arc<RwLock<HashMap<String, GameRoom>>>;
 #[cfg(feature = "ssr")]
+type LobbyChallenges = Arc<RwLock<Lobby>>;
+#[cfg(feature = "ssr")]
+type DirectChallenges = Arc<RwLock<HashMap<String, PendingChallenge>>>;
+#[cfg(feature = "ssr")]
 type GameStates = Arc<RwLock<HashMap<String, GameState>>>;
 #[cfg(feature = "ssr")]
-type PlayerSessions =
-    Arc<RwLock<HashMap<String, tokio::sync::mpsc::UnboundedSender<ServerMessage>>>>;
+type ChatLogs = Arc<RwLock<HashMap<String, ChatLog>>>;
+#[cfg(feature = "ssr")]
+type ClockTimers = Arc<RwLock<HashMap<String, tokio::task::JoinHandle<()>>>>;
+#[cfg(feature = "ssr")]
+type Seeks = Arc<RwLock<SeekQueue>>;
+#[cfg(feature = "ssr")]
+type PlayerSessions = Arc<RwLock<HashMap<String, PlayerSession>>>;
+
+// How long a disconnected player may take to rejoin before forfeiting
+#[cfg(feature = "ssr")]
+const DEFAULT_ABANDON_GRACE_SECS: u64 = 60;
+// How long a direct challenge waits for an answer
+#[cfg(feature = "ssr")]
+const DEFAULT_CHALLENGE_TIMEOUT_SECS: u64 = 60;
+#[cfg(feature = "ssr")]
+const DEFAULT_DATABASE_PATH: &str = "chess.db";
+#[cfg(feature = "ssr")]
+const DEFAULT_PAGE_SIZE: usize = 20;
+#[cfg(feature = "ssr")]
+const MAX_PAGE_SIZE: usize = 100;
+#[cfg(feature = "ssr")]
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+// How often waiting seeks are paired again as their rating windows widen
+#[cfg(feature = "ssr")]
+const MATCHMAKING_INTERVAL: Duration = Duration::from_secs(2);
+
+// A connected socket, by player id
+#[cfg(feature = "ssr")]
+struct PlayerSession {
+    sender: tokio::sync::mpsc::UnboundedSender<ServerMessage>,
+    account_id: Option<String>, // None for guests
+}
 
 #[cfg(feature = "ssr")]
 #[derive(Clone)]
 struct AppState {
     rooms: GameRooms,
+    lobby: LobbyChallenges,
+    challenges: DirectChallenges,
     games: GameStates,
+    chats: ChatLogs,
     sessions: PlayerSessions,
+    clock_timers: ClockTimers,
+    seeks: Seeks,
+    abandon_grace: Duration,
+    challenge_timeout: Duration,
+    storage: Arc<dyn Storage>,
+    writer: StorageWriter, // room and game writes, applied in the background
+    session_key: Key,      // signs session cookies
+}
+
+#[cfg(feature = "ssr")]
+impl FromRef<AppState> for Key {
+    fn from_ref(state: &AppState) -> Self {
+        state.session_key.clone()
+    }
 }
 #[cfg(feature = "ssr")]
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
 
+    let database_path =
+        std::env::var("DATABASE_PATH").unwrap_or_else(|_| DEFAULT_DATABASE_PATH.to_string());
+    let storage: Arc<dyn Storage> = match SqliteStorage::open(&database_path) {
+        Ok(storage) => Arc::new(storage),
+        Err(err) => {
+            tracing::error!(
+                "Could not open {}, games will not survive a restart: {}",
+                database_path,
+                err
+            );
+            Arc::new(MemoryStorage::default())
+        }
+    };
+
     let state = AppState {
         rooms: Arc::new(RwLock::new(HashMap::new())),
+        lobby: Arc::new(RwLock::new(Lobby::default())),
+        challenges: Arc::new(RwLock::new(HashMap::new())),
         games: Arc::new(RwLock::new(HashMap::new())),
+        chats: Arc::new(RwLock::new(HashMap::new())),
         sessions: Arc::new(RwLock::new(HashMap::new())),
+        clock_timers: Arc::new(RwLock::new(HashMap::new())),
+        seeks: Arc::new(RwLock::new(SeekQueue::default())),
+        abandon_grace: Duration::from_secs(
+            std::env::var("ABANDON_GRACE_SECS")
+                .ok()
+                .and_then(|secs| secs.parse().ok())
+                .unwrap_or(DEFAULT_ABANDON_GRACE_SECS),
+        ),
+        challenge_timeout: Duration::from_secs(
+            std::env::var("CHALLENGE_TIMEOUT_SECS")
+                .ok()
+                .and_then(|secs| secs.parse().ok())
+                .unwrap_or(DEFAULT_CHALLENGE_TIMEOUT_SECS),
+        ),
+        writer: StorageWriter::spawn(storage.clone()),
+        storage,
+        session_key: session_key(),
     };
 
+    restore_games(&state).await;
+    tokio::spawn(run_matchmaking(state.clone()));
+
     let app = Router::new()
         .route("/ws", get(ws_handler))
+        .route("/api/register", post(register_handler))
+        .route("/api/login", post(login_handler))
+        .route("/api/logout", post(logout_handler))
+        .route("/api/me", get(me_handler))
+        .route("/api/games", get(games_handler))
+        .route("/api/games/{file}", get(pgn_handler))
+        .route("/api/ratings/{username}", get(ratings_handler))
+        .route("/api/lobby", get(lobby_handler))
         .fallback_service(ServeDir::new("dist"))
         .with_state(state);
 
@@ -63,18 +203,448 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
+// Without a valid session cookie the socket belongs to a guest
+#[cfg(feature = "ssr")]
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    jar: SignedCookieJar,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let account = session_account(&jar, &state).await;
+    ws.on_upgrade(|socket| handle_socket(socket, account, state))
+}
+
+// `SESSION_SECRET` keeps logins valid across restarts; without it a fresh key
+// is made on every boot
+#[cfg(feature = "ssr")]
+fn session_key() -> Key {
+    match std::env::var("SESSION_SECRET") {
+        Ok(secret) => Key::try_from(secret.as_bytes()).unwrap_or_else(|_| {
+            tracing::error!("SESSION_SECRET must be at least 64 bytes, using a random key");
+            Key::generate()
+        }),
+        Err(_) => {
+            tracing::warn!("SESSION_SECRET is not set, logins will not survive a restart");
+            Key::generate()
+        }
+    }
+}
+
+// Runs a storage call on the blocking pool. SQLite calls wait for the
+// connection, which the background writer holds while it writes
+#[cfg(feature = "ssr")]
+async fn with_storage<T, F>(state: &AppState, call: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&dyn Storage) -> Result<T, String> + Send + 'static,
+{
+    let storage = state.storage.clone();
+    tokio::task::spawn_blocking(move || call(storage.as_ref()))
+        .await
+        .map_err(|err| format!("Storage task failed: {}", err))?
+}
+
+#[cfg(feature = "ssr")]
+async fn session_account(jar: &SignedCookieJar, state: &AppState) -> Option<Account> {
+    let token = jar.get(accounts::SESSION_COOKIE)?.value().to_string();
+    let now = GameState::current_time_ms();
+    match with_storage(state, move |storage| storage.session_account(&token, now)).await {
+        Ok(account) => account,
+        Err(err) => {
+            tracing::error!("Could not look up session: {}", err);
+            None
+        }
+    }
+}
+
+// Starts a session for the account and returns the cookie that carries it
+#[cfg(feature = "ssr")]
+async fn start_session(account: &Account, state: &AppState) -> Result<Cookie<'static>, String> {
+    let token = accounts::new_session_token();
+    let expires_at = GameState::current_time_ms() + accounts::SESSION_DAYS * DAY_MS;
+    let (session, account_id) = (token.clone(), account.id.clone());
+    with_storage(state, move |storage| {
+        storage.create_session(&session, &account_id, expires_at)
+    })
+    .await?;
+
+    Ok(Cookie::build((accounts::SESSION_COOKIE, token))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::days(accounts::SESSION_DAYS as i64))
+        .build())
+}
+
+#[cfg(feature = "ssr")]
+async fn register_handler(
+    jar: SignedCookieJar,
+    State(state): State<AppState>,
+    Json(request): Json<RegisterRequest>,
+) -> Response {
+    let username = request.username.trim().to_string();
+    let checked = accounts::validate_username(&username)
+        .and_then(|_| accounts::validate_password(&request.password))
+        .and_then(|_| accounts::display_name(request.display_name));
+    let display_name = match checked {
+        Ok(display_name) => display_name,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+
+    // Hashing is deliberately slow, so keep it off the async workers
+    let password = request.password;
+    let password_hash =
+        tokio::task::spawn_blocking(move || accounts::hash_password(&password)).await;
+    let password_hash = match password_hash {
+        Ok(Ok(hash)) => hash,
+        Ok(Err(err)) => {
+            tracing::error!("{}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        Err(err) => {
+            tracing::error!("Password hashing failed: {}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let stored = StoredAccount {
+        account: Account {
+            id: uuid::Uuid::new_v4().to_string(),
+            username,
+            display_name,
+        },
+        password_hash,
+        created_at: GameState::current_time_ms(),
+    };
+    let created = stored.clone();
+    match with_storage(&state, move |storage| storage.create_account(&created)).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return (StatusCode::CONFLICT, "That username is taken").into_response();
+        }
+        Err(err) => {
+            tracing::error!("Could not create account: {}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+    tracing::info!("Registered account {}", stored.account.username);
+
+    match start_session(&stored.account, &state).await {
+        Ok(cookie) => (jar.add(cookie), Json(stored.account)).into_response(),
+        Err(err) => {
+            tracing::error!("Could not start session: {}", err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+async fn login_handler(
+    jar: SignedCookieJar,
+    State(state): State<AppState>,
+    Json(request): Json<LoginRequest>,
+) -> Response {
+    let username = request.username.trim().to_string();
+    let stored = match with_storage(&state, move |storage| {
+        storage.account_by_username(&username)
+    })
+    .await
+    {
+        Ok(stored) => stored,
+        Err(err) => {
+            tracing::error!("Could not look up account: {}", err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let password = request.password;
+    let password_hash = stored.as_ref().map(|stored| stored.password_hash.clone());
+    let verified = tokio::task::spawn_blocking(move || {
+        password_hash.is_some_and(|hash| accounts::verify_password(&password, &hash))
+    })
+    .await
+    .unwrap_or(false);
+    let Some(stored) = stored.filter(|_| verified) else {
+        return (StatusCode::UNAUTHORIZED, "Wrong username or password").into_response();
+    };
+
+    match start_session(&stored.account, &state).await {
+        Ok(cookie) => (jar.add(cookie), Json(stored.account)).into_response(),
+        Err(err) => {
+            tracing::error!("Could not start session: {}", err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+async fn logout_handler(jar: SignedCookieJar, State(state): State<AppState>) -> Response {
+    if let Some(cookie) = jar.get(accounts::SESSION_COOKIE) {
+        let token = cookie.value().to_string();
+        if let Err(err) = with_storage(&state, move |storage| storage.delete_session(&token)).await
+        {
+            tracing::error!("Could not end session: {}", err);
+        }
+    }
+    let removal = Cookie::build(accounts::SESSION_COOKIE).path("/").build();
+    (jar.remove(removal), StatusCode::NO_CONTENT).into_response()
+}
+
+// The logged-in account, or 401 for guests
+#[cfg(feature = "ssr")]
+async fn me_handler(jar: SignedCookieJar, State(state): State<AppState>) -> Response {
+    match session_account(&jar, &state).await {
+        Some(account) => Json(account).into_response(),
+        None => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+// Serves `/api/games/{room_code}.pgn` for any game still held by the server,
+// and `/api/games/{game_id}.pgn` for archived games
+#[cfg(feature = "ssr")]
+async fn pgn_handler(Path(file): Path<String>, State(state): State<AppState>) -> Response {
+    let Some(key) = file.strip_suffix(".pgn") else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let (rated, white, black) = state
+        .rooms
+        .read()
+        .await
+        .get(key)
+        .map(|room| (room.rated, room.white_name.clone(), room.black_name.clone()))
+        .unwrap_or_default();
+    let live = state
+        .games
+        .read()
+        .await
+        .get(key)
+        .map(|game| pgn::export(game, key, rated, white.as_deref(), black.as_deref()));
+
+    let pgn = match live {
+        Some(pgn) => pgn,
+        None => match with_storage(&state, {
+            let id = key.to_string();
+            move |storage| storage.archived_game(&id)
+        })
+        .await
+        {
+            Ok(Some(archived)) => archived.pgn,
+            Ok(None) => return (StatusCode::NOT_FOUND, "Game not found").into_response(),
+            Err(err) => {
+                tracing::error!("Could not load archived game {}: {}", key, err);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        },
+    };
+
+    let filename: String = key
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    (
+        [
+            (header::CONTENT_TYPE, "application/x-chess-pgn".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.pgn\"", filename),
+            ),
+        ],
+        pgn,
+    )
+        .into_response()
+}
+
+// Query string of `GET /api/games`. Dates are "YYYY-MM-DD" and both ends are
+// inclusive; pages start at 1. `mine` picks the signed-in player's games in
+// place of `player`
+#[cfg(feature = "ssr")]
+#[derive(serde::Deserialize)]
+struct GamesQuery {
+    #[serde(default)]
+    mine: bool,
+    player: Option<String>,
+    result: Option<String>,
+    eco: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    time_control: Option<String>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+// Searches the archive of finished games, newest first
+#[cfg(feature = "ssr")]
+async fn games_handler(
+    jar: SignedCookieJar,
+    Query(query): Query<GamesQuery>,
+    State(state): State<AppState>,
+) -> Response {
+    let (since, until) = match (date_param(&query.from), date_param(&query.to)) {
+        (Ok(since), Ok(until)) => (since, until.map(|ms| ms + DAY_MS)),
+        (Err(message), _) | (_, Err(message)) => {
+            return (StatusCode::BAD_REQUEST, message).into_response();
+        }
+    };
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    // Names can be chosen freely, so a player is looked up by username and
+    // matched on their account
+    let account = match non_empty(query.player) {
+        _ if query.mine => match session_account(&jar, &state).await {
+            Some(account) => Some(account.id),
+            None => return StatusCode::UNAUTHORIZED.into_response(),
+        },
+        None => None,
+        Some(username) => match with_storage(&state, {
+            let username = username.clone();
+            move |storage| storage.account_by_username(&username)
+        })
+        .await
+        {
+            Ok(Some(stored)) => Some(stored.account.id),
+            Ok(None) => {
+                return Json(GamePage {
+                    games: Vec::new(),
+                    total: 0,
+                    page,
+                    per_page,
+                })
+                .into_response();
+            }
+            Err(err) => {
+                tracing::error!("Could not load account {}: {}", username, err);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        },
+    };
+    let search = ArchiveQuery {
+        account,
+        result: non_empty(query.result),
+        eco: non_empty(query.eco).map(|eco| eco.to_ascii_uppercase()),
+        time_control: non_empty(query.time_control),
+        since,
+        until,
+        offset: (page - 1) * per_page,
+        limit: per_page,
+    };
+    match with_storage(&state, move |storage| storage.search_archive(&search)).await {
+        Ok((games, total)) => Json(GamePage {
+            games,
+            total,
+            page,
+            per_page,
+        })
+        .into_response(),
+        Err(err) => {
+            tracing::error!("Could not search the game archive: {}", err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+// The lobby's open challenges, oldest first
+#[cfg(feature = "ssr")]
+async fn lobby_handler(State(state): State<AppState>) -> Response {
+    Json(state.lobby.read().await.challenges()).into_response()
+}
+
+// Query string of `GET /api/ratings/{username}`; without a category the
+// history covers all of them
+#[cfg(feature = "ssr")]
+#[derive(serde::Deserialize)]
+struct RatingsQuery {
+    category: Option<RatingCategory>,
+}
+
+// The player's current ratings and, oldest first, every rating they have had
+#[cfg(feature = "ssr")]
+async fn ratings_handler(
+    Path(username): Path<String>,
+    Query(query): Query<RatingsQuery>,
+    State(state): State<AppState>,
+) -> Response {
+    let lookup = with_storage(&state, {
+        let username = username.clone();
+        move |storage| {
+            let Some(stored) = storage.account_by_username(&username)? else {
+                return Ok(None);
+            };
+            let id = &stored.account.id;
+            let (ratings, history) = (storage.ratings(id)?, storage.rating_history(id)?);
+            Ok(Some((stored.account, ratings, history)))
+        }
+    })
+    .await;
+    let (account, ratings, history) = match lookup {
+        Ok(Some(found)) => found,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Player not found").into_response(),
+        Err(err) => {
+            tracing::error!("Could not load ratings of {}: {}", username, err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let mut ratings: Vec<CategoryRating> = ratings
+        .into_iter()
+        .filter(|(category, _)| query.category.is_none_or(|wanted| wanted == *category))
+        .map(|(category, rating)| CategoryRating {
+            category,
+            rating: rating.rating.round() as i32,
+            deviation: rating.deviation.round() as i32,
+            games: rating.games,
+            provisional: rating.is_provisional(),
+        })
+        .collect();
+    ratings.sort_by_key(|rating| rating.category);
+    let history = history
+        .into_iter()
+        .filter(|point| query.category.is_none_or(|wanted| wanted == point.category))
+        .collect();
+
+    Json(RatingProfile {
+        username: account.username,
+        display_name: account.display_name,
+        ratings,
+        history,
+    })
+    .into_response()
+}
+
+#[cfg(feature = "ssr")]
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
 #[cfg(feature = "ssr")]
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+fn date_param(date: &Option<String>) -> Result<Option<u64>, String> {
+    match non_empty(date.clone()) {
+        None => Ok(None),
+        Some(date) => pgn::parse_date(&date)
+            .map(Some)
+            .ok_or_else(|| format!("Invalid date \"{}\", expected YYYY-MM-DD", date)),
+    }
 }
 
 #[cfg(feature = "ssr")]
-async fn handle_socket(socket: WebSocket, state: AppState) {
+async fn handle_socket(socket: WebSocket, account: Option<Account>, state: AppState) {
     let (mut sender, mut receiver) = socket.split();
     let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
 
     let player_id = uuid::Uuid::new_v4().to_string();
-    state.sessions.write().await.insert(player_id.clone(), tx);
+    state.sessions.write().await.insert(
+        player_id.clone(),
+        PlayerSession {
+            sender: tx,
+            account_id: account.as_ref().map(|account| account.id.clone()),
+        },
+    );
 
     // Send task
     let mut send_task = tokio::spawn(async move {
@@ -87,15 +657,15 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     });
 
     // Receive task
-    let sessions = state.sessions.clone();
     let recv_state = state.clone();
     let recv_player_id = player_id.clone();
     let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
-            if let Message::Text(text) = msg {
-                if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) {
-                    handle_client_message(client_msg, &recv_player_id, &recv_state).await;
-                }
+            if let Message::Text(text) = msg
+                && let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text)
+            {
+                handle_client_message(client_msg, &recv_player_id, account.as_ref(), &recv_state)
+                    .await;
             }
         }
     });
@@ -106,14 +676,132 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     }
 
     state.sessions.write().await.remove(&player_id);
+    state.seeks.write().await.remove(&player_id);
+    state.lobby.write().await.unwatch(&player_id);
+    withdraw_open_challenge(&player_id, &state).await;
+    cancel_direct_challenges(&player_id, &state).await;
+    remove_spectator(&player_id, &state).await;
+    cleanup_player(&player_id, &state).await;
+}
+
+// Puts a player back in the seat their rejoin token or account holds
+#[cfg(feature = "ssr")]
+async fn rejoin_room(
+    room_code: &str,
+    token: Option<&str>,
+    player_id: &str,
+    account: Option<&Account>,
+    state: &AppState,
+) {
+    let mut rooms = state.rooms.write().await;
+    let sessions = state.sessions.read().await;
+
+    let seat = rooms.get_mut(room_code).and_then(|room| {
+        let color = if token.is_some() && room.white_token.as_deref() == token {
+            PlayerColor::White
+        } else if token.is_some() && room.black_token.as_deref() == token {
+            PlayerColor::Black
+        } else {
+            room.seat_of_account(account)?
+        };
+        let seat = match color {
+            PlayerColor::White => &mut room.white_player,
+            PlayerColor::Black => &mut room.black_player,
+        };
+        // Taking the seat over from a tab that is still open is no reconnect
+        let was_disconnected = seat.as_ref().is_none_or(|id| !sessions.contains_key(id));
+        *seat = Some(player_id.to_string());
+        Some((color, room.config.clone(), was_disconnected))
+    });
+    drop(sessions);
+    drop(rooms);
+
+    match seat {
+        Some((player_color, config, was_disconnected)) => {
+            tracing::info!("Player {} rejoined room {}", player_id, room_code);
+            if let Some(game) = state.games.write().await.get_mut(room_code)
+                && game.abandoned_by == Some(player_color)
+            {
+                game.abandoned_by = None;
+            }
+            if was_disconnected {
+                broadcast_to_room(
+                    room_code,
+                    ServerMessage::OpponentReconnected {
+                        color: player_color,
+                    },
+                    state,
+                )
+                .await;
+            }
+            send_to_player(
+                player_id,
+                ServerMessage::Rejoined {
+                    room_code: room_code.to_string(),
+                    player_color,
+                    config,
+                },
+                state,
+            )
+            .await;
+            send_game_state(room_code, state).await;
+            send_chat_history(room_code, player_id, true, state).await;
+
+            let result = state
+                .games
+                .read()
+                .await
+                .get(room_code)
+                .and_then(|game| game.result.clone());
+            if let Some(result) = result {
+                send_to_player(
+                    player_id,
+                    ServerMessage::GameOver {
+                        result,
+                        rating_changes: None,
+                    },
+                    state,
+                )
+                .await;
+            }
+        }
+        None => {
+            send_to_player(
+                player_id,
+                ServerMessage::Error {
+                    message: "Could not rejoin this room".to_string(),
+                },
+                state,
+            )
+            .await;
+        }
+    }
 }
 
 #[cfg(feature = "ssr")]
-async fn handle_client_message(msg: ClientMessage, player_id: &str, state: &AppState) {
+async fn handle_client_message(
+    msg: ClientMessage,
+    player_id: &str,
+    account: Option<&Account>,
+    state: &AppState,
+) {
     match msg {
-        ClientMessage::CreateRoom { room_code } => {
+        ClientMessage::CreateRoom {
+            room_code,
+            config,
+            name,
+            rated,
+        } => {
             tracing::info!("Creating room {} for player {}", room_code, player_id);
 
+            let name = match seat_name(account, name, state).await {
+                Ok(name) => name,
+                Err(message) => {
+                    send_to_player(player_id, ServerMessage::Error { message }, state).await;
+                    return;
+                }
+            };
+
             let rooms = state.rooms.read().await;
             if rooms.contains_key(&room_code) {
                 drop(rooms);
@@ -129,43 +817,114 @@ async fn handle_client_message(msg: ClientMessage, player_id: &str, state: &AppS
             }
             drop(rooms);
 
+            if rated && let Err(message) = rated_config(account, &config) {
+                send_to_player(player_id, ServerMessage::Error { message }, state).await;
+                return;
+            }
+
+            let mut config = config;
+            if rated {
+                // Takebacks would let a player undo a blunder that costs rating
+                config.allow_takebacks = false;
+            }
+            choose_chess960_position(&mut config);
+
+            let game = match GameState::new(&config) {
+                Ok(game) => game,
+                Err(message) => {
+                    send_to_player(player_id, ServerMessage::Error { message }, state).await;
+                    return;
+                }
+            };
+
+            let rejoin_token = uuid::Uuid::new_v4().to_string();
             let room = GameRoom {
                 room_code: room_code.clone(),
                 white_player: Some(player_id.to_string()),
                 black_player: None,
+                white_token: Some(rejoin_token.clone()),
+                black_token: None,
+                white_name: name,
+                black_name: None,
+                white_account: account.map(|account| account.id.clone()),
+                black_account: None,
+                rated,
+                spectators: Vec::new(),
+                config: config.clone(),
+                rematch_offer: None,
+                score: MatchScore::default(),
             };
 
             state.rooms.write().await.insert(room_code.clone(), room);
-            state
-                .games
-                .write()
-                .await
-                .insert(room_code.clone(), GameState::new(600_000));
+            state.games.write().await.insert(room_code.clone(), game);
+            persist_game(&room_code, state).await;
+            persist_room(&room_code, state).await;
 
             send_to_player(
                 player_id,
                 ServerMessage::RoomCreated {
                     room_code,
                     player_color: PlayerColor::White,
+                    config,
+                    rejoin_token,
                 },
                 state,
             )
             .await;
         }
 
-        ClientMessage::JoinRoom { room_code } => {
+        ClientMessage::JoinRoom { room_code, name } => {
             tracing::info!("Player {} attempting to join room {}", player_id, room_code);
+
+            let name = match seat_name(account, name, state).await {
+                Ok(name) => name,
+                Err(message) => {
+                    send_to_player(player_id, ServerMessage::Error { message }, state).await;
+                    return;
+                }
+            };
+
+            // Players who already hold a seat, say from another device, take it back
+            let holds_seat = state
+                .rooms
+                .read()
+                .await
+                .get(&room_code)
+                .is_some_and(|room| room.seat_of_account(account).is_some());
+            if holds_seat {
+                rejoin_room(&room_code, None, player_id, account, state).await;
+                return;
+            }
+
             let mut rooms = state.rooms.write().await;
 
             if let Some(room) = rooms.get_mut(&room_code) {
-                if room.black_player.is_none() {
+                // Rated seats are for accounts, and a restored seat stays
+                // reserved for its rejoin token
+                if room.rated && account.is_none() {
+                    drop(rooms);
+                    send_to_player(
+                        player_id,
+                        ServerMessage::Error {
+                            message: "Log in to play rated games".to_string(),
+                        },
+                        state,
+                    )
+                    .await;
+                } else if room.black_player.is_none() && room.black_token.is_none() {
+                    let rejoin_token = uuid::Uuid::new_v4().to_string();
                     room.black_player = Some(player_id.to_string());
+                    room.black_token = Some(rejoin_token.clone());
+                    room.black_name = name;
+                    room.black_account = account.map(|account| account.id.clone());
 
                     send_to_player(
                         player_id,
                         ServerMessage::RoomJoined {
                             room_code: room_code.clone(),
                             player_color: PlayerColor::Black,
+                            config: room.config.clone(),
+                            rejoin_token,
                         },
                         state,
                     )
@@ -176,7 +935,14 @@ async fn handle_client_message(msg: ClientMessage, player_id: &str, state: &AppS
                     }
 
                     drop(rooms);
+                    if let Some(game) = state.games.write().await.get_mut(&room_code) {
+                        game.start_clock();
+                    }
+                    persist_game(&room_code, state).await;
+                    persist_room(&room_code, state).await;
+                    schedule_flag_check(&room_code, state).await;
                     send_game_state(&room_code, state).await;
+                    send_chat_history(&room_code, player_id, true, state).await;
                 } else {
                     send_to_player(
                         player_id,
@@ -199,79 +965,1330 @@ async fn handle_client_message(msg: ClientMessage, player_id: &str, state: &AppS
             }
         }
 
-        ClientMessage::MakeMove {
-            from,
-            to,
-            promotion,
-        } => {
-            if let Some((room_code, _)) = find_player_room(player_id, state).await {
-                let mut games = state.games.write().await;
-
-                if let Some(game) = games.get_mut(&room_code) {
-                    match game.make_move(&from, &to, promotion.as_deref()) {
-                        Ok(san) => {
-                            let fen = game.get_fen();
-                            drop(games);
-
-                            broadcast_to_room(
-                                &room_code,
-                                ServerMessage::MoveMade { from, to, san, fen },
-                                state,
-                            )
-                            .await;
-
-                            send_game_state(&room_code, state).await;
-                        }
-                        Err(reason) => {
-                            send_to_player(player_id, ServerMessage::InvalidMove { reason }, state)
-                                .await;
-                        }
-                    }
-                }
-            }
+        ClientMessage::Rejoin { room_code, token } => {
+            rejoin_room(&room_code, Some(&token), player_id, account, state).await;
         }
 
-        ClientMessage::Resign => {
-            if let Some((room_code, color)) = find_player_room(player_id, state).await {
-                let winner = match color {
-                    PlayerColor::White => PlayerColor::Black,
-                    PlayerColor::Black => PlayerColor::White,
-                };
-
-                broadcast_to_room(
-                    &room_code,
-                    ServerMessage::GameOver {
-                        result: GameResult::Resignation { winner },
+        ClientMessage::Spectate { room_code } => {
+            tracing::info!("Player {} spectating room {}", player_id, room_code);
+            if find_player_room(player_id, state).await.is_some() {
+                send_to_player(
+                    player_id,
+                    ServerMessage::Error {
+                        message: "Players cannot spectate while seated in a room".to_string(),
                     },
                     state,
                 )
                 .await;
+                return;
+            }
+            // One room is watched at a time, so stop watching any other
+            let watching = find_spectator_room(player_id, state).await;
+            if watching.is_some_and(|watched| watched != room_code)
+                && state.rooms.read().await.contains_key(&room_code)
+            {
+                remove_spectator(player_id, state).await;
             }
-        }
-    }
-}
+
+            let mut rooms = state.rooms.write().await;
+            if let Some(room) = rooms.get_mut(&room_code) {
+                if !room.spectators.iter().any(|id| id == player_id) {
+                    room.spectators.push(player_id.to_string());
+                }
+                let config = room.config.clone();
+                let count = room.spectators.len();
+                drop(rooms);
+
+                send_to_player(
+                    player_id,
+                    ServerMessage::Spectating {
+                        room_code: room_code.clone(),
+                        config,
+                    },
+                    state,
+                )
+                .await;
+                send_game_state(&room_code, state).await;
+                send_chat_history(&room_code, player_id, false, state).await;
+                broadcast_to_room(&room_code, ServerMessage::SpectatorCount { count }, state).await;
+
+                let result = state
+                    .games
+                    .read()
+                    .await
+                    .get(&room_code)
+                    .and_then(|game| game.result.clone());
+                if let Some(result) = result {
+                    send_to_player(
+                        player_id,
+                        ServerMessage::GameOver {
+                            result,
+                            rating_changes: None,
+                        },
+                        state,
+                    )
+                    .await;
+                }
+            } else {
+                send_to_player(
+                    player_id,
+                    ServerMessage::Error {
+                        message: "Room not found".to_string(),
+                    },
+                    state,
+                )
+                .await;
+            }
+        }
+
+        ClientMessage::MakeMove {
+            from,
+            to,
+            promotion,
+        } => {
+            play_move(player_id, state, from.clone(), to.clone(), |game| {
+                game.make_move(&from, &to, promotion.as_deref())
+            })
+            .await;
+        }
+
+        ClientMessage::DropPiece { piece, square } => {
+            let from = format!("{}@", piece.to_uppercase());
+            play_move(player_id, state, from, square.clone(), |game| {
+                game.drop_piece(&piece, &square)
+            })
+            .await;
+        }
+
+        ClientMessage::Resign => {
+            if let Some((room_code, color)) = find_player_room(player_id, state).await {
+                let mut games = state.games.write().await;
+
+                if let Some(game) = games.get_mut(&room_code) {
+                    match game.resign(color) {
+                        Ok(result) => {
+                            drop(games);
+                            schedule_flag_check(&room_code, state).await;
+                            finish_game(&room_code, result, state).await;
+                        }
+                        Err(message) => {
+                            send_to_player(player_id, ServerMessage::Error { message }, state)
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        ClientMessage::ClaimDraw => {
+            if let Some((room_code, color)) = find_player_room(player_id, state).await {
+                let mut games = state.games.write().await;
+
+                if let Some(game) = games.get_mut(&room_code) {
+                    match game.claim_draw(color) {
+                        Ok(result) => {
+                            drop(games);
+                            schedule_flag_check(&room_code, state).await;
+                            finish_game(&room_code, result, state).await;
+                        }
+                        Err(message) => {
+                            send_to_player(player_id, ServerMessage::Error { message }, state)
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        ClientMessage::ClaimVictory => {
+            if let Some((room_code, color)) = find_player_room(player_id, state).await {
+                let mut games = state.games.write().await;
+
+                if let Some(game) = games.get_mut(&room_code) {
+                    match game.claim_victory(color) {
+                        Ok(result) => {
+                            drop(games);
+                            schedule_flag_check(&room_code, state).await;
+                            finish_game(&room_code, result, state).await;
+                        }
+                        Err(message) => {
+                            send_to_player(player_id, ServerMessage::Error { message }, state)
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        ClientMessage::OfferDraw => {
+            if let Some((room_code, color)) = find_player_room(player_id, state).await {
+                let mut games = state.games.write().await;
+
+                if let Some(game) = games.get_mut(&room_code) {
+                    match game.offer_draw(color) {
+                        Ok(()) => {
+                            drop(games);
+                            broadcast_to_room(
+                                &room_code,
+                                ServerMessage::DrawOffered { by: color },
+                                state,
+                            )
+                            .await;
+                        }
+                        Err(message) => {
+                            send_to_player(player_id, ServerMessage::Error { message }, state)
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        ClientMessage::AcceptDraw => {
+            if let Some((room_code, color)) = find_player_room(player_id, state).await {
+                let mut games = state.games.write().await;
+
+                if let Some(game) = games.get_mut(&room_code) {
+                    match game.accept_draw(color) {
+                        Ok(result) => {
+                            drop(games);
+                            schedule_flag_check(&room_code, state).await;
+                            finish_game(&room_code, result, state).await;
+                        }
+                        Err(message) => {
+                            send_to_player(player_id, ServerMessage::Error { message }, state)
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        ClientMessage::DeclineDraw => {
+            if let Some((room_code, color)) = find_player_room(player_id, state).await {
+                let mut games = state.games.write().await;
+
+                if let Some(game) = games.get_mut(&room_code) {
+                    match game.decline_draw(color) {
+                        Ok(()) => {
+                            drop(games);
+                            broadcast_to_room(
+                                &room_code,
+                                ServerMessage::DrawDeclined { by: color },
+                                state,
+                            )
+                            .await;
+                        }
+                        Err(message) => {
+                            send_to_player(player_id, ServerMessage::Error { message }, state)
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        ClientMessage::RequestTakeback => {
+            if let Some((room_code, color)) = find_player_room(player_id, state).await {
+                let allow_takebacks = state
+                    .rooms
+                    .read()
+                    .await
+                    .get(&room_code)
+                    .is_some_and(|room| room.config.allow_takebacks);
+                if !allow_takebacks {
+                    send_to_player(
+                        player_id,
+                        ServerMessage::Error {
+                            message: "Takebacks are disabled in this room".to_string(),
+                        },
+                        state,
+                    )
+                    .await;
+                    return;
+                }
+
+                let mut games = state.games.write().await;
+
+                if let Some(game) = games.get_mut(&room_code) {
+                    match game.request_takeback(color) {
+                        Ok(()) => {
+                            drop(games);
+                            broadcast_to_room(
+                                &room_code,
+                                ServerMessage::TakebackRequested { by: color },
+                                state,
+                            )
+                            .await;
+                        }
+                        Err(message) => {
+                            send_to_player(player_id, ServerMessage::Error { message }, state)
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        ClientMessage::AcceptTakeback => {
+            if let Some((room_code, color)) = find_player_room(player_id, state).await {
+                let mut games = state.games.write().await;
+
+                if let Some(game) = games.get_mut(&room_code) {
+                    match game.accept_takeback(color) {
+                        Ok(()) => {
+                            state.writer.queue(StorageWrite::TruncateMoves {
+                                game_id: game.id.clone(),
+                                plies: game.moves.len(),
+                            });
+                            drop(games);
+                            schedule_flag_check(&room_code, state).await;
+                            broadcast_to_room(&room_code, ServerMessage::TakebackAccepted, state)
+                                .await;
+                            send_game_state(&room_code, state).await;
+                        }
+                        Err(message) => {
+                            send_to_player(player_id, ServerMessage::Error { message }, state)
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        ClientMessage::DeclineTakeback => {
+            if let Some((room_code, color)) = find_player_room(player_id, state).await {
+                let mut games = state.games.write().await;
+
+                if let Some(game) = games.get_mut(&room_code) {
+                    match game.decline_takeback(color) {
+                        Ok(()) => {
+                            drop(games);
+                            broadcast_to_room(
+                                &room_code,
+                                ServerMessage::TakebackDeclined { by: color },
+                                state,
+                            )
+                            .await;
+                        }
+                        Err(message) => {
+                            send_to_player(player_id, ServerMessage::Error { message }, state)
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        ClientMessage::OfferRematch => {
+            if let Some((room_code, color)) = find_player_room(player_id, state).await {
+                if !is_game_over(&room_code, state).await {
+                    send_to_player(
+                        player_id,
+                        ServerMessage::Error {
+                            message: "The game is still in progress".to_string(),
+                        },
+                        state,
+                    )
+                    .await;
+                    return;
+                }
+
+                let mut rooms = state.rooms.write().await;
+                if let Some(room) = rooms.get_mut(&room_code) {
+                    match room.offer_rematch(color) {
+                        Ok(true) => {
+                            drop(rooms);
+                            start_rematch(&room_code, state).await;
+                        }
+                        Ok(false) => {
+                            drop(rooms);
+                            broadcast_to_room(
+                                &room_code,
+                                ServerMessage::RematchOffered { by: color },
+                                state,
+                            )
+                            .await;
+                        }
+                        Err(message) => {
+                            send_to_player(player_id, ServerMessage::Error { message }, state)
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        ClientMessage::AcceptRematch => {
+            if let Some((room_code, color)) = find_player_room(player_id, state).await {
+                let mut rooms = state.rooms.write().await;
+                if let Some(room) = rooms.get_mut(&room_code) {
+                    match room.accept_rematch(color) {
+                        Ok(()) => {
+                            drop(rooms);
+                            start_rematch(&room_code, state).await;
+                        }
+                        Err(message) => {
+                            send_to_player(player_id, ServerMessage::Error { message }, state)
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        ClientMessage::DeclineRematch => {
+            if let Some((room_code, color)) = find_player_room(player_id, state).await {
+                let mut rooms = state.rooms.write().await;
+                if let Some(room) = rooms.get_mut(&room_code) {
+                    match room.decline_rematch(color) {
+                        Ok(()) => {
+                            drop(rooms);
+                            broadcast_to_room(
+                                &room_code,
+                                ServerMessage::RematchDeclined { by: color },
+                                state,
+                            )
+                            .await;
+                        }
+                        Err(message) => {
+                            send_to_player(player_id, ServerMessage::Error { message }, state)
+                                .await;
+                        }
+                    }
+                }
+            }
+        }
+
+        ClientMessage::Chat { text } => {
+            let (room_code, role) = match find_player_room(player_id, state).await {
+                Some((room_code, color)) => (room_code, ChatRole::Player(color)),
+                None => match find_spectator_room(player_id, state).await {
+                    Some(room_code) => (room_code, ChatRole::Spectator),
+                    None => return,
+                },
+            };
+
+            let posted = state
+                .chats
+                .write()
+                .await
+                .entry(room_code.clone())
+                .or_default()
+                .post(player_id, role, &text);
+
+            match posted {
+                Ok(message) => deliver_chat(&room_code, message, state).await,
+                Err(message) => {
+                    send_to_player(player_id, ServerMessage::Error { message }, state).await;
+                }
+            }
+        }
+
+        ClientMessage::MuteChat { muted } => {
+            let room_code = match find_player_room(player_id, state).await {
+                Some((room_code, _)) => Some(room_code),
+                None => find_spectator_room(player_id, state).await,
+            };
+
+            if let Some(room_code) = room_code {
+                state
+                    .chats
+                    .write()
+                    .await
+                    .entry(room_code)
+                    .or_default()
+                    .set_muted(player_id, muted);
+            }
+        }
+
+        ClientMessage::Seek {
+            time_control,
+            rated,
+            rating_range,
+            color_preference,
+            name,
+        } => {
+            let name = match seat_name(account, name, state).await {
+                Ok(name) => name,
+                Err(message) => {
+                    send_to_player(player_id, ServerMessage::Error { message }, state).await;
+                    return;
+                }
+            };
+
+            let config = match_config(time_control.clone(), Variant::Standard);
+            let valid = if rated {
+                rated_config(account, &config)
+            } else {
+                Ok(())
+            }
+            .and_then(|()| GameState::new(&config).map(|_| ()));
+            if let Err(message) = valid {
+                send_to_player(player_id, ServerMessage::Error { message }, state).await;
+                return;
+            }
+
+            let rating = match (account, &time_control) {
+                (Some(account), Some(time_control)) => {
+                    stored_rating(&account.id, RatingCategory::of(time_control), state)
+                        .await
+                        .unwrap_or_else(|err| {
+                            tracing::error!("Could not load rating of {}: {}", account.id, err);
+                            Rating::default()
+                        })
+                }
+                _ => Rating::default(),
+            };
+
+            tracing::info!("Player {} seeking a game", player_id);
+            state.seeks.write().await.add(Seek {
+                player: MatchPlayer {
+                    player_id: player_id.to_string(),
+                    account_id: account.map(|account| account.id.clone()),
+                    name,
+                },
+                rating: rating.rating,
+                time_control,
+                rated,
+                rating_range,
+                color_preference,
+                since: GameState::current_time_ms(),
+            });
+            send_to_player(player_id, ServerMessage::Seeking, state).await;
+            pair_seekers(state).await;
+        }
+
+        ClientMessage::CancelSeek => {
+            if state.seeks.write().await.remove(player_id) {
+                send_to_player(player_id, ServerMessage::SeekCancelled, state).await;
+            }
+        }
+
+        ClientMessage::WatchLobby => {
+            let challenges = {
+                let mut lobby = state.lobby.write().await;
+                lobby.watch(player_id);
+                lobby.challenges()
+            };
+            send_to_player(player_id, ServerMessage::Lobby { challenges }, state).await;
+        }
+
+        ClientMessage::PostOpenChallenge {
+            time_control,
+            variant,
+            rated,
+            color,
+            name,
+        } => {
+            let name = match seat_name(account, name, state).await {
+                Ok(name) => name,
+                Err(message) => {
+                    send_to_player(player_id, ServerMessage::Error { message }, state).await;
+                    return;
+                }
+            };
+
+            let config = match_config(time_control.clone(), variant);
+            let valid = if rated {
+                rated_config(account, &config)
+            } else {
+                Ok(())
+            }
+            .and_then(|()| GameState::new(&config).map(|_| ()));
+            if let Err(message) = valid {
+                send_to_player(player_id, ServerMessage::Error { message }, state).await;
+                return;
+            }
+
+            let rating = match (account, &time_control) {
+                (Some(account), Some(time_control)) => {
+                    match stored_rating(&account.id, RatingCategory::of(time_control), state).await
+                    {
+                        Ok(rating) => Some(rating.rating.round() as i32),
+                        Err(err) => {
+                            tracing::error!("Could not load rating of {}: {}", account.id, err);
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            };
+
+            let challenge = OpenChallenge {
+                id: uuid::Uuid::new_v4().to_string(),
+                name: name.clone(),
+                rating,
+                time_control,
+                variant,
+                rated,
+                color,
+                created_at: GameState::current_time_ms(),
+            };
+            let replaced = state.lobby.write().await.post(PostedChallenge {
+                poster: MatchPlayer {
+                    player_id: player_id.to_string(),
+                    account_id: account.map(|account| account.id.clone()),
+                    name,
+                },
+                challenge: challenge.clone(),
+            });
+
+            if let Some(id) = replaced {
+                broadcast_to_lobby(ServerMessage::OpenChallengeRemoved { id }, state).await;
+            }
+            send_to_player(
+                player_id,
+                ServerMessage::OpenChallengePosted {
+                    id: challenge.id.clone(),
+                },
+                state,
+            )
+            .await;
+            broadcast_to_lobby(ServerMessage::OpenChallengeAdded { challenge }, state).await;
+        }
+
+        ClientMessage::CancelOpenChallenge => {
+            withdraw_open_challenge(player_id, state).await;
+        }
+
+        ClientMessage::AcceptOpenChallenge { id, name } => {
+            let name = match seat_name(account, name, state).await {
+                Ok(name) => name,
+                Err(message) => {
+                    send_to_player(player_id, ServerMessage::Error { message }, state).await;
+                    return;
+                }
+            };
+
+            let taken = {
+                let mut lobby = state.lobby.write().await;
+                match lobby.get(&id) {
+                    None => Err("This challenge is no longer open"),
+                    Some(posted)
+                        if posted
+                            .poster
+                            .is_player(player_id, account.map(|account| account.id.as_str())) =>
+                    {
+                        Err("You cannot accept your own challenge")
+                    }
+                    Some(posted) if posted.challenge.rated && account.is_none() => {
+                        Err("Log in to play rated games")
+                    }
+                    Some(_) => lobby.take(&id).ok_or("This challenge is no longer open"),
+                }
+            };
+            let posted = match taken {
+                Ok(posted) => posted,
+                Err(message) => {
+                    send_to_player(
+                        player_id,
+                        ServerMessage::Error {
+                            message: message.to_string(),
+                        },
+                        state,
+                    )
+                    .await;
+                    return;
+                }
+            };
+            broadcast_to_lobby(ServerMessage::OpenChallengeRemoved { id }, state).await;
+
+            let challenge = posted.challenge;
+            let accepter = MatchPlayer {
+                player_id: player_id.to_string(),
+                account_id: account.map(|account| account.id.clone()),
+                name,
+            };
+            let (white, black) =
+                if matchmaking::first_plays_white(challenge.color, ColorPreference::Random) {
+                    (posted.poster, accepter)
+                } else {
+                    (accepter, posted.poster)
+                };
+            let config = match_config(challenge.time_control, challenge.variant);
+            start_match(white, black, config, challenge.rated, state).await;
+        }
+
+        ClientMessage::Challenge {
+            to_user,
+            time_control,
+            variant,
+            color,
+            rated,
+        } => {
+            let Some(account) = account else {
+                send_to_player(
+                    player_id,
+                    ServerMessage::Error {
+                        message: "Log in to challenge other players".to_string(),
+                    },
+                    state,
+                )
+                .await;
+                return;
+            };
+            let sent = send_direct_challenge(
+                account,
+                player_id,
+                to_user.trim(),
+                match_config(time_control, variant),
+                color,
+                rated,
+                state,
+            )
+            .await;
+            if let Err(message) = sent {
+                send_to_player(player_id, ServerMessage::Error { message }, state).await;
+            }
+        }
+
+        ClientMessage::AcceptChallenge { id } => {
+            let pending = take_direct_challenge(
+                &id,
+                |pending| account.is_some_and(|account| pending.recipient_id == account.id),
+                state,
+            )
+            .await;
+            let (Some(pending), Some(account)) = (pending, account) else {
+                send_to_player(
+                    player_id,
+                    ServerMessage::Error {
+                        message: "This challenge is no longer open".to_string(),
+                    },
+                    state,
+                )
+                .await;
+                return;
+            };
+            notify_direct_challenge(
+                &pending,
+                ServerMessage::ChallengeAccepted { id: id.clone() },
+                state,
+            )
+            .await;
+
+            let challenge = pending.challenge;
+            let recipient = MatchPlayer {
+                player_id: player_id.to_string(),
+                account_id: Some(account.id.clone()),
+                name: Some(account.name().to_string()),
+            };
+            let (white, black) =
+                if matchmaking::first_plays_white(challenge.color, ColorPreference::Random) {
+                    (pending.challenger, recipient)
+                } else {
+                    (recipient, pending.challenger)
+                };
+            let config = match_config(challenge.time_control, challenge.variant);
+            start_match(white, black, config, challenge.rated, state).await;
+        }
+
+        ClientMessage::DeclineChallenge { id, reason } => {
+            let reason = match non_empty(reason).map(|reason| chat::moderate(&reason)) {
+                Some(Err(message)) => {
+                    send_to_player(player_id, ServerMessage::Error { message }, state).await;
+                    return;
+                }
+                reason => reason.and_then(Result::ok),
+            };
+            let pending = take_direct_challenge(
+                &id,
+                |pending| account.is_some_and(|account| pending.recipient_id == account.id),
+                state,
+            )
+            .await;
+            if let Some(pending) = pending {
+                notify_direct_challenge(
+                    &pending,
+                    ServerMessage::ChallengeDeclined { id, reason },
+                    state,
+                )
+                .await;
+            }
+        }
+
+        ClientMessage::CancelChallenge { id } => {
+            let pending = take_direct_challenge(
+                &id,
+                |pending| pending.challenger.player_id == player_id,
+                state,
+            )
+            .await;
+            if let Some(pending) = pending {
+                notify_direct_challenge(&pending, ServerMessage::ChallengeCancelled { id }, state)
+                    .await;
+            }
+        }
+    }
+}
+
+// Pairs seeks again every so often, as their rating windows widen
+#[cfg(feature = "ssr")]
+async fn run_matchmaking(state: AppState) {
+    let mut interval = tokio::time::interval(MATCHMAKING_INTERVAL);
+    loop {
+        interval.tick().await;
+        pair_seekers(&state).await;
+    }
+}
+
+// Starts a game for every two seeks that can play each other
+#[cfg(feature = "ssr")]
+async fn pair_seekers(state: &AppState) {
+    let matches = state
+        .seeks
+        .write()
+        .await
+        .take_matches(GameState::current_time_ms());
+    for (seek, opponent) in matches {
+        let config = match_config(seek.time_control.clone(), Variant::Standard);
+        let (white, black) =
+            if matchmaking::first_plays_white(seek.color_preference, opponent.color_preference) {
+                (seek.player, opponent.player)
+            } else {
+                (opponent.player, seek.player)
+            };
+        start_match(white, black, config, seek.rated, state).await;
+    }
+}
+
+// Games between players the server brought together are played without
+// takebacks, from the variant's own starting position
+#[cfg(feature = "ssr")]
+fn match_config(time_control: Option<TimeControl>, variant: Variant) -> RoomConfig {
+    RoomConfig {
+        allow_takebacks: false,
+        time_control,
+        start_fen: None,
+        variant,
+    }
+}
+
+// Opens a room for two matched players. Like a restored room it holds both
+// seats for their rejoin tokens, so each player takes theirs from the game
+// page; a seat nobody takes is abandoned
+#[cfg(feature = "ssr")]
+async fn start_match(
+    white: MatchPlayer,
+    black: MatchPlayer,
+    mut config: RoomConfig,
+    rated: bool,
+    state: &AppState,
+) {
+    choose_chess960_position(&mut config);
+    // The config was validated when the game was asked for
+    let Ok(mut game) = GameState::new(&config) else {
+        return;
+    };
+    game.start_clock();
+
+    // Players who have a game stop waiting for another
+    for player in [&white, &black] {
+        state.seeks.write().await.remove(&player.player_id);
+        withdraw_open_challenge(&player.player_id, state).await;
+    }
+
+    let white_token = uuid::Uuid::new_v4().to_string();
+    let black_token = uuid::Uuid::new_v4().to_string();
+    let mut rooms = state.rooms.write().await;
+    let mut room_code = new_room_code();
+    while rooms.contains_key(&room_code) {
+        room_code = new_room_code();
+    }
+    rooms.insert(
+        room_code.clone(),
+        GameRoom {
+            room_code: room_code.clone(),
+            white_player: None,
+            black_player: None,
+            white_token: Some(white_token.clone()),
+            black_token: Some(black_token.clone()),
+            white_name: white.name,
+            black_name: black.name,
+            white_account: white.account_id,
+            black_account: black.account_id,
+            rated,
+            spectators: Vec::new(),
+            config: config.clone(),
+            rematch_offer: None,
+            score: MatchScore::default(),
+        },
+    );
+    drop(rooms);
+    state.games.write().await.insert(room_code.clone(), game);
+
+    tracing::info!("Matched players in room {}", room_code);
+    persist_game(&room_code, state).await;
+    persist_room(&room_code, state).await;
+    schedule_flag_check(&room_code, state).await;
+    tokio::spawn(release_unclaimed_seats(
+        room_code.clone(),
+        false,
+        state.clone(),
+    ));
+
+    for (player_id, player_color, rejoin_token) in [
+        (white.player_id, PlayerColor::White, white_token),
+        (black.player_id, PlayerColor::Black, black_token),
+    ] {
+        send_to_player(
+            &player_id,
+            ServerMessage::MatchFound {
+                room_code: room_code.clone(),
+                player_color,
+                config: config.clone(),
+                rejoin_token,
+            },
+            state,
+        )
+        .await;
+    }
+}
+
+// Delivers a challenge to every socket of the recipient, who must be online,
+// and withdraws it if nobody answers in time
+#[cfg(feature = "ssr")]
+async fn send_direct_challenge(
+    challenger: &Account,
+    player_id: &str,
+    to_user: &str,
+    config: RoomConfig,
+    color: ColorPreference,
+    rated: bool,
+    state: &AppState,
+) -> Result<(), String> {
+    let username = to_user.to_string();
+    let recipient =
+        match with_storage(state, move |storage| storage.account_by_username(&username)).await {
+            Ok(Some(stored)) => stored.account,
+            Ok(None) => return Err(format!("No player is called {}", to_user)),
+            Err(err) => {
+                tracing::error!("Could not load account {}: {}", to_user, err);
+                return Err("Could not send the challenge".to_string());
+            }
+        };
+    if recipient.id == challenger.id {
+        return Err("You cannot challenge yourself".to_string());
+    }
+    if rated {
+        rated_config(Some(challenger), &config)?;
+    }
+    GameState::new(&config)?;
+
+    let online = state
+        .sessions
+        .read()
+        .await
+        .values()
+        .any(|session| session.account_id.as_deref() == Some(recipient.id.as_str()));
+    if !online {
+        return Err(format!("{} is not online", recipient.username));
+    }
+
+    let challenge = DirectChallenge {
+        id: uuid::Uuid::new_v4().to_string(),
+        from: challenger.username.clone(),
+        to: recipient.username,
+        time_control: config.time_control,
+        variant: config.variant,
+        rated,
+        color,
+        expires_at: GameState::current_time_ms() + state.challenge_timeout.as_millis() as u64,
+    };
+    let pending = PendingChallenge {
+        challenger: MatchPlayer {
+            player_id: player_id.to_string(),
+            account_id: Some(challenger.id.clone()),
+            name: Some(challenger.name().to_string()),
+        },
+        recipient_id: recipient.id,
+        challenge: challenge.clone(),
+    };
+    state
+        .challenges
+        .write()
+        .await
+        .insert(challenge.id.clone(), pending.clone());
+
+    tracing::info!("{} challenged {}", challenge.from, challenge.to);
+    send_to_player(
+        player_id,
+        ServerMessage::ChallengeSent {
+            challenge: challenge.clone(),
+        },
+        state,
+    )
+    .await;
+    send_to_account(
+        &pending.recipient_id,
+        ServerMessage::ChallengeReceived {
+            challenge: challenge.clone(),
+        },
+        state,
+    )
+    .await;
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(state.challenge_timeout).await;
+        let expired = take_direct_challenge(&challenge.id, |_| true, &state).await;
+        if let Some(pending) = expired {
+            notify_direct_challenge(
+                &pending,
+                ServerMessage::ChallengeExpired { id: challenge.id },
+                &state,
+            )
+            .await;
+        }
+    });
+    Ok(())
+}
+
+// Removes a pending challenge if it is still there and `allowed` says this
+// player may end it
+#[cfg(feature = "ssr")]
+async fn take_direct_challenge(
+    id: &str,
+    allowed: impl FnOnce(&PendingChallenge) -> bool,
+    state: &AppState,
+) -> Option<PendingChallenge> {
+    let mut challenges = state.challenges.write().await;
+    if !allowed(challenges.get(id)?) {
+        return None;
+    }
+    challenges.remove(id)
+}
+
+// Tells both sides of a challenge, the recipient on every socket
+#[cfg(feature = "ssr")]
+async fn notify_direct_challenge(pending: &PendingChallenge, msg: ServerMessage, state: &AppState) {
+    send_to_player(&pending.challenger.player_id, msg.clone(), state).await;
+    send_to_account(&pending.recipient_id, msg, state).await;
+}
+
+// Challenges sent from a socket that has gone away are cancelled
+#[cfg(feature = "ssr")]
+async fn cancel_direct_challenges(player_id: &str, state: &AppState) {
+    let cancelled: Vec<PendingChallenge> = {
+        let mut challenges = state.challenges.write().await;
+        let ids: Vec<String> = challenges
+            .iter()
+            .filter(|(_, pending)| pending.challenger.player_id == player_id)
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.iter().filter_map(|id| challenges.remove(id)).collect()
+    };
+    for pending in cancelled {
+        let id = pending.challenge.id.clone();
+        send_to_account(
+            &pending.recipient_id,
+            ServerMessage::ChallengeCancelled { id },
+            state,
+        )
+        .await;
+    }
+}
+
+// Takes the player's challenge out of the lobby, if they have one
+#[cfg(feature = "ssr")]
+async fn withdraw_open_challenge(player_id: &str, state: &AppState) {
+    let withdrawn = state.lobby.write().await.withdraw(player_id);
+    if let Some(id) = withdrawn {
+        broadcast_to_lobby(ServerMessage::OpenChallengeRemoved { id }, state).await;
+    }
+}
+
+#[cfg(feature = "ssr")]
+async fn broadcast_to_lobby(msg: ServerMessage, state: &AppState) {
+    let watchers = state.lobby.read().await.watchers();
+    for player_id in watchers {
+        send_to_player(&player_id, msg.clone(), state).await;
+    }
+}
+
+// Chess960 games set up without a position get a random one
+#[cfg(feature = "ssr")]
+fn choose_chess960_position(config: &mut RoomConfig) {
+    if let Variant::Chess960 { position: None } = config.variant {
+        let position = uuid::Uuid::new_v4().as_u128() % chess960::POSITIONS as u128;
+        config.variant = Variant::Chess960 {
+            position: Some(position as u16),
+        };
+    }
+}
+
+// Six letters and digits, like the codes players pick on the home page
+#[cfg(feature = "ssr")]
+fn new_room_code() -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    let mut n = uuid::Uuid::new_v4().as_u128();
+    (0..6)
+        .map(|_| {
+            let c = CHARS[(n % CHARS.len() as u128) as usize] as char;
+            n /= CHARS.len() as u128;
+            c
+        })
+        .collect()
+}
+
+// Plays a move or drop for the player and shows it to the room, or tells the
+// player why it was refused
+#[cfg(feature = "ssr")]
+async fn play_move<F>(player_id: &str, state: &AppState, from: String, to: String, apply: F)
+where
+    F: FnOnce(&mut GameState) -> Result<String, String>,
+{
+    let Some((room_code, _)) = find_player_room(player_id, state).await else {
+        return;
+    };
+    let mut games = state.games.write().await;
+    let Some(game) = games.get_mut(&room_code) else {
+        return;
+    };
+
+    let had_draw_offer = game.draw_offer.is_some();
+    let was_over = game.game_over;
+    match apply(game) {
+        Ok(san) => {
+            // Queued while the game is still locked so moves reach storage in order
+            if let Some((ply, stored)) = StoredMove::latest(game) {
+                state.writer.queue(StorageWrite::SaveMove {
+                    game_id: game.id.clone(),
+                    ply,
+                    stored,
+                });
+            }
+            let fen = game.get_fen();
+            let result = game.result.clone();
+            let draw_offer_expired = had_draw_offer && game.draw_offer.is_none();
+            drop(games);
+            schedule_flag_check(&room_code, state).await;
+
+            if draw_offer_expired {
+                broadcast_to_room(&room_code, ServerMessage::DrawOfferExpired, state).await;
+            }
+
+            broadcast_to_room(
+                &room_code,
+                ServerMessage::MoveMade { from, to, san, fen },
+                state,
+            )
+            .await;
+
+            send_game_state(&room_code, state).await;
+
+            if let Some(result) = result {
+                finish_game(&room_code, result, state).await;
+            }
+        }
+        Err(reason) => {
+            let flagged = !was_over && game.game_over;
+            let result = game.result.clone();
+            drop(games);
+
+            send_to_player(player_id, ServerMessage::InvalidMove { reason }, state).await;
+
+            if let (true, Some(result)) = (flagged, result) {
+                schedule_flag_check(&room_code, state).await;
+                finish_game(&room_code, result, state).await;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+async fn is_game_over(room_code: &str, state: &AppState) -> bool {
+    state
+        .games
+        .read()
+        .await
+        .get(room_code)
+        .is_some_and(|game| game.game_over)
+}
+
+// Resets the room's game in place with the same time control and the
+// players' colors swapped
+#[cfg(feature = "ssr")]
+async fn start_rematch(room_code: &str, state: &AppState) {
+    let result = state
+        .games
+        .read()
+        .await
+        .get(room_code)
+        .and_then(|game| game.result.clone());
+
+    let (config, score) = {
+        let mut rooms = state.rooms.write().await;
+        let Some(room) = rooms.get_mut(room_code) else {
+            return;
+        };
+        room.start_rematch(result.as_ref());
+        (room.config.clone(), room.score)
+    };
+
+    // The config was validated when the room was created
+    let Ok(mut game) = GameState::new(&config) else {
+        return;
+    };
+    game.start_clock();
+    state
+        .games
+        .write()
+        .await
+        .insert(room_code.to_string(), game);
+
+    persist_game(room_code, state).await;
+    persist_room(room_code, state).await;
+
+    tracing::info!("Rematch started in room {}", room_code);
+    schedule_flag_check(room_code, state).await;
+    broadcast_to_room(room_code, ServerMessage::RematchStarted { score }, state).await;
+    send_game_state(room_code, state).await;
+}
+
+// (Re)arms the room's timer so the server declares a flag fall the moment the
+// side to move runs out of time, even if nobody sends another message
+#[cfg(feature = "ssr")]
+async fn schedule_flag_check(room_code: &str, state: &AppState) {
+    let time_until_flag = state
+        .games
+        .read()
+        .await
+        .get(room_code)
+        .and_then(|game| game.time_until_flag());
+
+    let mut timers = state.clock_timers.write().await;
+    if let Some(timer) = timers.remove(room_code) {
+        timer.abort();
+    }
+
+    if let Some(mut wait_ms) = time_until_flag {
+        let timer_room = room_code.to_string();
+        let timer_state = state.clone();
+        let timer = tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_millis(wait_ms)).await;
+
+                let mut games = timer_state.games.write().await;
+                let Some(game) = games.get_mut(&timer_room) else {
+                    return;
+                };
+                game.update_time();
+                if !game.game_over {
+                    match game.time_until_flag() {
+                        Some(ms) => {
+                            wait_ms = ms;
+                            continue;
+                        }
+                        None => return,
+                    }
+                }
+
+                let result = game.result.clone();
+                drop(games);
+                // A rematch may have armed a newer timer for the room meanwhile
+                let mut timers = timer_state.clock_timers.write().await;
+                if timers
+                    .get(&timer_room)
+                    .is_some_and(|timer| timer.id() == tokio::task::id())
+                {
+                    timers.remove(&timer_room);
+                }
+                drop(timers);
+
+                if let Some(result) = result {
+                    tracing::info!("Flag fell in room {}", timer_room);
+                    send_game_state(&timer_room, &timer_state).await;
+                    finish_game(&timer_room, result, &timer_state).await;
+                }
+                return;
+            }
+        });
+        timers.insert(room_code.to_string(), timer);
+    }
+}
 
 #[cfg(feature = "ssr")]
 async fn send_game_state(room_code: &str, state: &AppState) {
     let games = state.games.read().await;
     if let Some(game) = games.get(room_code) {
-        let msg = ServerMessage::GameState {
-            fen: game.get_fen(),
-            moves: game.moves.clone(),
-            white_time: game.white_time_ms,
-            black_time: game.black_time_ms,
-            current_turn: game.current_turn(),
-        };
+        let msg = game.state_message();
         drop(games);
         broadcast_to_room(room_code, msg, state).await;
     }
 }
 
+// Sends a chat line to everyone allowed to read its channel who has not muted chat
+#[cfg(feature = "ssr")]
+async fn deliver_chat(room_code: &str, message: ChatEntry, state: &AppState) {
+    let recipients: Vec<String> = {
+        let rooms = state.rooms.read().await;
+        let chats = state.chats.read().await;
+        let Some(room) = rooms.get(room_code) else {
+            return;
+        };
+
+        let players = match message.channel {
+            ChatChannel::Players => vec![&room.white_player, &room.black_player],
+            ChatChannel::Spectators => Vec::new(),
+        };
+        players
+            .into_iter()
+            .flatten()
+            .chain(room.spectators.iter())
+            .filter(|id| {
+                chats
+                    .get(room_code)
+                    .is_none_or(|chat| !chat.muted.contains(*id))
+            })
+            .cloned()
+            .collect()
+    };
+
+    for recipient in recipients {
+        send_to_player(
+            &recipient,
+            ServerMessage::ChatMessage {
+                message: message.clone(),
+            },
+            state,
+        )
+        .await;
+    }
+}
+
+#[cfg(feature = "ssr")]
+async fn send_chat_history(room_code: &str, player_id: &str, is_player: bool, state: &AppState) {
+    let messages = state
+        .chats
+        .read()
+        .await
+        .get(room_code)
+        .map(|chat| chat.visible_to(is_player))
+        .unwrap_or_default();
+
+    if !messages.is_empty() {
+        send_to_player(player_id, ServerMessage::ChatHistory { messages }, state).await;
+    }
+}
+
 #[cfg(feature = "ssr")]
 async fn send_to_player(player_id: &str, msg: ServerMessage, state: &AppState) {
     let sessions = state.sessions.read().await;
-    if let Some(tx) = sessions.get(player_id) {
-        let _ = tx.send(msg);
+    if let Some(session) = sessions.get(player_id) {
+        let _ = session.sender.send(msg);
+    }
+}
+
+// Sends to every socket the account is connected with
+#[cfg(feature = "ssr")]
+async fn send_to_account(account_id: &str, msg: ServerMessage, state: &AppState) {
+    let sessions = state.sessions.read().await;
+    for session in sessions.values() {
+        if session.account_id.as_deref() == Some(account_id) {
+            let _ = session.sender.send(msg.clone());
+        }
     }
 }
 
@@ -283,7 +2300,10 @@ async fn broadcast_to_room(room_code: &str, msg: ServerMessage, state: &AppState
             send_to_player(white, msg.clone(), state).await;
         }
         if let Some(black) = &room.black_player {
-            send_to_player(black, msg, state).await;
+            send_to_player(black, msg.clone(), state).await;
+        }
+        for spectator in &room.spectators {
+            send_to_player(spectator, msg.clone(), state).await;
         }
     }
 }
@@ -302,9 +2322,442 @@ async fn find_player_room(player_id: &str, state: &AppState) -> Option<(String,
     None
 }
 
+#[cfg(feature = "ssr")]
+async fn find_spectator_room(player_id: &str, state: &AppState) -> Option<String> {
+    let rooms = state.rooms.read().await;
+    rooms
+        .iter()
+        .find(|(_, room)| room.spectators.iter().any(|id| id == player_id))
+        .map(|(code, _)| code.clone())
+}
+
+// Handles a dropped connection: rooms nobody is left in are removed, and in a
+// running game the opponent is told and may claim the game once the grace
+// period passes without the player rejoining
 #[cfg(feature = "ssr")]
 async fn cleanup_player(player_id: &str, state: &AppState) {
-    if let Some((room_code, _)) = find_player_room(player_id, state).await {
-        broadcast_to_room(&room_code, ServerMessage::OpponentLeft, state).await;
+    let Some((room_code, color)) = find_player_room(player_id, state).await else {
+        return;
+    };
+
+    // A restored opponent who has not rejoined yet still holds the seat by token
+    let (opponent, opponent_seated) =
+        state
+            .rooms
+            .read()
+            .await
+            .get(&room_code)
+            .map_or((None, false), |room| match color {
+                PlayerColor::White => (room.black_player.clone(), room.black_token.is_some()),
+                PlayerColor::Black => (room.white_player.clone(), room.white_token.is_some()),
+            });
+    let opponent_connected = match &opponent {
+        Some(opponent_id) => state.sessions.read().await.contains_key(opponent_id),
+        None => false,
+    };
+    let game_over = state
+        .games
+        .read()
+        .await
+        .get(&room_code)
+        .is_none_or(|game| game.game_over);
+
+    if !opponent_seated || game_over {
+        if opponent_connected {
+            // Leaving a finished game gives up the seat, so no rematch can be
+            // offered to a player who is gone
+            if let Some(room) = state.rooms.write().await.get_mut(&room_code) {
+                match color {
+                    PlayerColor::White => {
+                        room.white_player = None;
+                        room.white_token = None;
+                    }
+                    PlayerColor::Black => {
+                        room.black_player = None;
+                        room.black_token = None;
+                    }
+                }
+                room.rematch_offer = None;
+            }
+            persist_room(&room_code, state).await;
+            broadcast_to_room(&room_code, ServerMessage::OpponentLeft, state).await;
+        } else {
+            remove_room(&room_code, state).await;
+        }
+        return;
+    }
+
+    let grace_ms = state.abandon_grace.as_millis() as u64;
+    broadcast_to_room(
+        &room_code,
+        ServerMessage::OpponentDisconnected { color, grace_ms },
+        state,
+    )
+    .await;
+
+    let player_id = player_id.to_string();
+    let state = state.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(state.abandon_grace).await;
+
+        // Rejoining rebinds the seat to a new connection id
+        if find_player_room(&player_id, &state).await.is_none() {
+            return;
+        }
+
+        let anyone_connected = {
+            let rooms = state.rooms.read().await;
+            let sessions = state.sessions.read().await;
+            rooms.get(&room_code).is_some_and(|room| {
+                [&room.white_player, &room.black_player]
+                    .into_iter()
+                    .flatten()
+                    .any(|id| sessions.contains_key(id))
+            })
+        };
+        if !anyone_connected {
+            remove_room(&room_code, &state).await;
+            return;
+        }
+
+        let abandoned = state
+            .games
+            .write()
+            .await
+            .get_mut(&room_code)
+            .is_some_and(|game| game.mark_abandoned(color));
+
+        if abandoned {
+            tracing::info!("Player {} abandoned room {}", player_id, room_code);
+            broadcast_to_room(
+                &room_code,
+                ServerMessage::OpponentAbandoned { color },
+                &state,
+            )
+            .await;
+        }
+    });
+}
+
+#[cfg(feature = "ssr")]
+async fn remove_spectator(player_id: &str, state: &AppState) {
+    let mut rooms = state.rooms.write().await;
+    let mut watched = Vec::new();
+    for (code, room) in rooms.iter_mut() {
+        let before = room.spectators.len();
+        room.spectators.retain(|id| id != player_id);
+        if room.spectators.len() != before {
+            watched.push((code.clone(), room.spectators.len()));
+        }
+    }
+    drop(rooms);
+
+    for (room_code, count) in watched {
+        broadcast_to_room(&room_code, ServerMessage::SpectatorCount { count }, state).await;
+    }
+}
+
+#[cfg(feature = "ssr")]
+async fn remove_room(room_code: &str, state: &AppState) {
+    tracing::info!("Removing room {}", room_code);
+    state.rooms.write().await.remove(room_code);
+    state.games.write().await.remove(room_code);
+    state.chats.write().await.remove(room_code);
+    if let Some(timer) = state.clock_timers.write().await.remove(room_code) {
+        timer.abort();
+    }
+    state
+        .writer
+        .queue(StorageWrite::RemoveRoom(room_code.to_string()));
+}
+
+// Accounts play under their own name; guests may pick one or stay anonymous,
+// as long as it is not a registered username
+#[cfg(feature = "ssr")]
+async fn seat_name(
+    account: Option<&Account>,
+    name: Option<String>,
+    state: &AppState,
+) -> Result<Option<String>, String> {
+    if let Some(account) = account {
+        return Ok(Some(account.name().to_string()));
+    }
+    let Some(name) = accounts::display_name(name)? else {
+        return Ok(None);
+    };
+    let username = name.clone();
+    match with_storage(state, move |storage| storage.account_by_username(&username)).await {
+        Ok(None) => Ok(Some(name)),
+        Ok(Some(_)) => {
+            Err("That name belongs to a registered player; log in to use it".to_string())
+        }
+        Err(err) => {
+            tracing::error!("Could not look up account {}: {}", name, err);
+            Err("Could not check that name, try again".to_string())
+        }
+    }
+}
+
+// Rated games are between accounts, on the clock, from the standard starting
+// position
+#[cfg(feature = "ssr")]
+fn rated_config(account: Option<&Account>, config: &RoomConfig) -> Result<(), String> {
+    if account.is_none() {
+        return Err("Log in to play rated games".to_string());
+    }
+    if config.time_control.is_none() {
+        return Err("Rated games need a time control".to_string());
+    }
+    if config.variant != Variant::Standard || config.start_fen.is_some() {
+        return Err("Only standard chess from the starting position can be rated".to_string());
+    }
+    Ok(())
+}
+
+// Stores the finished game, rates it if it was rated and tells the room
+#[cfg(feature = "ssr")]
+async fn finish_game(room_code: &str, result: GameResult, state: &AppState) {
+    persist_game(room_code, state).await;
+    let rating_changes = rate_game(room_code, &result, state).await;
+    broadcast_to_room(
+        room_code,
+        ServerMessage::GameOver {
+            result,
+            rating_changes,
+        },
+        state,
+    )
+    .await;
+}
+
+// Updates both players' ratings in the game's category. Each game is rated
+// once, however many times its result is reported
+#[cfg(feature = "ssr")]
+async fn rate_game(
+    room_code: &str,
+    result: &GameResult,
+    state: &AppState,
+) -> Option<RatingChanges> {
+    let (white_id, black_id) = state
+        .rooms
+        .read()
+        .await
+        .get(room_code)
+        .filter(|room| room.rated)
+        .and_then(|room| Some((room.white_account.clone()?, room.black_account.clone()?)))?;
+    let (game_id, category) = state.games.read().await.get(room_code).and_then(|game| {
+        Some((
+            game.id.clone(),
+            RatingCategory::of(game.time_control.as_ref()?),
+        ))
+    })?;
+
+    let (white, black) = match (
+        stored_rating(&white_id, category, state).await,
+        stored_rating(&black_id, category, state).await,
+    ) {
+        (Ok(white), Ok(black)) => (white, black),
+        (Err(err), _) | (_, Err(err)) => {
+            tracing::error!("Could not load ratings in room {}: {}", room_code, err);
+            return None;
+        }
+    };
+
+    let white_score = match result.winner() {
+        Some(PlayerColor::White) => 1.0,
+        Some(PlayerColor::Black) => 0.0,
+        None => 0.5,
+    };
+    let new_white = white.update(&black, white_score);
+    let new_black = black.update(&white, 1.0 - white_score);
+
+    let now = GameState::current_time_ms();
+    let recorded = with_storage(state, move |storage| {
+        let ratings = [
+            (white_id.as_str(), new_white),
+            (black_id.as_str(), new_black),
+        ];
+        storage.record_ratings(&game_id, category, &ratings, now)
+    })
+    .await;
+    match recorded {
+        Ok(true) => Some(RatingChanges {
+            category,
+            white: white.change_to(&new_white),
+            black: black.change_to(&new_black),
+        }),
+        Ok(false) => None,
+        Err(err) => {
+            tracing::error!("Could not store ratings in room {}: {}", room_code, err);
+            None
+        }
+    }
+}
+
+// The account's rating in the category, or a newcomer's if it has none yet
+#[cfg(feature = "ssr")]
+async fn stored_rating(
+    account_id: &str,
+    category: RatingCategory,
+    state: &AppState,
+) -> Result<Rating, String> {
+    let account_id = account_id.to_string();
+    let ratings = with_storage(state, move |storage| storage.ratings(&account_id)).await?;
+    Ok(ratings
+        .into_iter()
+        .find(|(rated_in, _)| *rated_in == category)
+        .map(|(_, rating)| rating)
+        .unwrap_or_default())
+}
+
+// Writes the setup and result of the room's current game to storage, and
+// archives the game once it is over
+#[cfg(feature = "ssr")]
+async fn persist_game(room_code: &str, state: &AppState) {
+    let Some(room) = state.rooms.read().await.get(room_code).cloned() else {
+        return;
+    };
+    // Queued under the lock so a later snapshot cannot overtake this one
+    let games = state.games.read().await;
+    let Some(game) = games.get(room_code) else {
+        return;
+    };
+    state.writer.queue(StorageWrite::SaveGame(StoredGame::new(
+        room_code,
+        &room.config,
+        game,
+    )));
+    if let Some(archived) = ArchivedGame::new(
+        room_code,
+        game,
+        room.rated,
+        (room.white_name.as_deref(), room.white_account.as_deref()),
+        (room.black_name.as_deref(), room.black_account.as_deref()),
+    ) {
+        state.writer.queue(StorageWrite::ArchiveGame(archived));
+    }
+}
+
+// Writes the room's seats, score and current game to storage
+#[cfg(feature = "ssr")]
+async fn persist_room(room_code: &str, state: &AppState) {
+    let Some(room) = state.rooms.read().await.get(room_code).cloned() else {
+        return;
+    };
+    let games = state.games.read().await;
+    if let Some(game) = games.get(room_code) {
+        state
+            .writer
+            .queue(StorageWrite::SaveRoom(StoredRoom::new(&room, game)));
+    }
+}
+
+// Brings back every room whose game was still going when the server stopped.
+// Players return to their seats with their rejoin tokens
+#[cfg(feature = "ssr")]
+async fn restore_games(state: &AppState) {
+    let stored = match state.storage.ongoing_games() {
+        Ok(stored) => stored,
+        Err(err) => {
+            tracing::error!("Could not load stored games: {}", err);
+            return;
+        }
+    };
+
+    for (room, game, moves) in stored {
+        let game = match GameState::restore(&game, &moves) {
+            Ok(game) => game,
+            Err(err) => {
+                tracing::error!("Could not restore room {}: {}", room.room_code, err);
+                continue;
+            }
+        };
+
+        let room_code = room.room_code.clone();
+        let clock_running = room.black_token.is_some();
+        state.rooms.write().await.insert(
+            room_code.clone(),
+            GameRoom {
+                room_code: room.room_code,
+                white_player: None,
+                black_player: None,
+                white_token: room.white_token,
+                black_token: room.black_token,
+                white_name: room.white_name,
+                black_name: room.black_name,
+                white_account: room.white_account,
+                black_account: room.black_account,
+                rated: room.rated,
+                spectators: Vec::new(),
+                config: room.config,
+                rematch_offer: None,
+                score: room.score,
+            },
+        );
+        state.games.write().await.insert(room_code.clone(), game);
+
+        // The clock only runs once both players have joined
+        if clock_running {
+            schedule_flag_check(&room_code, state).await;
+        }
+        tokio::spawn(release_unclaimed_seats(
+            room_code.clone(),
+            true,
+            state.clone(),
+        ));
+        tracing::info!("Restored room {}", room_code);
+    }
+}
+
+// Seats of a restored or matched room nobody has taken once the grace period
+// is over are treated like any abandoned seat. A matched room nobody came to
+// is removed, while a restored game stays stored until its players return
+#[cfg(feature = "ssr")]
+async fn release_unclaimed_seats(room_code: String, restored: bool, state: AppState) {
+    tokio::time::sleep(state.abandon_grace).await;
+
+    let seats = state.rooms.read().await.get(&room_code).map(|room| {
+        [
+            (
+                PlayerColor::White,
+                room.white_token.is_some(),
+                room.white_player.is_some(),
+            ),
+            (
+                PlayerColor::Black,
+                room.black_token.is_some(),
+                room.black_player.is_some(),
+            ),
+        ]
+    });
+    let Some(seats) = seats else {
+        return;
+    };
+
+    if !seats.iter().any(|&(_, _, reclaimed)| reclaimed) {
+        if !restored {
+            remove_room(&room_code, &state).await;
+        }
+        return;
+    }
+
+    for (color, taken, reclaimed) in seats {
+        if !taken || reclaimed {
+            continue;
+        }
+        let abandoned = state
+            .games
+            .write()
+            .await
+            .get_mut(&room_code)
+            .is_some_and(|game| game.mark_abandoned(color));
+        if abandoned {
+            broadcast_to_room(
+                &room_code,
+                ServerMessage::OpponentAbandoned { color },
+                &state,
+            )
+            .await;
+        }
     }
 }
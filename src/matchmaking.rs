@@ -0,0 +1,129 @@
+#[cfg(feature = "ssr")]
+use crate::shared::{ColorPreference, TimeControl};
+
+// Every this often a waiting seek accepts opponents another
+// `RATING_WINDOW_STEP` points further away
+#[cfg(feature = "ssr")]
+pub const RATING_WINDOW_INTERVAL_MS: u64 = 10_000;
+#[cfg(feature = "ssr")]
+const RATING_WINDOW_STEP: f64 = 50.0;
+
+// One side of a game arranged by the server, by seek, open challenge or
+// direct challenge
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub struct MatchPlayer {
+    pub player_id: String,
+    pub account_id: Option<String>,
+    pub name: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl MatchPlayer {
+    // Whether this is the given socket, or the given account on any socket
+    pub fn is_player(&self, player_id: &str, account_id: Option<&str>) -> bool {
+        self.player_id == player_id
+            || (account_id.is_some() && self.account_id.as_deref() == account_id)
+    }
+}
+
+// A player waiting to be paired
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub struct Seek {
+    pub player: MatchPlayer,
+    pub rating: f64, // in the time control's category; guests count as 1500
+    pub time_control: Option<TimeControl>,
+    pub rated: bool,
+    pub rating_range: Option<u32>,
+    pub color_preference: ColorPreference,
+    pub since: u64,
+}
+
+#[cfg(feature = "ssr")]
+impl Seek {
+    // How far an opponent's rating may be from ours after waiting until
+    // `now`; None accepts anyone
+    fn rating_window(&self, now: u64) -> Option<f64> {
+        let widenings = now.saturating_sub(self.since) / RATING_WINDOW_INTERVAL_MS;
+        self.rating_range
+            .map(|range| range as f64 + widenings as f64 * RATING_WINDOW_STEP)
+    }
+
+    fn accepts_rating(&self, rating: f64, now: u64) -> bool {
+        self.rating_window(now)
+            .is_none_or(|window| (self.rating - rating).abs() <= window)
+    }
+
+    fn can_play(&self, other: &Seek, now: u64) -> bool {
+        let same_account =
+            self.player.account_id.is_some() && self.player.account_id == other.player.account_id;
+        let same_color = self.color_preference != ColorPreference::Random
+            && self.color_preference == other.color_preference;
+        !same_account
+            && !same_color
+            && self.time_control == other.time_control
+            && self.rated == other.rated
+            && self.accepts_rating(other.rating, now)
+            && other.accepts_rating(self.rating, now)
+    }
+}
+
+// Seeks waiting for an opponent, oldest first
+#[cfg(feature = "ssr")]
+#[derive(Debug, Default)]
+pub struct SeekQueue {
+    seeks: Vec<Seek>,
+}
+
+#[cfg(feature = "ssr")]
+impl SeekQueue {
+    // A player has one seek at a time; seeking again replaces it
+    pub fn add(&mut self, seek: Seek) {
+        self.remove(&seek.player.player_id);
+        self.seeks.push(seek);
+    }
+
+    pub fn remove(&mut self, player_id: &str) -> bool {
+        let before = self.seeks.len();
+        self.seeks.retain(|seek| seek.player.player_id != player_id);
+        self.seeks.len() != before
+    }
+
+    // Takes every pair that can play each other out of the queue. The longest
+    // waiting seeks are paired first, each with the closest rated opponent
+    pub fn take_matches(&mut self, now: u64) -> Vec<(Seek, Seek)> {
+        let mut matches = Vec::new();
+        let mut i = 0;
+        while i < self.seeks.len() {
+            let seek = &self.seeks[i];
+            let opponent = (i + 1..self.seeks.len())
+                .filter(|&j| seek.can_play(&self.seeks[j], now))
+                .min_by(|&a, &b| {
+                    let distance = |j: usize| (self.seeks[j].rating - seek.rating).abs();
+                    distance(a).total_cmp(&distance(b))
+                });
+            match opponent {
+                Some(j) => {
+                    let opponent = self.seeks.remove(j);
+                    let seek = self.seeks.remove(i);
+                    matches.push((seek, opponent));
+                }
+                None => i += 1,
+            }
+        }
+        matches
+    }
+}
+
+// Whether the first of two players gets white. Preferences are honoured
+// where they do not clash, the first player's before the second's; otherwise
+// a coin decides
+#[cfg(feature = "ssr")]
+pub fn first_plays_white(first: ColorPreference, second: ColorPreference) -> bool {
+    match (first, second) {
+        (ColorPreference::White, _) | (_, ColorPreference::Black) => true,
+        (ColorPreference::Black, _) | (_, ColorPreference::White) => false,
+        _ => uuid::Uuid::new_v4().as_u128().is_multiple_of(2),
+    }
+}
@@ -0,0 +1,285 @@
+#[cfg(feature = "ssr")]
+use crate::eco;
+#[cfg(feature = "ssr")]
+use crate::game::{clock_after_move, GameState};
+#[cfg(feature = "ssr")]
+use crate::shared::{GameResult, PlayerColor, TimeControl, Variant};
+
+// Export format lines must stay below 80 characters
+#[cfg(feature = "ssr")]
+const MAX_LINE_LEN: usize = 79;
+
+// Renders a game as PGN with the Seven Tag Roster, time control, termination,
+// opening and the clock each player had left after every move
+#[cfg(feature = "ssr")]
+pub fn export(
+    game: &GameState,
+    room_code: &str,
+    rated: bool,
+    white: Option<&str>,
+    black: Option<&str>,
+) -> String {
+    let result = result_tag(game.result.as_ref());
+    let time_control = game
+        .time_control
+        .as_ref()
+        .map_or_else(|| "-".to_string(), time_control_tag);
+
+    let mut tags = vec![
+        (
+            "Event",
+            if rated { "Rated game" } else { "Casual game" }.to_string(),
+        ),
+        ("Site", format!("Room {}", room_code)),
+        ("Date", format_date(game.started_at)),
+        ("Round", "-".to_string()),
+        ("White", white.unwrap_or("?").to_string()),
+        ("Black", black.unwrap_or("?").to_string()),
+        ("Result", result.to_string()),
+        ("TimeControl", time_control),
+        (
+            "Termination",
+            termination_tag(game.result.as_ref()).to_string(),
+        ),
+    ];
+    if let Some(variant) = variant_tag(game.variant) {
+        tags.push(("Variant", variant.to_string()));
+    }
+    if let Some(fen) = &game.start_fen {
+        tags.push(("SetUp", "1".to_string()));
+        tags.push(("FEN", fen.clone()));
+    }
+    if let Some((eco, name)) = opening(game) {
+        tags.push(("ECO", eco.to_string()));
+        tags.push(("Opening", name.to_string()));
+    }
+
+    let mut pgn = String::new();
+    for (name, value) in tags {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, escape(&value)));
+    }
+    pgn.push('\n');
+    pgn.push_str(&wrap(&movetext(game, result)));
+    pgn.push('\n');
+    pgn
+}
+
+#[cfg(feature = "ssr")]
+fn movetext(game: &GameState, result: &str) -> Vec<String> {
+    let clocks = move_clocks(game);
+    let mut tokens = Vec::new();
+
+    let black_first = usize::from(game.start_turn == PlayerColor::Black);
+
+    for (ply, record) in game.moves.iter().enumerate() {
+        let number = game.first_move_number as usize + (ply + black_first) / 2;
+        if (ply + black_first).is_multiple_of(2) {
+            tokens.push(format!("{}.", number));
+        } else if ply == 0 {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(record.san.clone());
+        if let Some(clock) = clocks.get(ply) {
+            tokens.push(format!("{{[%clk {}]}}", format_clock(*clock)));
+        }
+    }
+    tokens.push(result.to_string());
+
+    tokens
+}
+
+// Replays the clocks from the move timestamps; empty for untimed games
+#[cfg(feature = "ssr")]
+fn move_clocks(game: &GameState) -> Vec<u64> {
+    let Some(time_control) = &game.time_control else {
+        return Vec::new();
+    };
+
+    // Indexed by mover, so a start position with black to move works too
+    let black_first = usize::from(game.start_turn == PlayerColor::Black);
+    let mut clocks = [time_control.base_ms; 2];
+    let mut previous = game.started_at;
+    game.moves
+        .iter()
+        .enumerate()
+        .map(|(ply, record)| {
+            let mover = (ply + black_first) % 2;
+            let elapsed = record.timestamp.saturating_sub(previous);
+            previous = record.timestamp;
+            clocks[mover] = clock_after_move(time_control, clocks[mover], elapsed, ply);
+            clocks[mover]
+        })
+        .collect()
+}
+
+// ECO code and name, for standard games from the usual starting position
+#[cfg(feature = "ssr")]
+pub fn opening(game: &GameState) -> Option<(&'static str, &'static str)> {
+    if game.variant != Variant::Standard || game.start_fen.is_some() {
+        return None;
+    }
+    eco::classify(&game.moves)
+}
+
+#[cfg(feature = "ssr")]
+fn variant_tag(variant: Variant) -> Option<&'static str> {
+    match variant {
+        Variant::Standard => None,
+        Variant::Chess960 { .. } => Some("Chess960"),
+        Variant::KingOfTheHill => Some("King of the Hill"),
+        Variant::ThreeCheck => Some("Three-check"),
+        Variant::RacingKings => Some("Racing Kings"),
+        Variant::Crazyhouse => Some("Crazyhouse"),
+        Variant::Atomic => Some("Atomic"),
+        Variant::Antichess => Some("Antichess"),
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub fn result_tag(result: Option<&GameResult>) -> &'static str {
+    match result {
+        None => "*",
+        Some(result) => match result.winner() {
+            Some(PlayerColor::White) => "1-0",
+            Some(PlayerColor::Black) => "0-1",
+            None => "1/2-1/2",
+        },
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn termination_tag(result: Option<&GameResult>) -> &'static str {
+    match result {
+        None => "unterminated",
+        Some(GameResult::Timeout { .. } | GameResult::TimeoutVsInsufficientMaterial) => {
+            "time forfeit"
+        }
+        Some(GameResult::Abandoned { .. } | GameResult::DrawByAbandonment) => "abandoned",
+        Some(_) => "normal",
+    }
+}
+
+// "base+increment" in seconds, with "moves/seconds" periods for staged controls
+#[cfg(feature = "ssr")]
+pub fn time_control_tag(time_control: &TimeControl) -> String {
+    let period = |ms: u64| {
+        if time_control.increment_ms > 0 {
+            format!("{}+{}", ms / 1000, time_control.increment_ms / 1000)
+        } else {
+            (ms / 1000).to_string()
+        }
+    };
+
+    let mut periods = Vec::new();
+    let mut base_ms = time_control.base_ms;
+    let mut moves_before = 0;
+    for stage in &time_control.stages {
+        periods.push(format!(
+            "{}/{}",
+            stage.after_moves - moves_before,
+            period(base_ms)
+        ));
+        moves_before = stage.after_moves;
+        base_ms = stage.added_ms;
+    }
+    periods.push(period(base_ms));
+
+    periods.join(":")
+}
+
+#[cfg(feature = "ssr")]
+fn format_clock(ms: u64) -> String {
+    let secs = ms / 1000;
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+// "YYYY.MM.DD" for a unix timestamp in milliseconds
+#[cfg(feature = "ssr")]
+fn format_date(timestamp_ms: u64) -> String {
+    // Days to civil date, after Howard Hinnant's algorithm
+    let days = (timestamp_ms / 86_400_000) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+// Unix timestamp in milliseconds of midnight UTC on a "YYYY-MM-DD" or
+// "YYYY.MM.DD" date
+#[cfg(feature = "ssr")]
+pub fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.split(['-', '.']);
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Civil date to days, the inverse of `format_date`
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let shifted_month = (month + 9) % 12;
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    u64::try_from(days).ok().map(|days| days * 86_400_000)
+}
+
+#[cfg(feature = "ssr")]
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(feature = "ssr")]
+fn wrap(tokens: &[String]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LEN {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(token);
+    }
+    lines.push(line);
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::RoomConfig;
+
+    #[test]
+    fn event_tag_says_whether_the_game_was_rated() {
+        let game = GameState::new(&RoomConfig {
+            allow_takebacks: false,
+            time_control: Some(TimeControl::new(180_000, 2_000)),
+            start_fen: None,
+            variant: Variant::Standard,
+        })
+        .unwrap();
+        let casual = export(&game, "ROOM42", false, Some("Alice"), Some("Bob"));
+        let rated = export(&game, "ROOM42", true, Some("Alice"), Some("Bob"));
+        assert!(casual.starts_with("[Event \"Casual game\"]\n"));
+        assert!(rated.starts_with("[Event \"Rated game\"]\n"));
+    }
+}
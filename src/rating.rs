@@ -0,0 +1,217 @@
+// Glicko-2, with every rated game treated as a rating period of its own
+// (http://www.glicko.net/glicko/glicko2.pdf)
+
+#[cfg(feature = "ssr")]
+use crate::shared::RatingChange;
+#[cfg(feature = "ssr")]
+use std::f64::consts::PI;
+
+// Converts between the Glicko and Glicko-2 scales
+#[cfg(feature = "ssr")]
+const SCALE: f64 = 173.7178;
+// Constrains how fast volatility may change
+#[cfg(feature = "ssr")]
+const TAU: f64 = 0.5;
+#[cfg(feature = "ssr")]
+const CONVERGENCE: f64 = 0.000_001;
+#[cfg(feature = "ssr")]
+const MIN_DEVIATION: f64 = 45.0;
+#[cfg(feature = "ssr")]
+const MAX_DEVIATION: f64 = 350.0;
+// Ratings stay provisional until the deviation drops below this
+#[cfg(feature = "ssr")]
+const PROVISIONAL_DEVIATION: f64 = 110.0;
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+    pub games: u32,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            rating: 1500.0,
+            deviation: MAX_DEVIATION,
+            volatility: 0.06,
+            games: 0,
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl Rating {
+    pub fn is_provisional(&self) -> bool {
+        self.deviation > PROVISIONAL_DEVIATION
+    }
+
+    // The rating after one game against `opponent`, scoring 1 for a win, 0.5
+    // for a draw and 0 for a loss
+    pub fn update(&self, opponent: &Rating, score: f64) -> Rating {
+        self.rate_period(&[(*opponent, score)])
+    }
+
+    // Steps 2 to 8 of the paper for a rating period with these results
+    fn rate_period(&self, results: &[(Rating, f64)]) -> Rating {
+        let mu = (self.rating - 1500.0) / SCALE;
+        let phi = self.deviation / SCALE;
+
+        let (mut information, mut improvement) = (0.0, 0.0);
+        for (opponent, score) in results {
+            let opponent_mu = (opponent.rating - 1500.0) / SCALE;
+            let opponent_phi = opponent.deviation / SCALE;
+            let g = 1.0 / (1.0 + 3.0 * opponent_phi.powi(2) / PI.powi(2)).sqrt();
+            let expected = 1.0 / (1.0 + (-g * (mu - opponent_mu)).exp());
+            information += g.powi(2) * expected * (1.0 - expected);
+            improvement += g * (score - expected);
+        }
+        let variance = 1.0 / information;
+        let delta = variance * improvement;
+
+        let volatility = self.new_volatility(phi, variance, delta);
+        let phi_star = (phi.powi(2) + volatility.powi(2)).sqrt();
+        let new_phi = 1.0 / (1.0 / phi_star.powi(2) + 1.0 / variance).sqrt();
+        let new_mu = mu + new_phi.powi(2) * improvement;
+
+        Rating {
+            rating: 1500.0 + SCALE * new_mu,
+            deviation: (SCALE * new_phi).clamp(MIN_DEVIATION, MAX_DEVIATION),
+            volatility,
+            games: self.games + results.len() as u32,
+        }
+    }
+
+    // Step 5 of the paper, solved with the Illinois algorithm
+    fn new_volatility(&self, phi: f64, variance: f64, delta: f64) -> f64 {
+        let a = self.volatility.powi(2).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta.powi(2) - phi.powi(2) - variance - ex)
+                / (2.0 * (phi.powi(2) + variance + ex).powi(2))
+                - (x - a) / TAU.powi(2)
+        };
+
+        let mut low = a;
+        let mut high = if delta.powi(2) > phi.powi(2) + variance {
+            (delta.powi(2) - phi.powi(2) - variance).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * TAU
+        };
+        let mut f_low = f(low);
+        let mut f_high = f(high);
+        while (high - low).abs() > CONVERGENCE {
+            let next = low + (low - high) * f_low / (f_high - f_low);
+            let f_next = f(next);
+            if f_next * f_high <= 0.0 {
+                low = high;
+                f_low = f_high;
+            } else {
+                f_low /= 2.0;
+            }
+            high = next;
+            f_high = f_next;
+        }
+        (low / 2.0).exp()
+    }
+
+    // How a game moved this rating, as shown to the players
+    pub fn change_to(&self, after: &Rating) -> RatingChange {
+        RatingChange {
+            rating: after.rating.round() as i32,
+            delta: after.rating.round() as i32 - self.rating.round() as i32,
+            provisional: after.is_provisional(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rating(rating: f64, deviation: f64) -> Rating {
+        Rating {
+            rating,
+            deviation,
+            ..Default::default()
+        }
+    }
+
+    // The worked example from the paper, as a period of three games
+    #[test]
+    fn matches_the_example_in_the_paper() {
+        let player = rating(1500.0, 200.0);
+        let after = player.rate_period(&[
+            (rating(1400.0, 30.0), 1.0),
+            (rating(1550.0, 100.0), 0.0),
+            (rating(1700.0, 300.0), 0.0),
+        ]);
+        assert!((after.rating - 1464.06).abs() < 0.01, "{}", after.rating);
+        assert!(
+            (after.deviation - 151.52).abs() < 0.01,
+            "{}",
+            after.deviation
+        );
+        assert!(
+            (after.volatility - 0.05999).abs() < 0.00001,
+            "{}",
+            after.volatility
+        );
+        assert_eq!(after.games, 3);
+    }
+
+    // Played one game per period instead, the same results land close by
+    #[test]
+    fn rates_one_game_per_period() {
+        let after = [
+            (rating(1400.0, 30.0), 1.0),
+            (rating(1550.0, 100.0), 0.0),
+            (rating(1700.0, 300.0), 0.0),
+        ]
+        .iter()
+        .fold(rating(1500.0, 200.0), |player, (opponent, score)| {
+            player.update(opponent, *score)
+        });
+        assert!((after.rating - 1464.06).abs() < 1.0, "{}", after.rating);
+        assert!(
+            (after.deviation - 151.52).abs() < 1.0,
+            "{}",
+            after.deviation
+        );
+        assert_eq!(after.games, 3);
+
+        // Between equals a win gains what the loss costs, and a draw is even
+        let (winner, loser) = (Rating::default(), Rating::default());
+        let gained = winner.update(&loser, 1.0).rating - 1500.0;
+        let lost = 1500.0 - loser.update(&winner, 0.0).rating;
+        assert!(gained > 0.0 && (gained - lost).abs() < 1e-9);
+        assert!((winner.update(&loser, 0.5).rating - 1500.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ratings_settle_below_the_provisional_threshold() {
+        assert!(Rating::default().is_provisional());
+        assert!(!rating(1500.0, PROVISIONAL_DEVIATION).is_provisional());
+        assert!(rating(1500.0, PROVISIONAL_DEVIATION + 0.1).is_provisional());
+
+        let opponent = rating(1500.0, 60.0);
+        let mut player = Rating::default();
+        let mut games = 0;
+        while player.is_provisional() {
+            let next = player.update(&opponent, (games % 2) as f64);
+            assert!(next.deviation < player.deviation);
+            let change = player.change_to(&next);
+            assert_eq!(change.provisional, next.is_provisional());
+            player = next;
+            games += 1;
+        }
+        assert!((8..=12).contains(&games), "{}", games);
+    }
+}
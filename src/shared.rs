@@ -1,10 +1,221 @@
 use serde::{Deserialize, Serialize};
 
+pub mod pgn;
+pub mod position;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameRoom {
     pub room_code: String,
     pub white_player: Option<String>,
     pub black_player: Option<String>,
+    pub white_token: Option<String>,
+    pub black_token: Option<String>,
+    pub white_name: Option<String>, // shown in PGN and the game archive
+    pub black_name: Option<String>,
+    pub white_account: Option<String>, // account ids; None for guests
+    pub black_account: Option<String>,
+    pub rated: bool,
+    pub spectators: Vec<String>,
+    pub config: RoomConfig,
+    pub rematch_offer: Option<PlayerColor>,
+    pub score: MatchScore,
+}
+
+impl GameRoom {
+    // Offering while the opponent's offer is open accepts it; returns whether
+    // the rematch should start now
+    pub fn offer_rematch(&mut self, color: PlayerColor) -> Result<bool, String> {
+        if self.white_player.is_none() || self.black_player.is_none() {
+            return Err("Your opponent has left the room".to_string());
+        }
+
+        match self.rematch_offer {
+            Some(by) if by == color => Err("You have already offered a rematch".to_string()),
+            Some(_) => Ok(true),
+            None => {
+                self.rematch_offer = Some(color);
+                Ok(false)
+            }
+        }
+    }
+
+    pub fn accept_rematch(&mut self, color: PlayerColor) -> Result<(), String> {
+        if self.rematch_offer != Some(color.opponent()) {
+            return Err("There is no rematch offer to accept".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn decline_rematch(&mut self, color: PlayerColor) -> Result<(), String> {
+        if self.rematch_offer != Some(color.opponent()) {
+            return Err("There is no rematch offer to decline".to_string());
+        }
+
+        self.rematch_offer = None;
+        Ok(())
+    }
+
+    // The seat the account holds; guests hold seats by connection and token only
+    pub fn seat_of_account(&self, account: Option<&Account>) -> Option<PlayerColor> {
+        let id = Some(&account?.id);
+        if self.white_account.as_ref() == id {
+            Some(PlayerColor::White)
+        } else if self.black_account.as_ref() == id {
+            Some(PlayerColor::Black)
+        } else {
+            None
+        }
+    }
+
+    // Credits the finished game to the match score and swaps the seats so each
+    // player takes the other color
+    pub fn start_rematch(&mut self, result: Option<&GameResult>) {
+        if let Some(result) = result {
+            self.score.record(result);
+        }
+
+        std::mem::swap(&mut self.white_player, &mut self.black_player);
+        std::mem::swap(&mut self.white_token, &mut self.black_token);
+        std::mem::swap(&mut self.white_name, &mut self.black_name);
+        std::mem::swap(&mut self.white_account, &mut self.black_account);
+        std::mem::swap(&mut self.score.white, &mut self.score.black);
+        self.rematch_offer = None;
+    }
+}
+
+// Points scored across all games in a room, by the players' current colors
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct MatchScore {
+    pub white: f32,
+    pub black: f32,
+}
+
+impl MatchScore {
+    pub fn record(&mut self, result: &GameResult) {
+        match result.winner() {
+            Some(PlayerColor::White) => self.white += 1.0,
+            Some(PlayerColor::Black) => self.black += 1.0,
+            None => {
+                self.white += 0.5;
+                self.black += 0.5;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomConfig {
+    pub allow_takebacks: bool,
+    pub time_control: Option<TimeControl>, // None means untimed
+    pub start_fen: Option<String>,         // None means the standard starting position
+    pub variant: Variant,
+}
+
+impl Default for RoomConfig {
+    fn default() -> Self {
+        Self {
+            allow_takebacks: true,
+            time_control: Some(TimeControl::new(600_000, 0)),
+            start_fen: None,
+            variant: Variant::Standard,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Variant {
+    Standard,
+    // Position 0-959 in the standard numbering; None asks the server to pick one
+    Chess960 { position: Option<u16> },
+    KingOfTheHill,
+    ThreeCheck,
+    RacingKings,
+    Crazyhouse,
+    Atomic,
+    Antichess,
+}
+
+impl Variant {
+    pub fn label(&self) -> String {
+        match self {
+            Variant::Standard => "Standard".to_string(),
+            Variant::Chess960 {
+                position: Some(position),
+            } => format!("Chess960 #{}", position),
+            Variant::Chess960 { position: None } => "Chess960".to_string(),
+            Variant::KingOfTheHill => "King of the Hill".to_string(),
+            Variant::ThreeCheck => "Three-check".to_string(),
+            Variant::RacingKings => "Racing Kings".to_string(),
+            Variant::Crazyhouse => "Crazyhouse".to_string(),
+            Variant::Atomic => "Atomic".to_string(),
+            Variant::Antichess => "Antichess".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base_ms: u64,
+    pub increment_ms: u64,
+    pub delay: Option<Delay>,
+    pub stages: Vec<TimeControlStage>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Delay {
+    // The clock only starts running once the delay has passed
+    Simple { ms: u64 },
+    // Time used is given back after the move, up to the delay
+    Bronstein { ms: u64 },
+}
+
+// Extra time granted once a player completes `after_moves` moves,
+// e.g. 40 moves in 90 minutes, then 30 more minutes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TimeControlStage {
+    pub after_moves: u32,
+    pub added_ms: u64,
+}
+
+impl TimeControl {
+    pub fn new(base_ms: u64, increment_ms: u64) -> Self {
+        Self {
+            base_ms,
+            increment_ms,
+            delay: None,
+            stages: Vec::new(),
+        }
+    }
+
+    // Short human readable form such as "10+5", "90/40+30" or "5 d3"
+    pub fn label(&self) -> String {
+        let minutes = |ms: u64| {
+            if ms.is_multiple_of(60_000) {
+                (ms / 60_000).to_string()
+            } else {
+                format!("{}", ms as f64 / 60_000.0)
+            }
+        };
+
+        let mut label = minutes(self.base_ms);
+        for stage in &self.stages {
+            label.push_str(&format!(
+                "/{}+{}",
+                stage.after_moves,
+                minutes(stage.added_ms)
+            ));
+        }
+        if self.stages.is_empty() || self.increment_ms > 0 {
+            label.push_str(&format!("+{}", self.increment_ms / 1000));
+        }
+        match self.delay {
+            Some(Delay::Simple { ms }) => label.push_str(&format!(" d{}", ms / 1000)),
+            Some(Delay::Bronstein { ms }) => label.push_str(&format!(" b{}", ms / 1000)),
+            None => {}
+        }
+        label
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -13,6 +224,82 @@ pub enum PlayerColor {
     Black,
 }
 
+impl PlayerColor {
+    pub fn opponent(self) -> Self {
+        match self {
+            PlayerColor::White => PlayerColor::Black,
+            PlayerColor::Black => PlayerColor::White,
+        }
+    }
+}
+
+// Checks given by each side, counted towards Three-check's win condition
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CheckCount {
+    pub white: u8,
+    pub black: u8,
+}
+
+// Pieces that can be held in a Crazyhouse pocket, in display order
+pub const POCKET_PIECES: [char; 5] = ['p', 'n', 'b', 'r', 'q'];
+
+// Captured pieces a Crazyhouse player may drop, counted in `POCKET_PIECES` order
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Pocket {
+    pub counts: [u8; 5],
+}
+
+impl Pocket {
+    pub fn count(&self, piece: char) -> u8 {
+        pocket_index(piece).map_or(0, |index| self.counts[index])
+    }
+
+    pub fn add(&mut self, piece: char) {
+        if let Some(index) = pocket_index(piece) {
+            self.counts[index] += 1;
+        }
+    }
+
+    // Removes one `piece`, returning false if there was none to take
+    pub fn take(&mut self, piece: char) -> bool {
+        match pocket_index(piece) {
+            Some(index) if self.counts[index] > 0 => {
+                self.counts[index] -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn pocket_index(piece: char) -> Option<usize> {
+    POCKET_PIECES
+        .iter()
+        .position(|&p| p == piece.to_ascii_lowercase())
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Pockets {
+    pub white: Pocket,
+    pub black: Pocket,
+}
+
+impl Pockets {
+    pub fn get(&self, color: PlayerColor) -> &Pocket {
+        match color {
+            PlayerColor::White => &self.white,
+            PlayerColor::Black => &self.black,
+        }
+    }
+
+    pub fn get_mut(&mut self, color: PlayerColor) -> &mut Pocket {
+        match color {
+            PlayerColor::White => &mut self.white,
+            PlayerColor::Black => &mut self.black,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MoveRecord {
     pub san: String, // Standard Algebraic Notation
@@ -21,20 +308,167 @@ pub struct MoveRecord {
     pub timestamp: u64,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ChatRole {
+    Player(PlayerColor),
+    Spectator,
+}
+
+// Spectator chat is kept away from the players
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ChatChannel {
+    Players,
+    Spectators,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatEntry {
+    pub role: ChatRole,
+    pub channel: ChatChannel,
+    pub text: String,
+    pub timestamp: u64,
+}
+
+// The color a player asks for when looking for an opponent
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ColorPreference {
+    #[default]
+    Random,
+    White,
+    Black,
+}
+
+// A challenge in the lobby, as everyone sees it. Served by `GET /api/lobby`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OpenChallenge {
+    pub id: String,
+    pub name: Option<String>, // None for anonymous guests
+    pub rating: Option<i32>,  // the poster's, for accounts with a timed challenge
+    pub time_control: Option<TimeControl>,
+    pub variant: Variant,
+    pub rated: bool,
+    pub color: ColorPreference, // the poster's color
+    pub created_at: u64,
+}
+
+// A challenge from one player to another, both by username
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DirectChallenge {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+    pub time_control: Option<TimeControl>,
+    pub variant: Variant,
+    pub rated: bool,
+    pub color: ColorPreference, // the challenger's color
+    pub expires_at: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ClientMessage {
     CreateRoom {
         room_code: String,
+        config: RoomConfig,
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(default)]
+        rated: bool,
     },
     JoinRoom {
         room_code: String,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    Rejoin {
+        room_code: String,
+        token: String,
+    },
+    Spectate {
+        room_code: String,
     },
     MakeMove {
         from: String,
         to: String,
         promotion: Option<String>,
     },
+    // Crazyhouse: `piece` is a letter from `POCKET_PIECES`
+    DropPiece {
+        piece: String,
+        square: String,
+    },
     Resign,
+    ClaimDraw,
+    ClaimVictory,
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw,
+    RequestTakeback,
+    AcceptTakeback,
+    DeclineTakeback,
+    OfferRematch,
+    AcceptRematch,
+    DeclineRematch,
+    Chat {
+        text: String,
+    },
+    MuteChat {
+        muted: bool,
+    },
+    // Asks the server to pair us with someone seeking the same time control.
+    // `rating_range` is how far the opponent's rating may be from ours at
+    // first; None accepts anyone
+    Seek {
+        time_control: Option<TimeControl>,
+        #[serde(default)]
+        rated: bool,
+        #[serde(default)]
+        rating_range: Option<u32>,
+        #[serde(default)]
+        color_preference: ColorPreference,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    CancelSeek,
+    // Subscribes to the lobby's open challenges as they come and go
+    WatchLobby,
+    // Posts a challenge anyone may accept; a player has one at a time
+    PostOpenChallenge {
+        time_control: Option<TimeControl>,
+        variant: Variant,
+        #[serde(default)]
+        rated: bool,
+        #[serde(default)]
+        color: ColorPreference,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    CancelOpenChallenge,
+    AcceptOpenChallenge {
+        id: String,
+        #[serde(default)]
+        name: Option<String>,
+    },
+    // Challenges a logged-in player by username, on every device they use
+    Challenge {
+        to_user: String,
+        time_control: Option<TimeControl>,
+        variant: Variant,
+        #[serde(default)]
+        color: ColorPreference,
+        #[serde(default)]
+        rated: bool,
+    },
+    AcceptChallenge {
+        id: String,
+    },
+    DeclineChallenge {
+        id: String,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    CancelChallenge {
+        id: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,10 +476,26 @@ pub enum ServerMessage {
     RoomCreated {
         room_code: String,
         player_color: PlayerColor,
+        config: RoomConfig,
+        rejoin_token: String,
     },
     RoomJoined {
         room_code: String,
         player_color: PlayerColor,
+        config: RoomConfig,
+        rejoin_token: String,
+    },
+    Rejoined {
+        room_code: String,
+        player_color: PlayerColor,
+        config: RoomConfig,
+    },
+    Spectating {
+        room_code: String,
+        config: RoomConfig,
+    },
+    SpectatorCount {
+        count: usize,
     },
     GameState {
         fen: String,
@@ -53,6 +503,8 @@ pub enum ServerMessage {
         white_time: u64,
         black_time: u64,
         current_turn: PlayerColor,
+        checks: Option<CheckCount>, // only for Three-check
+        pockets: Option<Pockets>,   // only for Crazyhouse
     },
     MoveMade {
         from: String,
@@ -65,8 +517,91 @@ pub enum ServerMessage {
     },
     OpponentJoined,
     OpponentLeft,
+    OpponentDisconnected {
+        color: PlayerColor,
+        grace_ms: u64,
+    },
+    OpponentReconnected {
+        color: PlayerColor,
+    },
+    OpponentAbandoned {
+        color: PlayerColor,
+    },
+    DrawOffered {
+        by: PlayerColor,
+    },
+    DrawDeclined {
+        by: PlayerColor,
+    },
+    DrawOfferExpired,
+    TakebackRequested {
+        by: PlayerColor,
+    },
+    TakebackDeclined {
+        by: PlayerColor,
+    },
+    TakebackAccepted,
+    RematchOffered {
+        by: PlayerColor,
+    },
+    RematchDeclined {
+        by: PlayerColor,
+    },
+    RematchStarted {
+        score: MatchScore,
+    },
+    ChatMessage {
+        message: ChatEntry,
+    },
+    ChatHistory {
+        messages: Vec<ChatEntry>,
+    },
     GameOver {
         result: GameResult,
+        rating_changes: Option<RatingChanges>, // only for rated games
+    },
+    Seeking,
+    SeekCancelled,
+    Lobby {
+        challenges: Vec<OpenChallenge>,
+    },
+    OpenChallengeAdded {
+        challenge: OpenChallenge,
+    },
+    OpenChallengeRemoved {
+        id: String,
+    },
+    // Sent to the poster, who learns which of the lobby's challenges is theirs
+    OpenChallengePosted {
+        id: String,
+    },
+    // The challenge as sent, for the challenger
+    ChallengeSent {
+        challenge: DirectChallenge,
+    },
+    ChallengeReceived {
+        challenge: DirectChallenge,
+    },
+    // The players of an accepted challenge also get `MatchFound`
+    ChallengeAccepted {
+        id: String,
+    },
+    ChallengeDeclined {
+        id: String,
+        reason: Option<String>,
+    },
+    ChallengeCancelled {
+        id: String,
+    },
+    ChallengeExpired {
+        id: String,
+    },
+    // Both seats are held for the players' rejoin tokens until they take them
+    MatchFound {
+        room_code: String,
+        player_color: PlayerColor,
+        config: RoomConfig,
+        rejoin_token: String,
     },
     Error {
         message: String,
@@ -77,7 +612,194 @@ pub enum ServerMessage {
 pub enum GameResult {
     WhiteWins,
     BlackWins,
-    Draw,
+    Stalemate,
+    Repetition,
+    FiftyMoveRule,
+    InsufficientMaterial,
+    DrawByAgreement,
     Resignation { winner: PlayerColor },
     Timeout { winner: PlayerColor },
+    TimeoutVsInsufficientMaterial,
+    Abandoned { winner: PlayerColor },
+    DrawByAbandonment,
+    KingOfTheHill { winner: PlayerColor },
+    ThreeCheck { winner: PlayerColor },
+    RaceWon { winner: PlayerColor },
+    RaceDrawn,
+    Explosion { winner: PlayerColor },
+    AllPiecesLost { winner: PlayerColor }, // Antichess: the side with nothing left wins
+    NoMovesLeft { winner: PlayerColor },   // Antichess: so does a stalemated side
+}
+
+impl GameResult {
+    pub fn winner(&self) -> Option<PlayerColor> {
+        match self {
+            GameResult::WhiteWins => Some(PlayerColor::White),
+            GameResult::BlackWins => Some(PlayerColor::Black),
+            GameResult::Resignation { winner }
+            | GameResult::Timeout { winner }
+            | GameResult::Abandoned { winner }
+            | GameResult::KingOfTheHill { winner }
+            | GameResult::ThreeCheck { winner }
+            | GameResult::RaceWon { winner }
+            | GameResult::Explosion { winner }
+            | GameResult::AllPiecesLost { winner }
+            | GameResult::NoMovesLeft { winner } => Some(*winner),
+            _ => None,
+        }
+    }
+
+    // How the game ended, e.g. "Checkmate" or "Time forfeit"
+    pub fn description(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins | GameResult::BlackWins => "Checkmate",
+            GameResult::Stalemate => "Stalemate",
+            GameResult::Repetition => "Repetition",
+            GameResult::FiftyMoveRule => "Fifty-move rule",
+            GameResult::InsufficientMaterial => "Insufficient material",
+            GameResult::DrawByAgreement => "Draw by agreement",
+            GameResult::Resignation { .. } => "Resignation",
+            GameResult::Timeout { .. } => "Time forfeit",
+            GameResult::TimeoutVsInsufficientMaterial => "Timeout vs insufficient material",
+            GameResult::Abandoned { .. } => "Abandoned",
+            GameResult::DrawByAbandonment => "Draw by abandonment",
+            GameResult::KingOfTheHill { .. } => "King reached the hill",
+            GameResult::ThreeCheck { .. } => "Third check",
+            GameResult::RaceWon { .. } => "King reached the eighth rank",
+            GameResult::RaceDrawn => "Both kings reached the eighth rank",
+            GameResult::Explosion { .. } => "King exploded",
+            GameResult::AllPiecesLost { .. } => "All pieces lost",
+            GameResult::NoMovesLeft { .. } => "No moves left",
+        }
+    }
+}
+
+// A finished game as listed by `GET /api/games`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GameSummary {
+    pub id: String,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub result: String,       // "1-0", "0-1" or "1/2-1/2"
+    pub termination: String,  // see `GameResult::description`
+    pub time_control: String, // as in the PGN TimeControl tag, "-" when untimed
+    pub variant: String,
+    pub eco: Option<String>,
+    pub opening: Option<String>,
+    pub started_at: u64,
+    pub ended_at: u64,
+}
+
+// One page of archive search results, newest games first
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GamePage {
+    pub games: Vec<GameSummary>,
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+}
+
+// A registered player; the name shown is the display name if one was chosen
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Account {
+    pub id: String,
+    pub username: String,
+    pub display_name: Option<String>,
+}
+
+impl Account {
+    pub fn name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.username)
+    }
+}
+
+// Body of `POST /api/register`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterRequest {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+}
+
+// Body of `POST /api/login`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+// Rated games are rated in the category of their time control
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum RatingCategory {
+    Bullet,
+    Blitz,
+    Rapid,
+    Classical,
+}
+
+impl RatingCategory {
+    // By the expected length of a game, base time plus 40 increments
+    pub fn of(time_control: &TimeControl) -> Self {
+        let estimated_secs = (time_control.base_ms + 40 * time_control.increment_ms) / 1000;
+        match estimated_secs {
+            0..180 => RatingCategory::Bullet,
+            180..480 => RatingCategory::Blitz,
+            480..1500 => RatingCategory::Rapid,
+            _ => RatingCategory::Classical,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RatingCategory::Bullet => "Bullet",
+            RatingCategory::Blitz => "Blitz",
+            RatingCategory::Rapid => "Rapid",
+            RatingCategory::Classical => "Classical",
+        }
+    }
+}
+
+// A player's rating after a rated game and how much it moved
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct RatingChange {
+    pub rating: i32,
+    pub delta: i32,
+    pub provisional: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct RatingChanges {
+    pub category: RatingCategory,
+    pub white: RatingChange,
+    pub black: RatingChange,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CategoryRating {
+    pub category: RatingCategory,
+    pub rating: i32,
+    pub deviation: i32,
+    pub games: u32,
+    pub provisional: bool,
+}
+
+// A player's rating right after one of their rated games
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RatingPoint {
+    pub category: RatingCategory,
+    pub game_id: String,
+    pub rating: i32,
+    pub deviation: i32,
+    pub recorded_at: u64,
+}
+
+// Served by `GET /api/ratings/{username}`, history oldest first
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RatingProfile {
+    pub username: String,
+    pub display_name: Option<String>,
+    pub ratings: Vec<CategoryRating>,
+    pub history: Vec<RatingPoint>,
 }
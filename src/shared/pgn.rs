@@ -0,0 +1,724 @@
+use crate::shared::position::{MoveRules, Position, ANTICHESS_FEN};
+use crate::shared::{MoveRecord, PlayerColor};
+use chess::{Board, BoardStatus, ChessMove, File, MoveGen, Piece, Square};
+use std::str::FromStr;
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// A move in an imported game; the first child continues the line and any
+// others are variations branching off at the same point
+#[derive(Debug, Clone)]
+pub struct MoveNode {
+    pub record: MoveRecord,
+    pub fen: String,
+    pub ply: usize,
+    pub comment: Option<String>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MoveTree {
+    pub nodes: Vec<MoveNode>,
+    pub roots: Vec<usize>,
+}
+
+impl MoveTree {
+    // Moves playable from `node`, or from the starting position for `None`
+    pub fn children(&self, node: Option<usize>) -> &[usize] {
+        match node {
+            Some(index) => &self.nodes[index].children,
+            None => &self.roots,
+        }
+    }
+
+    pub fn mainline(&self) -> Vec<usize> {
+        let mut line = Vec::new();
+        let mut next = self.roots.first().copied();
+        while let Some(index) = next {
+            line.push(index);
+            next = self.nodes[index].children.first().copied();
+        }
+        line
+    }
+
+    fn add(&mut self, parent: Option<usize>, record: MoveRecord, fen: String) -> usize {
+        // Playing a move the parent already has follows that branch instead of
+        // starting a duplicate one
+        if let Some(&existing) = self
+            .children(parent)
+            .iter()
+            .find(|&&child| self.nodes[child].record.san == record.san)
+        {
+            return existing;
+        }
+
+        let index = self.nodes.len();
+        let ply = parent.map_or(0, |p| self.nodes[p].ply + 1);
+        self.nodes.push(MoveNode {
+            record,
+            fen,
+            ply,
+            comment: None,
+            parent,
+            children: Vec::new(),
+        });
+        match parent {
+            Some(p) => self.nodes[p].children.push(index),
+            None => self.roots.push(index),
+        }
+        index
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub start_fen: String,
+    pub comment: Option<String>,
+    pub tree: MoveTree,
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // "12. Nf3" or "12... Nc6"; black moves only carry a number when asked
+    pub fn move_label(&self, index: usize, with_number: bool) -> String {
+        let node = &self.tree.nodes[index];
+        let mut parts = self.start_fen.split_whitespace().skip(1);
+        let black_first = parts.next() == Some("b");
+        let first_move: usize = parts.nth(3).and_then(|n| n.parse().ok()).unwrap_or(1);
+
+        let ply = node.ply + usize::from(black_first);
+        let number = first_move + ply / 2;
+        if ply.is_multiple_of(2) {
+            format!("{}. {}", number, node.record.san)
+        } else if with_number {
+            format!("{}... {}", number, node.record.san)
+        } else {
+            node.record.san.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    OpenVariation,
+    CloseVariation,
+    Result(String),
+    Move(String),
+}
+
+// Parses every game in a PGN file, checking each move for legality
+pub fn parse_pgn(input: &str) -> Result<Vec<PgnGame>, String> {
+    let mut games = Vec::new();
+    let mut builder = GameBuilder::default();
+
+    for token in tokenize(input)? {
+        match token {
+            Token::Tag(name, value) => {
+                if builder.in_movetext {
+                    games.push(std::mem::take(&mut builder).finish("*".to_string()));
+                }
+                builder.tags.push((name, value));
+            }
+            Token::Comment(text) => builder.comment(text),
+            Token::OpenVariation => builder.open_variation()?,
+            Token::CloseVariation => builder.close_variation()?,
+            Token::Move(san) => builder.play(&san)?,
+            Token::Result(result) => {
+                games.push(std::mem::take(&mut builder).finish(result));
+            }
+        }
+    }
+
+    if builder.in_movetext || !builder.tags.is_empty() {
+        games.push(builder.finish("*".to_string()));
+    }
+    if games.is_empty() {
+        return Err("No games found".to_string());
+    }
+
+    Ok(games)
+}
+
+// A position being replayed: a board for the variants the chess crate can
+// play, our own `Position` for the rest
+#[derive(Clone, Copy)]
+enum Replay {
+    Board(Board),
+    Position(Position),
+}
+
+impl Replay {
+    fn play(&self, san: &str) -> Result<(Replay, MoveRecord), String> {
+        let (next, chess_move, san) = match self {
+            Replay::Board(board) => {
+                let chess_move = parse_san(board, san)?;
+                let san = move_to_san(board, &chess_move);
+                (
+                    Replay::Board(board.make_move_new(chess_move)),
+                    chess_move,
+                    san,
+                )
+            }
+            Replay::Position(position) => {
+                let chess_move = position.parse_san(san)?;
+                let san = position.san(chess_move);
+                (
+                    Replay::Position(position.make_move(chess_move)),
+                    chess_move,
+                    san,
+                )
+            }
+        };
+
+        let record = MoveRecord {
+            san,
+            from: chess_move.get_source().to_string(),
+            to: chess_move.get_dest().to_string(),
+            timestamp: 0,
+        };
+        Ok((next, record))
+    }
+
+    fn fen(&self) -> String {
+        match self {
+            Replay::Board(board) => board.to_string(),
+            Replay::Position(position) => position.to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct GameBuilder {
+    tags: Vec<(String, String)>,
+    start: Option<Replay>,
+    comment: Option<String>,
+    tree: MoveTree,
+    boards: Vec<Replay>,
+    current: Option<usize>,
+    variations: Vec<Option<usize>>,
+    in_movetext: bool,
+}
+
+impl GameBuilder {
+    fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Atomic and Antichess games are replayed by their own rules
+    fn move_rules(&self) -> Option<MoveRules> {
+        match self.tag("Variant")?.to_ascii_lowercase().as_str() {
+            "atomic" => Some(MoveRules::Atomic),
+            "antichess" | "giveaway" => Some(MoveRules::Antichess),
+            _ => None,
+        }
+    }
+
+    fn start_fen(&self) -> String {
+        match (self.tag("FEN"), self.move_rules()) {
+            (Some(fen), _) => fen.to_string(),
+            (None, Some(MoveRules::Antichess)) => ANTICHESS_FEN.to_string(),
+            (None, _) => STARTING_FEN.to_string(),
+        }
+    }
+
+    fn start_board(&mut self) -> Result<Replay, String> {
+        if let Some(board) = self.start {
+            return Ok(board);
+        }
+
+        // Castling onto the rook and piece drops cannot be replayed here
+        if let Some(variant) = self.tag("Variant")
+            && matches!(
+                variant.to_ascii_lowercase().as_str(),
+                "chess960" | "fischerandom" | "crazyhouse"
+            )
+        {
+            return Err(format!("{} games cannot be imported", variant));
+        }
+
+        let fen = self.start_fen();
+        let board = match self.move_rules() {
+            Some(rules) => Replay::Position(
+                Position::from_fen(&fen, rules).map_err(|_| format!("Invalid FEN tag: {}", fen))?,
+            ),
+            None => Replay::Board(
+                Board::from_str(&fen).map_err(|_| format!("Invalid FEN tag: {}", fen))?,
+            ),
+        };
+        self.start = Some(board);
+        Ok(board)
+    }
+
+    fn board_at(&mut self, node: Option<usize>) -> Result<Replay, String> {
+        match node {
+            Some(index) => Ok(self.boards[index]),
+            None => self.start_board(),
+        }
+    }
+
+    fn play(&mut self, san: &str) -> Result<(), String> {
+        self.in_movetext = true;
+        let board = self.board_at(self.current)?;
+        let (next, record) = board.play(san)?;
+
+        let index = self.tree.add(self.current, record, next.fen());
+        if index == self.boards.len() {
+            self.boards.push(next);
+        }
+        self.current = Some(index);
+        Ok(())
+    }
+
+    fn comment(&mut self, text: String) {
+        let target = match self.current {
+            Some(index) => &mut self.tree.nodes[index].comment,
+            None => &mut self.comment,
+        };
+        match target {
+            Some(existing) => {
+                existing.push(' ');
+                existing.push_str(&text);
+            }
+            None => *target = Some(text),
+        }
+    }
+
+    // A variation replaces the move just played, so it starts from the
+    // position before it
+    fn open_variation(&mut self) -> Result<(), String> {
+        let Some(index) = self.current else {
+            return Err("Variation before the first move".to_string());
+        };
+        self.variations.push(self.current);
+        self.current = self.tree.nodes[index].parent;
+        Ok(())
+    }
+
+    fn close_variation(&mut self) -> Result<(), String> {
+        self.current = self.variations.pop().ok_or("Unmatched ')' in movetext")?;
+        Ok(())
+    }
+
+    fn finish(self, result: String) -> PgnGame {
+        let start_fen = self.start_fen();
+        let result = self.tag("Result").map_or(result, str::to_string);
+
+        PgnGame {
+            tags: self.tags,
+            start_fen,
+            comment: self.comment,
+            tree: self.tree,
+            result,
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                line_start = true;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            // Escape lines are ignored, as are rest-of-line comments
+            '%' if line_start => {
+                chars.by_ref().find(|&c| c == '\n');
+                continue;
+            }
+            ';' => {
+                let text: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                tokens.push(Token::Comment(text.trim().to_string()));
+                line_start = true;
+                continue;
+            }
+            '{' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => text.push(c),
+                        None => return Err("Unterminated comment".to_string()),
+                    }
+                }
+                let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                tokens.push(Token::Comment(text));
+            }
+            '[' => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '"' {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                if chars.next() != Some('"') {
+                    return Err(format!("Malformed tag {}", name));
+                }
+
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => value.extend(chars.next()),
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(format!("Unterminated tag {}", name)),
+                    }
+                }
+                if chars.by_ref().find(|&c| c == ']').is_none() {
+                    return Err(format!("Unterminated tag {}", name));
+                }
+                tokens.push(Token::Tag(name, value));
+            }
+            '(' => tokens.push(Token::OpenVariation),
+            ')' => tokens.push(Token::CloseVariation),
+            // Numeric annotation glyphs carry nothing the viewer shows
+            '$' => while chars.next_if(|c| c.is_ascii_digit()).is_some() {},
+            _ => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{}()[];$".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                if let Some(token) = classify(&word) {
+                    tokens.push(token);
+                }
+            }
+        }
+        line_start = false;
+    }
+
+    Ok(tokens)
+}
+
+// Results, moves and move numbers ("12." or "12...", possibly glued to the move)
+fn classify(word: &str) -> Option<Token> {
+    if matches!(word, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        return Some(Token::Result(word.to_string()));
+    }
+
+    let san = word
+        .trim_start_matches(|c: char| c.is_ascii_digit())
+        .trim_start_matches('.');
+    let san = if san.len() < word.len() && !word[..word.len() - san.len()].contains('.') {
+        // Digits without a dot belong to the move, e.g. "0-0"
+        word
+    } else {
+        san
+    };
+    if san.is_empty() || san.chars().all(|c| "!?+#".contains(c)) {
+        return None;
+    }
+
+    Some(Token::Move(san.to_string()))
+}
+
+// Finds the legal move a SAN string describes, tolerating check marks,
+// annotations, missing '=' before promotions and needless disambiguation
+pub fn parse_san(board: &Board, san: &str) -> Result<ChessMove, String> {
+    let text = san.trim_end_matches(['+', '#', '!', '?']);
+
+    let castle = match text {
+        "O-O" | "0-0" => Some(File::G),
+        "O-O-O" | "0-0-0" => Some(File::C),
+        _ => None,
+    };
+    if let Some(file) = castle {
+        let king = board.king_square(board.side_to_move());
+        return MoveGen::new_legal(board)
+            .find(|m| {
+                m.get_source() == king
+                    && m.get_dest().get_file() == file
+                    && king.get_file() == File::E
+            })
+            .ok_or_else(|| format!("Illegal move {}", san));
+    }
+
+    let mut chars: Vec<char> = text.chars().collect();
+    let promotion = match chars
+        .last()
+        .and_then(|&c| piece_from_char(c.to_ascii_uppercase()))
+    {
+        Some(piece) if chars.len() > 2 && piece != Piece::King => {
+            let before = chars[chars.len() - 2];
+            if before == '=' || before.is_ascii_digit() {
+                chars.pop();
+                if before == '=' {
+                    chars.pop();
+                }
+                Some(piece)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+
+    if chars.len() < 2 {
+        return Err(format!("Invalid move {}", san));
+    }
+    let dest: String = chars.split_off(chars.len() - 2).into_iter().collect();
+    let dest = Square::from_str(&dest).map_err(|_| format!("Invalid move {}", san))?;
+
+    let piece = match chars.first().and_then(|&c| piece_from_char(c)) {
+        Some(piece) => {
+            chars.remove(0);
+            piece
+        }
+        None => Piece::Pawn,
+    };
+
+    let mut from_file = None;
+    let mut from_rank = None;
+    for c in chars {
+        match c {
+            'a'..='h' => from_file = Some(c as usize - 'a' as usize),
+            '1'..='8' => from_rank = Some(c as usize - '1' as usize),
+            'x' | ':' | '-' => {}
+            _ => return Err(format!("Invalid move {}", san)),
+        }
+    }
+
+    let candidates: Vec<ChessMove> = MoveGen::new_legal(board)
+        .filter(|m| {
+            let source = m.get_source();
+            m.get_dest() == dest
+                && m.get_promotion() == promotion
+                && board.piece_on(source) == Some(piece)
+                && from_file.is_none_or(|f| source.get_file().to_index() == f)
+                && from_rank.is_none_or(|r| source.get_rank().to_index() == r)
+        })
+        .collect();
+
+    match candidates.as_slice() {
+        [chess_move] => Ok(*chess_move),
+        [] => Err(format!("Illegal move {}", san)),
+        _ => Err(format!("Ambiguous move {}", san)),
+    }
+}
+
+// Standard Algebraic Notation for a legal move in `board`
+pub fn move_to_san(board: &Board, chess_move: &ChessMove) -> String {
+    let source = chess_move.get_source();
+    let dest = chess_move.get_dest();
+    let piece = board.piece_on(source).unwrap_or(Piece::Pawn);
+
+    let mut san = String::new();
+
+    if piece == Piece::King && source.get_file() == File::E {
+        match dest.get_file() {
+            File::G if source.get_rank() == dest.get_rank() => san.push_str("O-O"),
+            File::C if source.get_rank() == dest.get_rank() => san.push_str("O-O-O"),
+            _ => {}
+        }
+    }
+
+    if san.is_empty() {
+        let is_en_passant = piece == Piece::Pawn && source.get_file() != dest.get_file();
+        let capture = board.piece_on(dest).is_some() || is_en_passant;
+
+        if piece == Piece::Pawn {
+            if capture {
+                san.push(file_char(source.get_file()));
+            }
+        } else {
+            san.push(piece_char(piece));
+            san.push_str(&disambiguation(
+                MoveGen::new_legal(board),
+                |square| board.piece_on(square),
+                chess_move,
+                piece,
+            ));
+        }
+
+        if capture {
+            san.push('x');
+        }
+
+        san.push_str(&dest.to_string());
+
+        if let Some(promo) = chess_move.get_promotion() {
+            san.push('=');
+            san.push(piece_char(promo));
+        }
+    }
+
+    let next = board.make_move_new(*chess_move);
+    if next.status() == BoardStatus::Checkmate {
+        san.push('#');
+    } else if next.checkers().popcnt() > 0 {
+        san.push('+');
+    }
+
+    san
+}
+
+// The file, rank or square that tells `chess_move` apart from other moves by
+// the same kind of piece to the same square, given the position's legal moves
+pub(crate) fn disambiguation(
+    legal_moves: impl IntoIterator<Item = ChessMove>,
+    piece_on: impl Fn(Square) -> Option<Piece>,
+    chess_move: &ChessMove,
+    piece: Piece,
+) -> String {
+    let source = chess_move.get_source();
+    let rivals: Vec<Square> = legal_moves
+        .into_iter()
+        .filter(|m| {
+            m.get_dest() == chess_move.get_dest()
+                && m.get_source() != source
+                && piece_on(m.get_source()) == Some(piece)
+        })
+        .map(|m| m.get_source())
+        .collect();
+
+    if rivals.is_empty() {
+        String::new()
+    } else if rivals.iter().all(|sq| sq.get_file() != source.get_file()) {
+        file_char(source.get_file()).to_string()
+    } else if rivals.iter().all(|sq| sq.get_rank() != source.get_rank()) {
+        (source.get_rank().to_index() + 1).to_string()
+    } else {
+        source.to_string()
+    }
+}
+
+// Side to move in a FEN, as the app's color type
+pub fn side_to_move(fen: &str) -> PlayerColor {
+    match fen.split_whitespace().nth(1) {
+        Some("b") => PlayerColor::Black,
+        _ => PlayerColor::White,
+    }
+}
+
+fn piece_from_char(c: char) -> Option<Piece> {
+    match c {
+        'K' => Some(Piece::King),
+        'Q' => Some(Piece::Queen),
+        'R' => Some(Piece::Rook),
+        'B' => Some(Piece::Bishop),
+        'N' => Some(Piece::Knight),
+        _ => None,
+    }
+}
+
+pub(crate) fn piece_char(piece: Piece) -> char {
+    match piece {
+        Piece::King => 'K',
+        Piece::Queen => 'Q',
+        Piece::Rook => 'R',
+        Piece::Bishop => 'B',
+        Piece::Knight => 'N',
+        Piece::Pawn => 'P',
+    }
+}
+
+pub(crate) fn file_char(file: File) -> char {
+    (b'a' + file.to_index() as u8) as char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san(fen: &str, from: Square, to: Square, promotion: Option<Piece>) -> String {
+        let board = Board::from_str(fen).unwrap();
+        let chess_move = ChessMove::new(from, to, promotion);
+        assert!(board.legal(chess_move), "{} is not legal", chess_move);
+        move_to_san(&board, &chess_move)
+    }
+
+    #[test]
+    fn castling() {
+        let white = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let black = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1";
+        assert_eq!(san(white, Square::E1, Square::G1, None), "O-O");
+        assert_eq!(san(white, Square::E1, Square::C1, None), "O-O-O");
+        assert_eq!(san(black, Square::E8, Square::G8, None), "O-O");
+        assert_eq!(san(black, Square::E8, Square::C8, None), "O-O-O");
+        assert_eq!(san(white, Square::E1, Square::F1, None), "Kf1");
+    }
+
+    #[test]
+    fn disambiguates_by_file_rank_or_square() {
+        let knights = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+        assert_eq!(san(knights, Square::B1, Square::D2, None), "Nbd2");
+        assert_eq!(san(knights, Square::F1, Square::D2, None), "Nfd2");
+
+        let rooks = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san(rooks, Square::A1, Square::A3, None), "R1a3");
+        assert_eq!(san(rooks, Square::A5, Square::A3, None), "R5a3");
+
+        let queens = "7K/8/8/7k/8/Q7/8/Q1Q5 w - - 0 1";
+        assert_eq!(san(queens, Square::A1, Square::B2, None), "Qa1b2");
+        assert_eq!(san(queens, Square::A3, Square::B2, None), "Q3b2");
+        assert_eq!(san(queens, Square::C1, Square::B2, None), "Qcb2");
+    }
+
+    #[test]
+    fn pinned_piece_does_not_disambiguate() {
+        // The e2 knight is pinned against the king, so only c2 can reach d4
+        let fen = "k3r3/8/8/8/8/8/2N1N3/4K3 w - - 0 1";
+        assert_eq!(san(fen, Square::C2, Square::D4, None), "Nd4");
+    }
+
+    #[test]
+    fn en_passant() {
+        let fen = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_eq!(san(fen, Square::E5, Square::D6, None), "exd6");
+    }
+
+    #[test]
+    fn promotion() {
+        let fen = "3r3k/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(
+            san(fen, Square::E7, Square::D8, Some(Piece::Queen)),
+            "exd8=Q+"
+        );
+        assert_eq!(
+            san(fen, Square::E7, Square::E8, Some(Piece::Knight)),
+            "e8=N"
+        );
+    }
+
+    #[test]
+    fn rejects_variants_it_cannot_replay() {
+        for variant in ["Chess960", "Crazyhouse"] {
+            let pgn = format!("[Variant \"{}\"]\n\n1. e4 e5 *", variant);
+            assert_eq!(
+                parse_pgn(&pgn).unwrap_err(),
+                format!("{} games cannot be imported", variant)
+            );
+        }
+        assert!(parse_pgn("[Variant \"Atomic\"]\n\n1. e4 e5 *").is_ok());
+    }
+
+    #[test]
+    fn checkmate() {
+        let fen = "6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san(fen, Square::A1, Square::A8, None), "Ra8#");
+    }
+}
@@ -0,0 +1,793 @@
+use crate::shared::pgn::{disambiguation, file_char, piece_char};
+use chess::{ChessMove, Color, File, Piece, Rank, Square, ALL_SQUARES};
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::str::FromStr;
+
+// Rule sets the chess crate cannot play, because a king may explode or be
+// captured and leave a board without one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MoveRules {
+    // Captures explode every non-pawn piece next to the capture square
+    Atomic,
+    // Captures are compulsory and the king is an ordinary piece
+    Antichess,
+}
+
+pub const ANTICHESS_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1";
+
+const KNIGHT_STEPS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_STEPS: [(i8, i8); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+const DIAGONALS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+const LINES: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+// A position with its own move generation, played by square index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position {
+    squares: [Option<(Color, Piece)>; 64],
+    side_to_move: Color,
+    castling: [[bool; 2]; 2],   // by color, then king side and queen side
+    en_passant: Option<Square>, // the square a pawn just skipped over
+    rules: MoveRules,
+}
+
+impl Position {
+    pub fn from_fen(fen: &str, rules: MoveRules) -> Result<Self, String> {
+        let invalid = || format!("{} is not a valid FEN", fen.trim());
+        let mut fields = fen.split_whitespace();
+
+        let mut squares = [None; 64];
+        let rows: Vec<&str> = fields.next().ok_or_else(invalid)?.split('/').collect();
+        if rows.len() != 8 {
+            return Err(invalid());
+        }
+        for (row, text) in rows.iter().enumerate() {
+            let mut file = 0;
+            for c in text.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    file += skip as usize;
+                    continue;
+                }
+                let piece = fen_piece(c).ok_or_else(invalid)?;
+                if file >= 8 {
+                    return Err(invalid());
+                }
+                let color = if c.is_ascii_uppercase() {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                squares[(7 - row) * 8 + file] = Some((color, piece));
+                file += 1;
+            }
+            if file != 8 {
+                return Err(invalid());
+            }
+        }
+
+        let side_to_move = match fields.next() {
+            None | Some("w") => Color::White,
+            Some("b") => Color::Black,
+            Some(_) => return Err(invalid()),
+        };
+
+        let mut castling = [[false; 2]; 2];
+        for c in fields.next().unwrap_or("-").chars() {
+            match c {
+                'K' => castling[0][0] = true,
+                'Q' => castling[0][1] = true,
+                'k' => castling[1][0] = true,
+                'q' => castling[1][1] = true,
+                '-' => {}
+                _ => return Err(invalid()),
+            }
+        }
+
+        let en_passant = match fields.next() {
+            None | Some("-") => None,
+            Some(square) => Some(Square::from_str(square).map_err(|_| invalid())?),
+        };
+
+        let mut position = Self {
+            squares,
+            side_to_move,
+            castling,
+            en_passant,
+            rules,
+        };
+        position.castling = match rules {
+            MoveRules::Atomic => position.remaining_castling(castling),
+            MoveRules::Antichess => [[false; 2]; 2],
+        };
+
+        if rules == MoveRules::Atomic {
+            let kings = |color| {
+                position
+                    .squares
+                    .iter()
+                    .filter(|&&square| square == Some((color, Piece::King)))
+                    .count()
+            };
+            if kings(Color::White) != 1 || kings(Color::Black) != 1 {
+                return Err("each side needs one king".to_string());
+            }
+            if position.is_in_check(!side_to_move) {
+                return Err("the side not to move cannot be in check".to_string());
+            }
+        }
+
+        Ok(position)
+    }
+
+    pub fn rules(&self) -> MoveRules {
+        self.rules
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    pub fn piece_on(&self, square: Square) -> Option<Piece> {
+        self.squares[square.to_index()].map(|(_, piece)| piece)
+    }
+
+    pub fn piece_count(&self, color: Color) -> usize {
+        self.squares
+            .iter()
+            .filter(|square| square.is_some_and(|(owner, _)| owner == color))
+            .count()
+    }
+
+    pub fn king(&self, color: Color) -> Option<Square> {
+        ALL_SQUARES
+            .into_iter()
+            .find(|square| self.squares[square.to_index()] == Some((color, Piece::King)))
+    }
+
+    // Whether the side to move is in check; there is no check in Antichess
+    pub fn in_check(&self) -> bool {
+        self.rules == MoveRules::Atomic && self.is_in_check(self.side_to_move)
+    }
+
+    pub fn is_capture(&self, chess_move: ChessMove) -> bool {
+        let (from, to) = (chess_move.get_source(), chess_move.get_dest());
+        let target = self.squares[to.to_index()];
+        target.is_some_and(|(color, _)| color != self.side_to_move)
+            || (self.piece_on(from) == Some(Piece::Pawn)
+                && self.en_passant == Some(to)
+                && from.get_file() != to.get_file())
+    }
+
+    pub fn legal_moves(&self) -> Vec<ChessMove> {
+        let moves = self.pseudo_legal_moves();
+        match self.rules {
+            MoveRules::Atomic => moves
+                .into_iter()
+                .filter(|&chess_move| self.atomic_legal(chess_move))
+                .collect(),
+            MoveRules::Antichess => {
+                let captures: Vec<ChessMove> = moves
+                    .iter()
+                    .copied()
+                    .filter(|&chess_move| self.is_capture(chess_move))
+                    .collect();
+                if captures.is_empty() {
+                    moves
+                } else {
+                    captures
+                }
+            }
+        }
+    }
+
+    // Plays a legal move, exploding the neighbourhood of Atomic captures
+    pub fn make_move(&self, chess_move: ChessMove) -> Self {
+        let (from, to) = (chess_move.get_source(), chess_move.get_dest());
+        let mut next = *self;
+        let Some((color, piece)) = self.squares[from.to_index()] else {
+            return next;
+        };
+        let capture = self.is_capture(chess_move);
+
+        next.squares[from.to_index()] = None;
+        if piece == Piece::Pawn && capture && self.squares[to.to_index()].is_none() {
+            let taken = Square::make_square(from.get_rank(), to.get_file());
+            next.squares[taken.to_index()] = None;
+        }
+        next.squares[to.to_index()] = Some((color, chess_move.get_promotion().unwrap_or(piece)));
+
+        // Castling is a two-square king move; the rook jumps over it
+        let king_step = to.get_file().to_index() as i8 - from.get_file().to_index() as i8;
+        if piece == Piece::King && king_step.abs() == 2 {
+            let (rook_from, rook_to) = if king_step > 0 {
+                (File::H, File::F)
+            } else {
+                (File::A, File::D)
+            };
+            let rank = from.get_rank();
+            next.squares[Square::make_square(rank, rook_from).to_index()] = None;
+            next.squares[Square::make_square(rank, rook_to).to_index()] =
+                Some((color, Piece::Rook));
+        }
+
+        if capture && self.rules == MoveRules::Atomic {
+            next.squares[to.to_index()] = None;
+            for (file_step, rank_step) in KING_STEPS {
+                if let Some(square) = offset(to, file_step, rank_step)
+                    && next
+                        .piece_on(square)
+                        .is_some_and(|piece| piece != Piece::Pawn)
+                {
+                    next.squares[square.to_index()] = None;
+                }
+            }
+        }
+
+        let rank_step = to.get_rank().to_index() as i8 - from.get_rank().to_index() as i8;
+        next.en_passant = if piece == Piece::Pawn && rank_step.abs() == 2 {
+            offset(from, 0, rank_step / 2)
+        } else {
+            None
+        };
+        next.castling = next.remaining_castling(self.castling);
+        next.side_to_move = !color;
+        next
+    }
+
+    // Standard Algebraic Notation, with '#' for mate or an exploded king
+    pub fn san(&self, chess_move: ChessMove) -> String {
+        let (from, to) = (chess_move.get_source(), chess_move.get_dest());
+        let piece = self.piece_on(from).unwrap_or(Piece::Pawn);
+        let capture = self.is_capture(chess_move);
+        let king_step = to.get_file().to_index() as i8 - from.get_file().to_index() as i8;
+
+        let mut san = String::new();
+        if piece == Piece::King && king_step.abs() == 2 && self.rules == MoveRules::Atomic {
+            san.push_str(if king_step > 0 { "O-O" } else { "O-O-O" });
+        } else {
+            if piece == Piece::Pawn {
+                if capture {
+                    san.push(file_char(from.get_file()));
+                }
+            } else {
+                san.push(piece_char(piece));
+                san.push_str(&disambiguation(
+                    self.legal_moves(),
+                    |square| self.piece_on(square),
+                    &chess_move,
+                    piece,
+                ));
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&to.to_string());
+            if let Some(promotion) = chess_move.get_promotion() {
+                san.push('=');
+                san.push(piece_char(promotion));
+            }
+        }
+
+        if self.rules == MoveRules::Atomic {
+            let next = self.make_move(chess_move);
+            if next.king(next.side_to_move).is_none() {
+                san.push('#');
+            } else if next.in_check() {
+                san.push(if next.legal_moves().is_empty() {
+                    '#'
+                } else {
+                    '+'
+                });
+            }
+        }
+        san
+    }
+
+    // Finds the legal move a SAN string describes, ignoring check marks,
+    // annotations and the '=' before promotions
+    pub fn parse_san(&self, san: &str) -> Result<ChessMove, String> {
+        let normalize = |text: &str| {
+            text.replace('0', "O")
+                .chars()
+                .filter(|c| !matches!(c, '+' | '#' | '!' | '?' | '='))
+                .collect::<String>()
+        };
+        let wanted = normalize(san);
+        self.legal_moves()
+            .into_iter()
+            .find(|&chess_move| normalize(&self.san(chess_move)) == wanted)
+            .ok_or_else(|| format!("Illegal move {}", san))
+    }
+
+    // Identifies the position for repetition counting
+    pub fn hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        Hash::hash(self, &mut hasher);
+        hasher.finish()
+    }
+
+    fn pseudo_legal_moves(&self) -> Vec<ChessMove> {
+        let mut moves = Vec::new();
+        let color = self.side_to_move;
+
+        for from in ALL_SQUARES {
+            let Some((owner, piece)) = self.squares[from.to_index()] else {
+                continue;
+            };
+            if owner != color {
+                continue;
+            }
+
+            match piece {
+                Piece::Pawn => self.pawn_moves(from, &mut moves),
+                Piece::Knight => self.step_moves(from, &KNIGHT_STEPS, &mut moves),
+                Piece::King => {
+                    self.step_moves(from, &KING_STEPS, &mut moves);
+                    self.castling_moves(from, &mut moves);
+                }
+                Piece::Bishop => self.slide_moves(from, &DIAGONALS, &mut moves),
+                Piece::Rook => self.slide_moves(from, &LINES, &mut moves),
+                Piece::Queen => {
+                    self.slide_moves(from, &DIAGONALS, &mut moves);
+                    self.slide_moves(from, &LINES, &mut moves);
+                }
+            }
+        }
+        moves
+    }
+
+    fn pawn_moves(&self, from: Square, moves: &mut Vec<ChessMove>) {
+        let color = self.side_to_move;
+        let (forward, start_rank, last_rank) = match color {
+            Color::White => (1, Rank::Second, Rank::Eighth),
+            Color::Black => (-1, Rank::Seventh, Rank::First),
+        };
+        let promotions: &[Piece] = match self.rules {
+            MoveRules::Atomic => &[Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight],
+            MoveRules::Antichess => &[
+                Piece::Queen,
+                Piece::Rook,
+                Piece::Bishop,
+                Piece::Knight,
+                Piece::King,
+            ],
+        };
+        let mut push = |to: Square| {
+            if to.get_rank() == last_rank {
+                for &piece in promotions {
+                    moves.push(ChessMove::new(from, to, Some(piece)));
+                }
+            } else {
+                moves.push(ChessMove::new(from, to, None));
+            }
+        };
+
+        if let Some(to) = offset(from, 0, forward).filter(|&to| self.piece_on(to).is_none()) {
+            push(to);
+            if from.get_rank() == start_rank
+                && let Some(to) = offset(to, 0, forward).filter(|&to| self.piece_on(to).is_none())
+            {
+                push(to);
+            }
+        }
+        for file_step in [-1, 1] {
+            if let Some(to) = offset(from, file_step, forward) {
+                let enemy = self.squares[to.to_index()].is_some_and(|(owner, _)| owner != color);
+                if enemy || self.en_passant == Some(to) {
+                    push(to);
+                }
+            }
+        }
+    }
+
+    fn step_moves(&self, from: Square, steps: &[(i8, i8)], moves: &mut Vec<ChessMove>) {
+        for &(file_step, rank_step) in steps {
+            if let Some(to) = offset(from, file_step, rank_step)
+                && !self.is_own(to)
+            {
+                moves.push(ChessMove::new(from, to, None));
+            }
+        }
+    }
+
+    fn slide_moves(&self, from: Square, directions: &[(i8, i8)], moves: &mut Vec<ChessMove>) {
+        for &(file_step, rank_step) in directions {
+            let mut current = from;
+            while let Some(to) = offset(current, file_step, rank_step) {
+                if self.is_own(to) {
+                    break;
+                }
+                moves.push(ChessMove::new(from, to, None));
+                if self.piece_on(to).is_some() {
+                    break;
+                }
+                current = to;
+            }
+        }
+    }
+
+    // Standard castling from the initial king and rook squares
+    fn castling_moves(&self, from: Square, moves: &mut Vec<ChessMove>) {
+        let color = self.side_to_move;
+        let rank = color.to_my_backrank();
+        if from != Square::make_square(rank, File::E) || self.is_in_check(color) {
+            return;
+        }
+
+        let sides = [
+            (0, [File::F, File::G].as_slice(), File::G),
+            (1, [File::D, File::C, File::B].as_slice(), File::C),
+        ];
+        for (side, between, destination) in sides {
+            if !self.castling[color.to_index()][side] {
+                continue;
+            }
+            let clear = between
+                .iter()
+                .all(|&file| self.piece_on(Square::make_square(rank, file)).is_none());
+            // The king may not pass through an attacked square
+            let safe = between.iter().take(2).all(|&file| {
+                !self.is_attacked(Square::make_square(rank, file), !color)
+                    || self.kings_touch_at(Square::make_square(rank, file), color)
+            });
+            if clear && safe {
+                moves.push(ChessMove::new(
+                    from,
+                    Square::make_square(rank, destination),
+                    None,
+                ));
+            }
+        }
+    }
+
+    // Kings may not capture, a move may not blow up its own king, and blowing
+    // up the enemy king wins whatever else happens
+    fn atomic_legal(&self, chess_move: ChessMove) -> bool {
+        let color = self.side_to_move;
+        if self.piece_on(chess_move.get_source()) == Some(Piece::King)
+            && self.is_capture(chess_move)
+        {
+            return false;
+        }
+
+        let next = self.make_move(chess_move);
+        match (next.king(color), next.king(!color)) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(_), Some(_)) => !next.is_in_check(color),
+        }
+    }
+
+    // Kings standing next to each other cannot be checked, since taking one
+    // would blow up the other
+    fn is_in_check(&self, color: Color) -> bool {
+        match self.king(color) {
+            Some(king) => self.is_attacked(king, !color) && !self.kings_touch_at(king, color),
+            None => false,
+        }
+    }
+
+    fn kings_touch_at(&self, square: Square, color: Color) -> bool {
+        self.king(!color).is_some_and(|enemy| {
+            let files = square
+                .get_file()
+                .to_index()
+                .abs_diff(enemy.get_file().to_index());
+            let ranks = square
+                .get_rank()
+                .to_index()
+                .abs_diff(enemy.get_rank().to_index());
+            files <= 1 && ranks <= 1
+        })
+    }
+
+    // Whether `by` attacks `square` with anything but its king
+    fn is_attacked(&self, square: Square, by: Color) -> bool {
+        let holds = |square: Option<Square>, pieces: &[Piece]| {
+            square
+                .and_then(|square| self.squares[square.to_index()])
+                .is_some_and(|(owner, piece)| owner == by && pieces.contains(&piece))
+        };
+
+        let pawn_rank_step = match by {
+            Color::White => -1,
+            Color::Black => 1,
+        };
+        if [-1, 1]
+            .into_iter()
+            .any(|file_step| holds(offset(square, file_step, pawn_rank_step), &[Piece::Pawn]))
+        {
+            return true;
+        }
+        if KNIGHT_STEPS
+            .iter()
+            .any(|&(f, r)| holds(offset(square, f, r), &[Piece::Knight]))
+        {
+            return true;
+        }
+
+        let sliders = [
+            (DIAGONALS, [Piece::Bishop, Piece::Queen]),
+            (LINES, [Piece::Rook, Piece::Queen]),
+        ];
+        sliders.iter().any(|(directions, pieces)| {
+            directions.iter().any(|&(file_step, rank_step)| {
+                let mut current = square;
+                while let Some(next) = offset(current, file_step, rank_step) {
+                    if self.piece_on(next).is_some() {
+                        return holds(Some(next), pieces);
+                    }
+                    current = next;
+                }
+                false
+            })
+        })
+    }
+
+    fn is_own(&self, square: Square) -> bool {
+        self.squares[square.to_index()].is_some_and(|(owner, _)| owner == self.side_to_move)
+    }
+
+    // Rights survive only while the king and rook are still on their squares
+    fn remaining_castling(&self, rights: [[bool; 2]; 2]) -> [[bool; 2]; 2] {
+        let mut remaining = [[false; 2]; 2];
+        for color in [Color::White, Color::Black] {
+            let rank = color.to_my_backrank();
+            let holds = |file, piece| {
+                self.squares[Square::make_square(rank, file).to_index()] == Some((color, piece))
+            };
+            if !holds(File::E, Piece::King) {
+                continue;
+            }
+            let index = color.to_index();
+            remaining[index][0] = rights[index][0] && holds(File::H, Piece::Rook);
+            remaining[index][1] = rights[index][1] && holds(File::A, Piece::Rook);
+        }
+        remaining
+    }
+}
+
+// FEN with placeholder move counters, like the chess crate's boards
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.squares[rank * 8 + file] {
+                    Some((color, piece)) => {
+                        if empty > 0 {
+                            write!(f, "{}", empty)?;
+                            empty = 0;
+                        }
+                        let letter = piece_char(piece);
+                        match color {
+                            Color::White => write!(f, "{}", letter)?,
+                            Color::Black => write!(f, "{}", letter.to_ascii_lowercase())?,
+                        }
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                write!(f, "{}", empty)?;
+            }
+            if rank > 0 {
+                write!(f, "/")?;
+            }
+        }
+
+        let side = match self.side_to_move {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+        let mut castling: String = ["K", "Q", "k", "q"]
+            .iter()
+            .zip(self.castling.iter().flatten())
+            .filter(|(_, allowed)| **allowed)
+            .map(|(letter, _)| *letter)
+            .collect();
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        let en_passant = self
+            .en_passant
+            .map_or_else(|| "-".to_string(), |square| square.to_string());
+
+        write!(f, " {} {} {} 0 1", side, castling, en_passant)
+    }
+}
+
+fn offset(square: Square, file_step: i8, rank_step: i8) -> Option<Square> {
+    let file = square.get_file().to_index() as i8 + file_step;
+    let rank = square.get_rank().to_index() as i8 + rank_step;
+    if (0..8).contains(&file) && (0..8).contains(&rank) {
+        Some(ALL_SQUARES[(rank * 8 + file) as usize])
+    } else {
+        None
+    }
+}
+
+fn fen_piece(c: char) -> Option<Piece> {
+    match c.to_ascii_uppercase() {
+        'K' => Some(Piece::King),
+        'Q' => Some(Piece::Queen),
+        'R' => Some(Piece::Rook),
+        'B' => Some(Piece::Bishop),
+        'N' => Some(Piece::Knight),
+        'P' => Some(Piece::Pawn),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn position(fen: &str, rules: MoveRules) -> Position {
+        Position::from_fen(fen, rules).unwrap()
+    }
+
+    fn perft(position: &Position, depth: usize) -> usize {
+        if depth == 0 {
+            return 1;
+        }
+        position
+            .legal_moves()
+            .into_iter()
+            .map(|chess_move| perft(&position.make_move(chess_move), depth - 1))
+            .sum()
+    }
+
+    fn play(position: Position, moves: &[&str]) -> Position {
+        moves.iter().fold(position, |position, san| {
+            position.make_move(position.parse_san(san).unwrap())
+        })
+    }
+
+    fn square(name: &str) -> Square {
+        Square::from_str(name).unwrap()
+    }
+
+    fn has_move(position: &Position, from: &str, to: &str) -> bool {
+        position
+            .legal_moves()
+            .iter()
+            .any(|m| m.get_source() == square(from) && m.get_dest() == square(to))
+    }
+
+    #[test]
+    fn atomic_perft_from_the_start() {
+        let start = position(START_FEN, MoveRules::Atomic);
+        let counts: Vec<usize> = (1..=4).map(|depth| perft(&start, depth)).collect();
+        assert_eq!(counts, [20, 400, 8902, 197326]);
+    }
+
+    #[test]
+    fn antichess_perft_from_the_start() {
+        let start = position(ANTICHESS_FEN, MoveRules::Antichess);
+        let counts: Vec<usize> = (1..=4).map(|depth| perft(&start, depth)).collect();
+        assert_eq!(counts, [20, 400, 8067, 153299]);
+    }
+
+    #[test]
+    fn atomic_captures_explode_everything_but_pawns() {
+        let before = position(
+            "7k/8/2b1p3/3n4/2P1N3/8/8/3R2K1 w - - 0 1",
+            MoveRules::Atomic,
+        );
+        let after = before.make_move(before.parse_san("Rxd5").unwrap());
+        // The rook, the knight it took and the pieces of both sides around
+        // them are gone; the pawns stay
+        assert_eq!(after.to_string(), "7k/8/4p3/8/2P5/8/8/6K1 b - - 0 1");
+    }
+
+    #[test]
+    fn atomic_forbids_exploding_your_own_king() {
+        let position = position("7k/8/8/8/8/8/R2n4/4K3 w - - 0 1", MoveRules::Atomic);
+        assert!(!has_move(&position, "a2", "d2"));
+        // Nor may the king capture at all
+        assert!(!has_move(&position, "e1", "d2"));
+    }
+
+    #[test]
+    fn atomic_kings_may_touch() {
+        // A king next to the enemy king cannot be checked
+        let touching = position("8/8/8/4k3/r3K3/8/8/8 w - - 0 1", MoveRules::Atomic);
+        assert!(!touching.in_check());
+
+        let apart = position("8/8/8/4k3/8/4K3/8/8 w - - 0 1", MoveRules::Atomic);
+        assert!(has_move(&apart, "e3", "e4"));
+    }
+
+    #[test]
+    fn atomic_en_passant_explodes_around_the_destination() {
+        let start = position("4k3/2np4/8/4P3/2b5/8/8/4K3 b - - 0 1", MoveRules::Atomic);
+        let after = play(start, &["d5"]);
+        assert_eq!(after.san(after.parse_san("exd6").unwrap()), "exd6");
+        let after = play(after, &["exd6"]);
+        // The knight next to d6 goes, the bishop next to the taken pawn stays
+        assert_eq!(after.to_string(), "4k3/8/8/8/2b5/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn atomic_castling() {
+        let start = position("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", MoveRules::Atomic);
+        assert_eq!(start.san(start.parse_san("O-O-O").unwrap()), "O-O-O");
+        let castled = play(start, &["O-O"]);
+        assert_eq!(castled.to_string(), "r3k2r/8/8/8/8/8/8/R4RK1 b kq - 0 1");
+
+        // Not through an attacked square
+        let attacked = position("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1", MoveRules::Atomic);
+        assert!(!has_move(&attacked, "e1", "g1"));
+        assert!(has_move(&attacked, "e1", "c1"));
+    }
+
+    #[test]
+    fn antichess_forces_captures() {
+        let position = play(position(ANTICHESS_FEN, MoveRules::Antichess), &["e3", "b5"]);
+        let moves = position.legal_moves();
+        assert_eq!(moves.len(), 1);
+        assert_eq!(position.san(moves[0]), "Bxb5");
+    }
+
+    #[test]
+    fn antichess_promotes_to_a_king() {
+        let position = position("8/P7/8/8/8/8/8/7k w - - 0 1", MoveRules::Antichess);
+        let promoted = play(position, &["a8=K"]);
+        assert_eq!(promoted.piece_on(square("a8")), Some(Piece::King));
+    }
+
+    #[test]
+    fn antichess_leaves_the_winner_without_pieces_or_moves() {
+        let position = position("8/8/8/8/8/8/p7/1R6 b - - 0 1", MoveRules::Antichess);
+        let taken = play(position, &["axb1=Q"]);
+        assert_eq!(taken.piece_count(Color::White), 0);
+
+        let blocked = Position::from_fen("8/8/8/8/8/p7/P7/8 w - - 0 1", MoveRules::Antichess);
+        assert!(blocked.unwrap().legal_moves().is_empty());
+    }
+
+    #[test]
+    fn san_round_trips_in_both_variants() {
+        let games = [
+            (
+                MoveRules::Atomic,
+                START_FEN,
+                ["Nf3", "d5", "Ne5", "Nf6", "Nxf7#"].as_slice(),
+            ),
+            (
+                MoveRules::Antichess,
+                ANTICHESS_FEN,
+                ["e3", "b5", "Bxb5", "c6", "Bxc6", "Nxc6"].as_slice(),
+            ),
+        ];
+        for (rules, fen, moves) in games {
+            let mut position = position(fen, rules);
+            for san in moves {
+                let chess_move = position.parse_san(san).unwrap();
+                assert_eq!(position.san(chess_move), *san);
+                position = position.make_move(chess_move);
+            }
+        }
+    }
+}
@@ -0,0 +1,1418 @@
+#[cfg(feature = "ssr")]
+use crate::game::GameState;
+#[cfg(feature = "ssr")]
+use crate::pgn;
+#[cfg(feature = "ssr")]
+use crate::rating::Rating;
+#[cfg(feature = "ssr")]
+use crate::shared::{
+    Account, GameResult, GameRoom, GameSummary, MatchScore, MoveRecord, RatingCategory,
+    RatingPoint, RoomConfig,
+};
+#[cfg(feature = "ssr")]
+use rusqlite::{params, Connection};
+#[cfg(feature = "ssr")]
+use std::collections::HashMap;
+#[cfg(feature = "ssr")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "ssr")]
+use tokio::sync::mpsc::UnboundedSender;
+
+// A room as kept across restarts. Connection ids are not stored since they
+// die with the server; players come back through their rejoin tokens
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub struct StoredRoom {
+    pub room_code: String,
+    pub white_token: Option<String>,
+    pub black_token: Option<String>,
+    pub white_name: Option<String>,
+    pub black_name: Option<String>,
+    pub white_account: Option<String>,
+    pub black_account: Option<String>,
+    pub rated: bool,
+    pub config: RoomConfig,
+    pub score: MatchScore,
+    pub game_id: String, // the game currently played in the room
+}
+
+#[cfg(feature = "ssr")]
+impl StoredRoom {
+    pub fn new(room: &GameRoom, game: &GameState) -> Self {
+        Self {
+            room_code: room.room_code.clone(),
+            white_token: room.white_token.clone(),
+            black_token: room.black_token.clone(),
+            white_name: room.white_name.clone(),
+            black_name: room.black_name.clone(),
+            white_account: room.white_account.clone(),
+            black_account: room.black_account.clone(),
+            rated: room.rated,
+            config: room.config.clone(),
+            score: room.score,
+            game_id: game.id.clone(),
+        }
+    }
+}
+
+// A game's setup and outcome; its moves are stored one by one as they are played
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub struct StoredGame {
+    pub id: String,
+    pub room_code: String,
+    pub config: RoomConfig,
+    pub started_at: u64,
+    pub result: Option<GameResult>,
+}
+
+#[cfg(feature = "ssr")]
+impl StoredGame {
+    pub fn new(room_code: &str, config: &RoomConfig, game: &GameState) -> Self {
+        Self {
+            id: game.id.clone(),
+            room_code: room_code.to_string(),
+            config: config.clone(),
+            started_at: game.started_at,
+            result: game.result.clone(),
+        }
+    }
+}
+
+// A move together with both clocks as they stood right after it
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub struct StoredMove {
+    pub record: MoveRecord,
+    pub white_time_ms: u64,
+    pub black_time_ms: u64,
+}
+
+#[cfg(feature = "ssr")]
+impl StoredMove {
+    // The last move of the game with its ply number
+    pub fn latest(game: &GameState) -> Option<(usize, Self)> {
+        let record = game.moves.last()?;
+        Some((
+            game.moves.len() - 1,
+            Self {
+                record: record.clone(),
+                white_time_ms: game.white_time_ms,
+                black_time_ms: game.black_time_ms,
+            },
+        ))
+    }
+}
+
+// A finished game as kept in the archive
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub struct ArchivedGame {
+    pub summary: GameSummary,
+    pub room_code: String,
+    pub pgn: String,
+    pub white_account: Option<String>, // None for guests
+    pub black_account: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl ArchivedGame {
+    // None while the game is still being played. Seats are (name, account)
+    pub fn new(
+        room_code: &str,
+        game: &GameState,
+        rated: bool,
+        (white, white_account): (Option<&str>, Option<&str>),
+        (black, black_account): (Option<&str>, Option<&str>),
+    ) -> Option<Self> {
+        let result = game.result.as_ref()?;
+        let opening = pgn::opening(game);
+        Some(Self {
+            summary: GameSummary {
+                id: game.id.clone(),
+                white: white.map(str::to_string),
+                black: black.map(str::to_string),
+                result: pgn::result_tag(Some(result)).to_string(),
+                termination: result.description().to_string(),
+                time_control: game
+                    .time_control
+                    .as_ref()
+                    .map_or_else(|| "-".to_string(), pgn::time_control_tag),
+                variant: game.variant.label(),
+                eco: opening.map(|(eco, _)| eco.to_string()),
+                opening: opening.map(|(_, name)| name.to_string()),
+                started_at: game.started_at,
+                ended_at: GameState::current_time_ms(),
+            },
+            room_code: room_code.to_string(),
+            pgn: pgn::export(game, room_code, rated, white, black),
+            white_account: white_account.map(str::to_string),
+            black_account: black_account.map(str::to_string),
+        })
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub struct StoredAccount {
+    pub account: Account,
+    pub password_hash: String,
+    pub created_at: u64,
+}
+
+// Filters for searching the archive; every filter given must match
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveQuery {
+    pub account: Option<String>, // the account id of either side
+    pub result: Option<String>,
+    pub eco: Option<String>, // a prefix, so "B" or "B9" select whole groups
+    pub time_control: Option<String>,
+    pub since: Option<u64>, // started at or after, in ms
+    pub until: Option<u64>, // started before, in ms
+    pub offset: usize,
+    pub limit: usize,
+}
+
+#[cfg(feature = "ssr")]
+impl ArchiveQuery {
+    fn matches(&self, archived: &ArchivedGame) -> bool {
+        let game = &archived.summary;
+        self.account.as_deref().is_none_or(|account| {
+            archived.white_account.as_deref() == Some(account)
+                || archived.black_account.as_deref() == Some(account)
+        }) && self
+            .result
+            .as_deref()
+            .is_none_or(|result| game.result == result)
+            && self.eco.as_deref().is_none_or(|eco| {
+                game.eco
+                    .as_deref()
+                    .is_some_and(|code| code.starts_with(eco))
+            })
+            && self
+                .time_control
+                .as_deref()
+                .is_none_or(|time_control| game.time_control == time_control)
+            && self.since.is_none_or(|since| game.started_at >= since)
+            && self.until.is_none_or(|until| game.started_at < until)
+    }
+}
+
+// Keeps rooms and games so they survive a restart of the server
+#[cfg(feature = "ssr")]
+pub trait Storage: Send + Sync {
+    fn save_room(&self, room: &StoredRoom) -> Result<(), String>;
+
+    // Rooms go once nobody is left in them; their games stay stored
+    fn remove_room(&self, room_code: &str) -> Result<(), String>;
+
+    fn save_game(&self, game: &StoredGame) -> Result<(), String>;
+
+    // Stores the move played as ply `ply` (0-based) of the game
+    fn save_move(&self, game_id: &str, ply: usize, stored: &StoredMove) -> Result<(), String>;
+
+    // Drops every move after the first `plies`, e.g. after a takeback
+    fn truncate_moves(&self, game_id: &str, plies: usize) -> Result<(), String>;
+
+    // Every room whose current game has no result yet, with that game and its moves
+    fn ongoing_games(&self) -> Result<Vec<(StoredRoom, StoredGame, Vec<StoredMove>)>, String>;
+
+    fn archive_game(&self, game: &ArchivedGame) -> Result<(), String>;
+
+    fn archived_game(&self, id: &str) -> Result<Option<ArchivedGame>, String>;
+
+    // The page of matching games the query selects, newest first, with the
+    // number of matching games in all
+    fn search_archive(&self, query: &ArchiveQuery) -> Result<(Vec<GameSummary>, usize), String>;
+
+    // False if the username is taken, ignoring case
+    fn create_account(&self, account: &StoredAccount) -> Result<bool, String>;
+
+    fn account_by_username(&self, username: &str) -> Result<Option<StoredAccount>, String>;
+
+    fn create_session(&self, token: &str, account_id: &str, expires_at: u64) -> Result<(), String>;
+
+    // The account a session belongs to, unless it has expired by `now`
+    fn session_account(&self, token: &str, now: u64) -> Result<Option<Account>, String>;
+
+    fn delete_session(&self, token: &str) -> Result<(), String>;
+
+    // Current ratings of the account in every category it has played
+    fn ratings(&self, account_id: &str) -> Result<Vec<(RatingCategory, Rating)>, String>;
+
+    // Stores the players' ratings after a rated game, by account id. Nothing
+    // is stored, and false returned, if the game has been rated already
+    fn record_ratings(
+        &self,
+        game_id: &str,
+        category: RatingCategory,
+        ratings: &[(&str, Rating)],
+        at: u64,
+    ) -> Result<bool, String>;
+
+    // Every rating the account has had, oldest first
+    fn rating_history(&self, account_id: &str) -> Result<Vec<RatingPoint>, String>;
+}
+
+// A change to a room or game, queued for the storage writer
+#[cfg(feature = "ssr")]
+pub enum StorageWrite {
+    SaveRoom(StoredRoom),
+    RemoveRoom(String),
+    SaveGame(StoredGame),
+    SaveMove {
+        game_id: String,
+        ply: usize,
+        stored: StoredMove,
+    },
+    TruncateMoves {
+        game_id: String,
+        plies: usize,
+    },
+    ArchiveGame(ArchivedGame),
+}
+
+#[cfg(feature = "ssr")]
+impl StorageWrite {
+    fn apply(&self, storage: &dyn Storage) -> Result<(), String> {
+        match self {
+            StorageWrite::SaveRoom(room) => storage
+                .save_room(room)
+                .map_err(|err| format!("Could not store room {}: {}", room.room_code, err)),
+            StorageWrite::RemoveRoom(room_code) => storage
+                .remove_room(room_code)
+                .map_err(|err| format!("Could not remove room {}: {}", room_code, err)),
+            StorageWrite::SaveGame(game) => storage
+                .save_game(game)
+                .map_err(|err| format!("Could not store game {}: {}", game.id, err)),
+            StorageWrite::SaveMove {
+                game_id,
+                ply,
+                stored,
+            } => storage
+                .save_move(game_id, *ply, stored)
+                .map_err(|err| format!("Could not store a move of game {}: {}", game_id, err)),
+            StorageWrite::TruncateMoves { game_id, plies } => storage
+                .truncate_moves(game_id, *plies)
+                .map_err(|err| format!("Could not take back moves of game {}: {}", game_id, err)),
+            StorageWrite::ArchiveGame(game) => storage
+                .archive_game(game)
+                .map_err(|err| format!("Could not archive game {}: {}", game.summary.id, err)),
+        }
+    }
+}
+
+// Applies queued writes one at a time on a thread of its own, so game
+// handlers never wait on the disk and writes land in the order they were
+// queued
+#[cfg(feature = "ssr")]
+#[derive(Clone)]
+pub struct StorageWriter {
+    sender: UnboundedSender<StorageWrite>,
+}
+
+#[cfg(feature = "ssr")]
+impl StorageWriter {
+    pub fn spawn(storage: Arc<dyn Storage>) -> Self {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<StorageWrite>();
+        std::thread::spawn(move || {
+            while let Some(write) = receiver.blocking_recv() {
+                if let Err(err) = write.apply(storage.as_ref()) {
+                    tracing::error!("{}", err);
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    pub fn queue(&self, write: StorageWrite) {
+        let _ = self.sender.send(write);
+    }
+}
+
+// Keeps everything in memory only, for tests or when no database can be opened
+#[cfg(feature = "ssr")]
+#[derive(Default)]
+pub struct MemoryStorage {
+    data: Mutex<MemoryData>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Default)]
+struct MemoryData {
+    rooms: HashMap<String, StoredRoom>,
+    games: HashMap<String, (StoredGame, Vec<StoredMove>)>,
+    archive: HashMap<String, ArchivedGame>,
+    accounts: HashMap<String, StoredAccount>, // by lowercase username
+    sessions: HashMap<String, (String, u64)>, // account id and expiry, by token
+    ratings: HashMap<(String, RatingCategory), Rating>,
+    rating_history: Vec<(String, RatingPoint)>, // by account id
+}
+
+#[cfg(feature = "ssr")]
+impl MemoryStorage {
+    fn data(&self) -> Result<std::sync::MutexGuard<'_, MemoryData>, String> {
+        self.data
+            .lock()
+            .map_err(|_| "Storage lock poisoned".to_string())
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl Storage for MemoryStorage {
+    fn save_room(&self, room: &StoredRoom) -> Result<(), String> {
+        self.data()?
+            .rooms
+            .insert(room.room_code.clone(), room.clone());
+        Ok(())
+    }
+
+    fn remove_room(&self, room_code: &str) -> Result<(), String> {
+        self.data()?.rooms.remove(room_code);
+        Ok(())
+    }
+
+    fn save_game(&self, game: &StoredGame) -> Result<(), String> {
+        let mut data = self.data()?;
+        let entry = data
+            .games
+            .entry(game.id.clone())
+            .or_insert_with(|| (game.clone(), Vec::new()));
+        entry.0 = game.clone();
+        Ok(())
+    }
+
+    fn save_move(&self, game_id: &str, ply: usize, stored: &StoredMove) -> Result<(), String> {
+        let mut data = self.data()?;
+        let (_, moves) = data.games.get_mut(game_id).ok_or("Unknown game")?;
+        if ply > moves.len() {
+            return Err(format!("Move {} stored before the ones preceding it", ply));
+        }
+        moves.truncate(ply);
+        moves.push(stored.clone());
+        Ok(())
+    }
+
+    fn truncate_moves(&self, game_id: &str, plies: usize) -> Result<(), String> {
+        if let Some((_, moves)) = self.data()?.games.get_mut(game_id) {
+            moves.truncate(plies);
+        }
+        Ok(())
+    }
+
+    fn ongoing_games(&self) -> Result<Vec<(StoredRoom, StoredGame, Vec<StoredMove>)>, String> {
+        let data = self.data()?;
+        Ok(data
+            .rooms
+            .values()
+            .filter_map(|room| {
+                let (game, moves) = data.games.get(&room.game_id)?;
+                game.result
+                    .is_none()
+                    .then(|| (room.clone(), game.clone(), moves.clone()))
+            })
+            .collect())
+    }
+
+    fn archive_game(&self, game: &ArchivedGame) -> Result<(), String> {
+        self.data()?
+            .archive
+            .insert(game.summary.id.clone(), game.clone());
+        Ok(())
+    }
+
+    fn archived_game(&self, id: &str) -> Result<Option<ArchivedGame>, String> {
+        Ok(self.data()?.archive.get(id).cloned())
+    }
+
+    fn search_archive(&self, query: &ArchiveQuery) -> Result<(Vec<GameSummary>, usize), String> {
+        let data = self.data()?;
+        let mut games: Vec<&GameSummary> = data
+            .archive
+            .values()
+            .filter(|game| query.matches(game))
+            .map(|game| &game.summary)
+            .collect();
+        games.sort_by(|a, b| b.started_at.cmp(&a.started_at).then(a.id.cmp(&b.id)));
+
+        let total = games.len();
+        let page = games
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit)
+            .cloned()
+            .collect();
+        Ok((page, total))
+    }
+
+    fn create_account(&self, account: &StoredAccount) -> Result<bool, String> {
+        let mut data = self.data()?;
+        let key = account.account.username.to_lowercase();
+        if data.accounts.contains_key(&key) {
+            return Ok(false);
+        }
+        data.accounts.insert(key, account.clone());
+        Ok(true)
+    }
+
+    fn account_by_username(&self, username: &str) -> Result<Option<StoredAccount>, String> {
+        Ok(self.data()?.accounts.get(&username.to_lowercase()).cloned())
+    }
+
+    fn create_session(&self, token: &str, account_id: &str, expires_at: u64) -> Result<(), String> {
+        self.data()?
+            .sessions
+            .insert(token.to_string(), (account_id.to_string(), expires_at));
+        Ok(())
+    }
+
+    fn session_account(&self, token: &str, now: u64) -> Result<Option<Account>, String> {
+        let data = self.data()?;
+        let Some((account_id, expires_at)) = data.sessions.get(token) else {
+            return Ok(None);
+        };
+        if *expires_at <= now {
+            return Ok(None);
+        }
+        Ok(data
+            .accounts
+            .values()
+            .find(|stored| &stored.account.id == account_id)
+            .map(|stored| stored.account.clone()))
+    }
+
+    fn delete_session(&self, token: &str) -> Result<(), String> {
+        self.data()?.sessions.remove(token);
+        Ok(())
+    }
+
+    fn ratings(&self, account_id: &str) -> Result<Vec<(RatingCategory, Rating)>, String> {
+        Ok(self
+            .data()?
+            .ratings
+            .iter()
+            .filter(|((id, _), _)| id == account_id)
+            .map(|((_, category), rating)| (*category, *rating))
+            .collect())
+    }
+
+    fn record_ratings(
+        &self,
+        game_id: &str,
+        category: RatingCategory,
+        ratings: &[(&str, Rating)],
+        at: u64,
+    ) -> Result<bool, String> {
+        let mut data = self.data()?;
+        if data
+            .rating_history
+            .iter()
+            .any(|(_, point)| point.game_id == game_id)
+        {
+            return Ok(false);
+        }
+        for (account_id, rating) in ratings {
+            data.ratings
+                .insert((account_id.to_string(), category), *rating);
+            data.rating_history.push((
+                account_id.to_string(),
+                rating_point(category, game_id, rating, at),
+            ));
+        }
+        Ok(true)
+    }
+
+    fn rating_history(&self, account_id: &str) -> Result<Vec<RatingPoint>, String> {
+        Ok(self
+            .data()?
+            .rating_history
+            .iter()
+            .filter(|(id, _)| id == account_id)
+            .map(|(_, point)| point.clone())
+            .collect())
+    }
+}
+
+// Applied in order to bring a database up to date; the number applied so far
+// is kept in `PRAGMA user_version`. Never edit one that has shipped
+#[cfg(feature = "ssr")]
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE IF NOT EXISTS games (
+        id TEXT PRIMARY KEY,
+        room_code TEXT NOT NULL,
+        config TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        result TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS moves (
+        game_id TEXT NOT NULL REFERENCES games (id),
+        ply INTEGER NOT NULL,
+        san TEXT NOT NULL,
+        from_square TEXT NOT NULL,
+        to_square TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        white_time_ms INTEGER NOT NULL,
+        black_time_ms INTEGER NOT NULL,
+        PRIMARY KEY (game_id, ply)
+    );
+
+    CREATE TABLE IF NOT EXISTS rooms (
+        room_code TEXT PRIMARY KEY,
+        white_token TEXT,
+        black_token TEXT,
+        config TEXT NOT NULL,
+        score TEXT NOT NULL,
+        game_id TEXT NOT NULL REFERENCES games (id)
+    );
+    ",
+    "
+    ALTER TABLE rooms ADD COLUMN white_name TEXT;
+    ALTER TABLE rooms ADD COLUMN black_name TEXT;
+
+    CREATE TABLE archive (
+        id TEXT PRIMARY KEY,
+        room_code TEXT NOT NULL,
+        white TEXT,
+        black TEXT,
+        result TEXT NOT NULL,
+        termination TEXT NOT NULL,
+        time_control TEXT NOT NULL,
+        variant TEXT NOT NULL,
+        eco TEXT,
+        opening TEXT,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER NOT NULL,
+        pgn TEXT NOT NULL
+    );
+    CREATE INDEX archive_white ON archive (white COLLATE NOCASE);
+    CREATE INDEX archive_black ON archive (black COLLATE NOCASE);
+    CREATE INDEX archive_started_at ON archive (started_at);
+    ",
+    "
+    CREATE TABLE accounts (
+        id TEXT PRIMARY KEY,
+        username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+        display_name TEXT,
+        password_hash TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE sessions (
+        token TEXT PRIMARY KEY,
+        account_id TEXT NOT NULL REFERENCES accounts (id),
+        expires_at INTEGER NOT NULL
+    );
+
+    ALTER TABLE rooms ADD COLUMN white_account TEXT;
+    ALTER TABLE rooms ADD COLUMN black_account TEXT;
+    ",
+    "
+    ALTER TABLE rooms ADD COLUMN rated INTEGER NOT NULL DEFAULT 0;
+
+    CREATE TABLE ratings (
+        account_id TEXT NOT NULL REFERENCES accounts (id),
+        category TEXT NOT NULL,
+        rating REAL NOT NULL,
+        deviation REAL NOT NULL,
+        volatility REAL NOT NULL,
+        games INTEGER NOT NULL,
+        PRIMARY KEY (account_id, category)
+    );
+
+    CREATE TABLE rating_history (
+        game_id TEXT NOT NULL,
+        account_id TEXT NOT NULL REFERENCES accounts (id),
+        category TEXT NOT NULL,
+        rating REAL NOT NULL,
+        deviation REAL NOT NULL,
+        recorded_at INTEGER NOT NULL,
+        PRIMARY KEY (game_id, account_id)
+    );
+    CREATE INDEX rating_history_account ON rating_history (account_id, recorded_at);
+    ",
+    "
+    ALTER TABLE archive ADD COLUMN white_account TEXT;
+    ALTER TABLE archive ADD COLUMN black_account TEXT;
+    CREATE INDEX archive_white_account ON archive (white_account);
+    CREATE INDEX archive_black_account ON archive (black_account);
+    DROP INDEX archive_white;
+    DROP INDEX archive_black;
+    ",
+];
+
+// A single SQLite file, with configs, scores and results kept as JSON
+#[cfg(feature = "ssr")]
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+#[cfg(feature = "ssr")]
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let mut connection = Connection::open(path).map_err(db_error)?;
+        connection
+            .execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")
+            .map_err(db_error)?;
+
+        let applied: usize = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(db_error)?;
+        for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+            let transaction = connection.transaction().map_err(db_error)?;
+            transaction.execute_batch(migration).map_err(db_error)?;
+            transaction
+                .pragma_update(None, "user_version", version + 1)
+                .map_err(db_error)?;
+            transaction.commit().map_err(db_error)?;
+        }
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> Result<std::sync::MutexGuard<'_, Connection>, String> {
+        self.connection
+            .lock()
+            .map_err(|_| "Storage lock poisoned".to_string())
+    }
+
+    fn moves(&self, connection: &Connection, game_id: &str) -> Result<Vec<StoredMove>, String> {
+        let mut statement = connection
+            .prepare(
+                "SELECT san, from_square, to_square, timestamp, white_time_ms, black_time_ms
+                 FROM moves WHERE game_id = ?1 ORDER BY ply",
+            )
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map(params![game_id], |row| {
+                Ok(StoredMove {
+                    record: MoveRecord {
+                        san: row.get(0)?,
+                        from: row.get(1)?,
+                        to: row.get(2)?,
+                        timestamp: row.get::<_, i64>(3)? as u64,
+                    },
+                    white_time_ms: row.get::<_, i64>(4)? as u64,
+                    black_time_ms: row.get::<_, i64>(5)? as u64,
+                })
+            })
+            .map_err(db_error)?;
+        rows.collect::<Result<_, _>>().map_err(db_error)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl Storage for SqliteStorage {
+    fn save_room(&self, room: &StoredRoom) -> Result<(), String> {
+        self.connection()?
+            .execute(
+                "INSERT OR REPLACE INTO rooms
+                 (room_code, white_token, black_token, white_name, black_name, white_account,
+                  black_account, rated, config, score, game_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    room.room_code,
+                    room.white_token,
+                    room.black_token,
+                    room.white_name,
+                    room.black_name,
+                    room.white_account,
+                    room.black_account,
+                    room.rated,
+                    to_json(&room.config)?,
+                    to_json(&room.score)?,
+                    room.game_id,
+                ],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn remove_room(&self, room_code: &str) -> Result<(), String> {
+        self.connection()?
+            .execute("DELETE FROM rooms WHERE room_code = ?1", params![room_code])
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn save_game(&self, game: &StoredGame) -> Result<(), String> {
+        let result = game.result.as_ref().map(to_json).transpose()?;
+        self.connection()?
+            .execute(
+                "INSERT INTO games (id, room_code, config, started_at, result)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (id) DO UPDATE SET started_at = ?4, result = ?5",
+                params![
+                    game.id,
+                    game.room_code,
+                    to_json(&game.config)?,
+                    game.started_at as i64,
+                    result,
+                ],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn save_move(&self, game_id: &str, ply: usize, stored: &StoredMove) -> Result<(), String> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction().map_err(db_error)?;
+        transaction
+            .execute(
+                "DELETE FROM moves WHERE game_id = ?1 AND ply >= ?2",
+                params![game_id, ply as i64],
+            )
+            .map_err(db_error)?;
+        transaction
+            .execute(
+                "INSERT INTO moves (game_id, ply, san, from_square, to_square, timestamp,
+                                    white_time_ms, black_time_ms)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    game_id,
+                    ply as i64,
+                    stored.record.san,
+                    stored.record.from,
+                    stored.record.to,
+                    stored.record.timestamp as i64,
+                    stored.white_time_ms as i64,
+                    stored.black_time_ms as i64,
+                ],
+            )
+            .map_err(db_error)?;
+        transaction.commit().map_err(db_error)
+    }
+
+    fn truncate_moves(&self, game_id: &str, plies: usize) -> Result<(), String> {
+        self.connection()?
+            .execute(
+                "DELETE FROM moves WHERE game_id = ?1 AND ply >= ?2",
+                params![game_id, plies as i64],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn ongoing_games(&self) -> Result<Vec<(StoredRoom, StoredGame, Vec<StoredMove>)>, String> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT rooms.room_code, rooms.white_token, rooms.white_name, rooms.white_account,
+                        rooms.black_token, rooms.black_name, rooms.black_account, rooms.config,
+                        rooms.score, games.id, games.config, games.started_at, rooms.rated
+                 FROM rooms JOIN games ON games.id = rooms.game_id
+                 WHERE games.result IS NULL",
+            )
+            .map_err(db_error)?;
+        // Token, name and account of each seat
+        let seat = |row: &rusqlite::Row, first: usize| -> rusqlite::Result<[Option<String>; 3]> {
+            Ok([row.get(first)?, row.get(first + 1)?, row.get(first + 2)?])
+        };
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    seat(row, 1)?,
+                    seat(row, 4)?,
+                    row.get::<_, String>(7)?,
+                    row.get::<_, String>(8)?,
+                    row.get::<_, String>(9)?,
+                    row.get::<_, String>(10)?,
+                    row.get::<_, i64>(11)?,
+                    row.get::<_, bool>(12)?,
+                ))
+            })
+            .map_err(db_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_error)?;
+
+        rows.into_iter()
+            .map(
+                |(
+                    room_code,
+                    [white_token, white_name, white_account],
+                    [black_token, black_name, black_account],
+                    config,
+                    score,
+                    game_id,
+                    game_config,
+                    started_at,
+                    rated,
+                )| {
+                    let room = StoredRoom {
+                        room_code: room_code.clone(),
+                        white_token,
+                        black_token,
+                        white_name,
+                        black_name,
+                        white_account,
+                        black_account,
+                        rated,
+                        config: from_json(&config)?,
+                        score: from_json(&score)?,
+                        game_id: game_id.clone(),
+                    };
+                    let moves = self.moves(&connection, &game_id)?;
+                    let game = StoredGame {
+                        id: game_id,
+                        room_code,
+                        config: from_json(&game_config)?,
+                        started_at: started_at as u64,
+                        result: None,
+                    };
+                    Ok((room, game, moves))
+                },
+            )
+            .collect()
+    }
+
+    fn archive_game(&self, game: &ArchivedGame) -> Result<(), String> {
+        let summary = &game.summary;
+        self.connection()?
+            .execute(
+                "INSERT OR REPLACE INTO archive
+                 (id, room_code, white, black, result, termination, time_control, variant, eco,
+                  opening, started_at, ended_at, pgn, white_account, black_account)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+                params![
+                    summary.id,
+                    game.room_code,
+                    summary.white,
+                    summary.black,
+                    summary.result,
+                    summary.termination,
+                    summary.time_control,
+                    summary.variant,
+                    summary.eco,
+                    summary.opening,
+                    summary.started_at as i64,
+                    summary.ended_at as i64,
+                    game.pgn,
+                    game.white_account,
+                    game.black_account,
+                ],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn archived_game(&self, id: &str) -> Result<Option<ArchivedGame>, String> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {}, room_code, pgn, white_account, black_account FROM archive
+                 WHERE id = ?1",
+                SUMMARY_COLUMNS
+            ))
+            .map_err(db_error)?;
+        let mut rows = statement
+            .query_map(params![id], |row| {
+                Ok(ArchivedGame {
+                    summary: summary_row(row)?,
+                    room_code: row.get(11)?,
+                    pgn: row.get(12)?,
+                    white_account: row.get(13)?,
+                    black_account: row.get(14)?,
+                })
+            })
+            .map_err(db_error)?;
+        rows.next().transpose().map_err(db_error)
+    }
+
+    fn search_archive(&self, query: &ArchiveQuery) -> Result<(Vec<GameSummary>, usize), String> {
+        let filter = "(?1 IS NULL OR white_account = ?1 OR black_account = ?1)
+             AND (?2 IS NULL OR result = ?2)
+             AND (?3 IS NULL OR substr(eco, 1, length(?3)) = ?3)
+             AND (?4 IS NULL OR time_control = ?4)
+             AND (?5 IS NULL OR started_at >= ?5)
+             AND (?6 IS NULL OR started_at < ?6)";
+        let since = query.since.map(|ms| ms as i64);
+        let until = query.until.map(|ms| ms as i64);
+        let filters = params![
+            query.account,
+            query.result,
+            query.eco,
+            query.time_control,
+            since,
+            until
+        ];
+
+        let connection = self.connection()?;
+        let total: i64 = connection
+            .query_row(
+                &format!("SELECT COUNT(*) FROM archive WHERE {}", filter),
+                filters,
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+
+        let (limit, offset) = (query.limit as i64, query.offset as i64);
+        let page = [filters, params![limit, offset]].concat();
+        let mut statement = connection
+            .prepare(&format!(
+                "SELECT {} FROM archive WHERE {}
+                 ORDER BY started_at DESC, id LIMIT ?7 OFFSET ?8",
+                SUMMARY_COLUMNS, filter
+            ))
+            .map_err(db_error)?;
+        let games = statement
+            .query_map(page.as_slice(), summary_row)
+            .map_err(db_error)?
+            .collect::<Result<_, _>>()
+            .map_err(db_error)?;
+
+        Ok((games, total as usize))
+    }
+
+    fn create_account(&self, stored: &StoredAccount) -> Result<bool, String> {
+        let inserted = self
+            .connection()?
+            .execute(
+                "INSERT INTO accounts (id, username, display_name, password_hash, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (username) DO NOTHING",
+                params![
+                    stored.account.id,
+                    stored.account.username,
+                    stored.account.display_name,
+                    stored.password_hash,
+                    stored.created_at as i64,
+                ],
+            )
+            .map_err(db_error)?;
+        Ok(inserted > 0)
+    }
+
+    fn account_by_username(&self, username: &str) -> Result<Option<StoredAccount>, String> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT id, username, display_name, password_hash, created_at
+                 FROM accounts WHERE username = ?1",
+            )
+            .map_err(db_error)?;
+        let mut rows = statement
+            .query_map(params![username], |row| {
+                Ok(StoredAccount {
+                    account: Account {
+                        id: row.get(0)?,
+                        username: row.get(1)?,
+                        display_name: row.get(2)?,
+                    },
+                    password_hash: row.get(3)?,
+                    created_at: row.get::<_, i64>(4)? as u64,
+                })
+            })
+            .map_err(db_error)?;
+        rows.next().transpose().map_err(db_error)
+    }
+
+    fn create_session(&self, token: &str, account_id: &str, expires_at: u64) -> Result<(), String> {
+        let connection = self.connection()?;
+        // Expired sessions are swept whenever someone logs in
+        connection
+            .execute(
+                "DELETE FROM sessions WHERE expires_at <= ?1",
+                params![GameState::current_time_ms() as i64],
+            )
+            .map_err(db_error)?;
+        connection
+            .execute(
+                "INSERT INTO sessions (token, account_id, expires_at) VALUES (?1, ?2, ?3)",
+                params![token, account_id, expires_at as i64],
+            )
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    fn session_account(&self, token: &str, now: u64) -> Result<Option<Account>, String> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT accounts.id, accounts.username, accounts.display_name
+                 FROM sessions JOIN accounts ON accounts.id = sessions.account_id
+                 WHERE sessions.token = ?1 AND sessions.expires_at > ?2",
+            )
+            .map_err(db_error)?;
+        let mut rows = statement
+            .query_map(params![token, now as i64], |row| {
+                Ok(Account {
+                    id: row.get(0)?,
+                    username: row.get(1)?,
+                    display_name: row.get(2)?,
+                })
+            })
+            .map_err(db_error)?;
+        rows.next().transpose().map_err(db_error)
+    }
+
+    fn delete_session(&self, token: &str) -> Result<(), String> {
+        self.connection()?
+            .execute("DELETE FROM sessions WHERE token = ?1", params![token])
+            .map_err(db_error)?;
+        Ok(())
+    }
+    fn ratings(&self, account_id: &str) -> Result<Vec<(RatingCategory, Rating)>, String> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT category, rating, deviation, volatility, games
+                 FROM ratings WHERE account_id = ?1",
+            )
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map(params![account_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    Rating {
+                        rating: row.get(1)?,
+                        deviation: row.get(2)?,
+                        volatility: row.get(3)?,
+                        games: row.get(4)?,
+                    },
+                ))
+            })
+            .map_err(db_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_error)?;
+
+        rows.into_iter()
+            .map(|(category, rating)| Ok((from_json(&category)?, rating)))
+            .collect()
+    }
+
+    fn record_ratings(
+        &self,
+        game_id: &str,
+        category: RatingCategory,
+        ratings: &[(&str, Rating)],
+        at: u64,
+    ) -> Result<bool, String> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction().map_err(db_error)?;
+        let rated_already: bool = transaction
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM rating_history WHERE game_id = ?1)",
+                params![game_id],
+                |row| row.get(0),
+            )
+            .map_err(db_error)?;
+        if rated_already {
+            return Ok(false);
+        }
+
+        let category = to_json(&category)?;
+        for (account_id, rating) in ratings {
+            transaction
+                .execute(
+                    "INSERT OR REPLACE INTO ratings
+                     (account_id, category, rating, deviation, volatility, games)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        account_id,
+                        category,
+                        rating.rating,
+                        rating.deviation,
+                        rating.volatility,
+                        rating.games,
+                    ],
+                )
+                .map_err(db_error)?;
+            transaction
+                .execute(
+                    "INSERT INTO rating_history
+                     (game_id, account_id, category, rating, deviation, recorded_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        game_id,
+                        account_id,
+                        category,
+                        rating.rating,
+                        rating.deviation,
+                        at as i64,
+                    ],
+                )
+                .map_err(db_error)?;
+        }
+        transaction.commit().map_err(db_error)?;
+        Ok(true)
+    }
+
+    fn rating_history(&self, account_id: &str) -> Result<Vec<RatingPoint>, String> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT category, game_id, rating, deviation, recorded_at
+                 FROM rating_history WHERE account_id = ?1
+                 ORDER BY recorded_at, game_id",
+            )
+            .map_err(db_error)?;
+        let rows = statement
+            .query_map(params![account_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    Rating {
+                        rating: row.get(2)?,
+                        deviation: row.get(3)?,
+                        ..Rating::default()
+                    },
+                    row.get::<_, i64>(4)?,
+                ))
+            })
+            .map_err(db_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_error)?;
+
+        rows.into_iter()
+            .map(|(category, game_id, rating, at)| {
+                Ok(rating_point(
+                    from_json(&category)?,
+                    &game_id,
+                    &rating,
+                    at as u64,
+                ))
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "ssr")]
+const SUMMARY_COLUMNS: &str = "id, white, black, result, termination, time_control, variant, \
+                               eco, opening, started_at, ended_at";
+
+// Reads the columns in `SUMMARY_COLUMNS` order
+#[cfg(feature = "ssr")]
+fn summary_row(row: &rusqlite::Row) -> rusqlite::Result<GameSummary> {
+    Ok(GameSummary {
+        id: row.get(0)?,
+        white: row.get(1)?,
+        black: row.get(2)?,
+        result: row.get(3)?,
+        termination: row.get(4)?,
+        time_control: row.get(5)?,
+        variant: row.get(6)?,
+        eco: row.get(7)?,
+        opening: row.get(8)?,
+        started_at: row.get::<_, i64>(9)? as u64,
+        ended_at: row.get::<_, i64>(10)? as u64,
+    })
+}
+
+#[cfg(feature = "ssr")]
+fn rating_point(category: RatingCategory, game_id: &str, rating: &Rating, at: u64) -> RatingPoint {
+    RatingPoint {
+        category,
+        game_id: game_id.to_string(),
+        rating: rating.rating.round() as i32,
+        deviation: rating.deviation.round() as i32,
+        recorded_at: at,
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|err| err.to_string())
+}
+
+#[cfg(feature = "ssr")]
+fn from_json<T: serde::de::DeserializeOwned>(json: &str) -> Result<T, String> {
+    serde_json::from_str(json).map_err(|err| format!("Corrupt stored value: {}", err))
+}
+
+#[cfg(feature = "ssr")]
+fn db_error(err: rusqlite::Error) -> String {
+    format!("Database error: {}", err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::{PlayerColor, TimeControl, Variant};
+
+    const MOVES: [(&str, &str); 5] = [
+        ("e2", "e4"),
+        ("e7", "e5"),
+        ("g1", "f3"),
+        ("b8", "c6"),
+        ("f1", "b5"),
+    ];
+
+    fn config() -> RoomConfig {
+        RoomConfig {
+            allow_takebacks: true,
+            time_control: Some(TimeControl::new(300_000, 2_000)),
+            start_fen: None,
+            variant: Variant::Standard,
+        }
+    }
+
+    fn room(config: &RoomConfig) -> GameRoom {
+        GameRoom {
+            room_code: "ROOM42".to_string(),
+            white_player: Some("white-player".to_string()),
+            black_player: Some("black-player".to_string()),
+            white_token: Some("white-token".to_string()),
+            black_token: Some("black-token".to_string()),
+            white_name: Some("Alice".to_string()),
+            black_name: Some("Bob".to_string()),
+            white_account: None,
+            black_account: None,
+            rated: false,
+            spectators: Vec::new(),
+            config: config.clone(),
+            rematch_offer: None,
+            score: MatchScore::default(),
+        }
+    }
+
+    // Plays and stores every move with clocks as if the players had thought
+    // for a while, then takes White's last move back
+    fn store_game(storage: &dyn Storage) -> GameState {
+        let config = config();
+        let mut game = GameState::new(&config).unwrap();
+        storage
+            .save_game(&StoredGame::new("ROOM42", &config, &game))
+            .unwrap();
+        storage
+            .save_room(&StoredRoom::new(&room(&config), &game))
+            .unwrap();
+
+        for (ply, (from, to)) in MOVES.iter().enumerate() {
+            game.make_move(from, to, None).unwrap();
+            game.white_time_ms = 290_000 - 3_000 * ply as u64;
+            game.black_time_ms = 280_000 - 5_000 * ply as u64;
+            let (stored_ply, stored) = StoredMove::latest(&game).unwrap();
+            assert_eq!(stored_ply, ply);
+            storage.save_move(&game.id, ply, &stored).unwrap();
+        }
+
+        game.request_takeback(PlayerColor::White).unwrap();
+        game.accept_takeback(PlayerColor::Black).unwrap();
+        storage.truncate_moves(&game.id, game.moves.len()).unwrap();
+        game
+    }
+
+    fn assert_restores(storage: &dyn Storage, played: &GameState) {
+        let ongoing = storage.ongoing_games().unwrap();
+        assert_eq!(ongoing.len(), 1);
+        let (room, stored, moves) = &ongoing[0];
+        assert_eq!(room.room_code, "ROOM42");
+        assert_eq!(room.white_token.as_deref(), Some("white-token"));
+        assert_eq!(room.black_name.as_deref(), Some("Bob"));
+        assert_eq!(room.game_id, played.id);
+
+        let restored = GameState::restore(stored, moves).unwrap();
+        assert_eq!(restored.get_fen(), played.get_fen());
+        let moves = |game: &GameState| -> Vec<(String, String, String, u64)> {
+            game.moves
+                .iter()
+                .map(|m| (m.san.clone(), m.from.clone(), m.to.clone(), m.timestamp))
+                .collect()
+        };
+        assert_eq!(moves(&restored), moves(played));
+        assert_eq!(restored.white_time_ms, 281_000);
+        assert_eq!(restored.black_time_ms, 265_000);
+
+        // White is to move and resumes from the stored clock, not charged for
+        // the time the server was down
+        assert_eq!(restored.current_turn(), PlayerColor::White);
+        assert!(restored.remaining_time(PlayerColor::White) > 280_000);
+        assert_eq!(restored.remaining_time(PlayerColor::Black), 265_000);
+    }
+
+    fn assert_finished_games_stay_down(storage: &dyn Storage, played: &GameState) {
+        let mut finished = StoredGame::new("ROOM42", &config(), played);
+        finished.result = Some(GameResult::Resignation {
+            winner: PlayerColor::White,
+        });
+        storage.save_game(&finished).unwrap();
+        assert!(storage.ongoing_games().unwrap().is_empty());
+    }
+
+    // A guest named after a registered player must not show up in their games
+    fn assert_archive_matches_accounts(storage: &dyn Storage) {
+        let mut games = Vec::new();
+        for (white_account, black_account) in [(Some("alice-id"), None), (None, Some("bob-id"))] {
+            let mut game = GameState::new(&config()).unwrap();
+            game.result = Some(GameResult::Resignation {
+                winner: PlayerColor::White,
+            });
+            let archived = ArchivedGame::new(
+                "ROOM42",
+                &game,
+                false,
+                (Some("Alice"), white_account),
+                (Some("Bob"), black_account),
+            )
+            .unwrap();
+            storage.archive_game(&archived).unwrap();
+            games.push(game.id);
+        }
+
+        let search = |account: &str| {
+            let query = ArchiveQuery {
+                account: Some(account.to_string()),
+                limit: 10,
+                ..Default::default()
+            };
+            let (found, total) = storage.search_archive(&query).unwrap();
+            assert_eq!(found.len(), total);
+            found.into_iter().map(|game| game.id).collect::<Vec<_>>()
+        };
+        assert_eq!(search("alice-id"), [games[0].clone()]);
+        assert_eq!(search("bob-id"), [games[1].clone()]);
+        assert!(search("Alice").is_empty());
+
+        let archived = storage.archived_game(&games[0]).unwrap().unwrap();
+        assert_eq!(archived.white_account.as_deref(), Some("alice-id"));
+        assert_eq!(archived.black_account, None);
+        assert_eq!(archived.summary.black.as_deref(), Some("Bob"));
+    }
+
+    #[test]
+    fn memory_storage_restores_ongoing_games() {
+        let storage = MemoryStorage::default();
+        let played = store_game(&storage);
+        assert_restores(&storage, &played);
+        assert_finished_games_stay_down(&storage, &played);
+    }
+
+    #[test]
+    fn sqlite_storage_restores_ongoing_games() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let played = store_game(&storage);
+        assert_restores(&storage, &played);
+        assert_finished_games_stay_down(&storage, &played);
+    }
+
+    #[test]
+    fn memory_storage_searches_the_archive_by_account() {
+        assert_archive_matches_accounts(&MemoryStorage::default());
+    }
+
+    #[test]
+    fn sqlite_storage_searches_the_archive_by_account() {
+        assert_archive_matches_accounts(&SqliteStorage::open(":memory:").unwrap());
+    }
+
+    #[test]
+    fn sqlite_storage_survives_reopening() {
+        let path = std::env::temp_dir().join(format!("chess-{}.db", uuid::Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+
+        let played = store_game(&SqliteStorage::open(path).unwrap());
+        // Reopening finds every migration applied and the game still there
+        let reopened = SqliteStorage::open(path).unwrap();
+        assert_restores(&reopened, &played);
+
+        drop(reopened);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+}
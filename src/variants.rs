@@ -0,0 +1,431 @@
+#[cfg(feature = "ssr")]
+use crate::game::{can_mate, insufficient_material, GameState};
+#[cfg(feature = "ssr")]
+use crate::shared::{GameResult, PlayerColor, Variant};
+#[cfg(feature = "ssr")]
+use chess::{Board, BoardStatus, ChessMove, Color, MoveGen, Rank, Square};
+#[cfg(feature = "ssr")]
+use std::str::FromStr;
+
+#[cfg(feature = "ssr")]
+const RACING_KINGS_FEN: &str = "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1";
+
+// Rules a variant layers over move generation, the chess crate's or the
+// game's own `Position`. Checkmate and stalemate apply unless `outcome` decides
+// first; the hooks add win conditions and adjust which moves and material count
+#[cfg(feature = "ssr")]
+pub trait Rules: Sync {
+    fn start_board(&self) -> Board {
+        Board::default()
+    }
+
+    fn is_legal(&self, board: &Board, chess_move: ChessMove) -> bool {
+        board.legal(chess_move)
+    }
+
+    fn has_legal_move(&self, game: &GameState) -> bool {
+        game.board.status() == BoardStatus::Ongoing
+    }
+
+    // Result from the variant's own win conditions, checked after every move
+    // before the standard endings
+    fn outcome(&self, _game: &GameState) -> Option<GameResult> {
+        None
+    }
+
+    // Neither side can ever win, so the game is drawn
+    fn insufficient_material(&self, game: &GameState) -> bool {
+        insufficient_material(&game.board)
+    }
+
+    // Whether `color` could still win, deciding if running out of time loses
+    fn can_win(&self, game: &GameState, color: Color) -> bool {
+        can_mate(&game.board, color)
+    }
+}
+
+// Standard chess, also used by Chess960 whose castling is handled by the game
+#[cfg(feature = "ssr")]
+pub struct Standard;
+
+#[cfg(feature = "ssr")]
+impl Rules for Standard {}
+
+// Bringing the king to one of the four centre squares wins
+#[cfg(feature = "ssr")]
+pub struct KingOfTheHill;
+
+#[cfg(feature = "ssr")]
+impl Rules for KingOfTheHill {
+    fn outcome(&self, game: &GameState) -> Option<GameResult> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|&color| is_centre(game.board.king_square(color)))
+            .map(|color| GameResult::KingOfTheHill {
+                winner: player_color(color),
+            })
+    }
+
+    fn insufficient_material(&self, _game: &GameState) -> bool {
+        false
+    }
+
+    fn can_win(&self, _game: &GameState, _color: Color) -> bool {
+        true
+    }
+}
+
+// Giving check for the third time wins
+#[cfg(feature = "ssr")]
+pub struct ThreeCheck;
+
+#[cfg(feature = "ssr")]
+impl Rules for ThreeCheck {
+    fn outcome(&self, game: &GameState) -> Option<GameResult> {
+        if game.checks.white >= 3 {
+            Some(GameResult::ThreeCheck {
+                winner: PlayerColor::White,
+            })
+        } else if game.checks.black >= 3 {
+            Some(GameResult::ThreeCheck {
+                winner: PlayerColor::Black,
+            })
+        } else {
+            None
+        }
+    }
+
+    // Any piece besides the king can still give checks
+    fn insufficient_material(&self, game: &GameState) -> bool {
+        game.board.combined().popcnt() == 2
+    }
+
+    fn can_win(&self, game: &GameState, color: Color) -> bool {
+        game.board.color_combined(color).popcnt() > 1
+    }
+}
+
+// Both sides race their king to the eighth rank; no move may give check.
+// If white gets there first black has one move left to draw by arriving too
+#[cfg(feature = "ssr")]
+pub struct RacingKings;
+
+#[cfg(feature = "ssr")]
+impl Rules for RacingKings {
+    fn start_board(&self) -> Board {
+        Board::from_str(RACING_KINGS_FEN).unwrap_or_default()
+    }
+
+    fn is_legal(&self, board: &Board, chess_move: ChessMove) -> bool {
+        board.legal(chess_move) && board.make_move_new(chess_move).checkers().popcnt() == 0
+    }
+
+    fn has_legal_move(&self, game: &GameState) -> bool {
+        MoveGen::new_legal(&game.board).any(|chess_move| self.is_legal(&game.board, chess_move))
+    }
+
+    fn outcome(&self, game: &GameState) -> Option<GameResult> {
+        let board = &game.board;
+        let at_goal = |color| board.king_square(color).get_rank() == Rank::Eighth;
+
+        match (at_goal(Color::White), at_goal(Color::Black)) {
+            (true, true) => Some(GameResult::RaceDrawn),
+            (false, true) => Some(GameResult::RaceWon {
+                winner: PlayerColor::Black,
+            }),
+            (true, false) => {
+                let black_can_follow = board.side_to_move() == Color::Black
+                    && MoveGen::new_legal(board).any(|chess_move| {
+                        chess_move.get_source() == board.king_square(Color::Black)
+                            && chess_move.get_dest().get_rank() == Rank::Eighth
+                            && self.is_legal(board, chess_move)
+                    });
+                if black_can_follow {
+                    None
+                } else {
+                    Some(GameResult::RaceWon {
+                        winner: PlayerColor::White,
+                    })
+                }
+            }
+            (false, false) => None,
+        }
+    }
+
+    fn insufficient_material(&self, _game: &GameState) -> bool {
+        false
+    }
+
+    fn can_win(&self, _game: &GameState, _color: Color) -> bool {
+        true
+    }
+}
+
+// Captured pieces change sides and may be dropped back onto the board
+#[cfg(feature = "ssr")]
+pub struct Crazyhouse;
+
+#[cfg(feature = "ssr")]
+impl Rules for Crazyhouse {
+    // A check with no move out of it can still be blocked by a drop
+    fn has_legal_move(&self, game: &GameState) -> bool {
+        game.board.status() == BoardStatus::Ongoing
+            || game
+                .reserves
+                .is_some_and(|reserves| reserves.has_drop(&game.board))
+    }
+
+    fn insufficient_material(&self, _game: &GameState) -> bool {
+        false
+    }
+
+    fn can_win(&self, _game: &GameState, _color: Color) -> bool {
+        true
+    }
+}
+
+// Captures explode the pieces around them; blowing up the enemy king wins.
+// Played on the game's own `Position`, as are the Antichess rules below
+#[cfg(feature = "ssr")]
+pub struct Atomic;
+
+#[cfg(feature = "ssr")]
+impl Rules for Atomic {
+    fn has_legal_move(&self, game: &GameState) -> bool {
+        game.position
+            .is_some_and(|position| !position.legal_moves().is_empty())
+    }
+
+    fn outcome(&self, game: &GameState) -> Option<GameResult> {
+        let position = game.position?;
+        let loser = position.side_to_move();
+        position
+            .king(loser)
+            .is_none()
+            .then(|| GameResult::Explosion {
+                winner: player_color(!loser),
+            })
+    }
+
+    // Two bare kings can never blow each other up
+    fn insufficient_material(&self, game: &GameState) -> bool {
+        game.position.is_some_and(|position| {
+            position.piece_count(Color::White) + position.piece_count(Color::Black) == 2
+        })
+    }
+
+    fn can_win(&self, game: &GameState, color: Color) -> bool {
+        game.position
+            .is_some_and(|position| position.piece_count(color) > 1)
+    }
+}
+
+// Captures are compulsory and whoever runs out of pieces or moves wins
+#[cfg(feature = "ssr")]
+pub struct Antichess;
+
+#[cfg(feature = "ssr")]
+impl Rules for Antichess {
+    fn has_legal_move(&self, game: &GameState) -> bool {
+        game.position
+            .is_some_and(|position| !position.legal_moves().is_empty())
+    }
+
+    fn outcome(&self, game: &GameState) -> Option<GameResult> {
+        let position = game.position?;
+        let winner = player_color(position.side_to_move());
+        if position.piece_count(position.side_to_move()) == 0 {
+            Some(GameResult::AllPiecesLost { winner })
+        } else if position.legal_moves().is_empty() {
+            Some(GameResult::NoMovesLeft { winner })
+        } else {
+            None
+        }
+    }
+
+    fn insufficient_material(&self, _game: &GameState) -> bool {
+        false
+    }
+
+    fn can_win(&self, _game: &GameState, _color: Color) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub fn rules(variant: Variant) -> &'static dyn Rules {
+    match variant {
+        Variant::Standard | Variant::Chess960 { .. } => &Standard,
+        Variant::KingOfTheHill => &KingOfTheHill,
+        Variant::ThreeCheck => &ThreeCheck,
+        Variant::RacingKings => &RacingKings,
+        Variant::Crazyhouse => &Crazyhouse,
+        Variant::Atomic => &Atomic,
+        Variant::Antichess => &Antichess,
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn is_centre(square: Square) -> bool {
+    (3..=4).contains(&square.get_file().to_index())
+        && (3..=4).contains(&square.get_rank().to_index())
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) fn player_color(color: Color) -> PlayerColor {
+    match color {
+        Color::White => PlayerColor::White,
+        Color::Black => PlayerColor::Black,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::{CheckCount, RoomConfig, ServerMessage};
+
+    fn game(variant: Variant, start_fen: Option<&str>) -> GameState {
+        GameState::new(&RoomConfig {
+            allow_takebacks: false,
+            time_control: None,
+            start_fen: start_fen.map(str::to_string),
+            variant,
+        })
+        .unwrap()
+    }
+
+    fn play(game: &mut GameState, moves: &[(&str, &str)]) {
+        for (from, to) in moves {
+            game.make_move(from, to, None).unwrap();
+        }
+    }
+
+    fn sent_checks(game: &GameState) -> Option<CheckCount> {
+        match game.state_message() {
+            ServerMessage::GameState { checks, .. } => checks,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn king_of_the_hill_ends_when_a_king_reaches_the_centre() {
+        let mut game = game(
+            Variant::KingOfTheHill,
+            Some("4k3/8/8/8/8/4K3/8/8 w - - 0 1"),
+        );
+        play(&mut game, &[("e3", "e4")]);
+        assert!(game.game_over);
+        assert!(matches!(
+            game.result,
+            Some(GameResult::KingOfTheHill {
+                winner: PlayerColor::White
+            })
+        ));
+    }
+
+    #[test]
+    fn king_of_the_hill_keeps_bare_kings_playing() {
+        let mut game = game(
+            Variant::KingOfTheHill,
+            Some("4k3/8/8/8/8/8/8/4K3 w - - 0 1"),
+        );
+        play(&mut game, &[("e1", "e2")]);
+        assert!(!game.game_over);
+    }
+
+    #[test]
+    fn three_check_ends_on_the_third_check() {
+        let mut game = game(Variant::ThreeCheck, Some("7k/8/8/8/8/8/8/R3K3 w - - 0 1"));
+        play(&mut game, &[("a1", "a8"), ("h8", "h7"), ("a8", "a7")]);
+        assert!(!game.game_over);
+        assert_eq!(sent_checks(&game), Some(CheckCount { white: 2, black: 0 }));
+
+        play(&mut game, &[("h7", "g6"), ("a7", "a6")]);
+        assert!(matches!(
+            game.result,
+            Some(GameResult::ThreeCheck {
+                winner: PlayerColor::White
+            })
+        ));
+        assert_eq!(sent_checks(&game), Some(CheckCount { white: 3, black: 0 }));
+    }
+
+    #[test]
+    fn check_counter_is_only_sent_for_three_check() {
+        assert_eq!(sent_checks(&game(Variant::Standard, None)), None);
+    }
+
+    fn race(fen: &str) -> GameState {
+        let mut game = game(Variant::RacingKings, None);
+        game.board = Board::from_str(fen).unwrap();
+        game
+    }
+
+    #[test]
+    fn racing_kings_forbids_giving_check() {
+        let mut game = game(Variant::RacingKings, None);
+        // Nc3 would check the black king on a2
+        assert!(game.make_move("e2", "c3", None).is_err());
+        assert!(game.make_move("h2", "h3", None).is_ok());
+    }
+
+    #[test]
+    fn racing_kings_is_won_by_reaching_the_last_rank() {
+        let mut game = race("8/6K1/8/8/8/8/k7/8 w - - 0 1");
+        play(&mut game, &[("g7", "g8")]);
+        assert!(matches!(
+            game.result,
+            Some(GameResult::RaceWon {
+                winner: PlayerColor::White
+            })
+        ));
+
+        let mut game = race("8/1k6/8/8/8/8/6K1/8 b - - 0 1");
+        play(&mut game, &[("b7", "b8")]);
+        assert!(matches!(
+            game.result,
+            Some(GameResult::RaceWon {
+                winner: PlayerColor::Black
+            })
+        ));
+    }
+
+    #[test]
+    fn racing_kings_gives_black_one_move_to_draw() {
+        let mut game = race("8/1k4K1/8/8/8/8/8/8 w - - 0 1");
+        play(&mut game, &[("g7", "g8")]);
+        assert!(!game.game_over);
+        play(&mut game, &[("b7", "b8")]);
+        assert!(matches!(game.result, Some(GameResult::RaceDrawn)));
+
+        let mut game = race("8/1k4K1/8/8/8/8/8/8 w - - 0 1");
+        play(&mut game, &[("g7", "g8"), ("b7", "b6")]);
+        assert!(matches!(
+            game.result,
+            Some(GameResult::RaceWon {
+                winner: PlayerColor::White
+            })
+        ));
+    }
+
+    #[test]
+    fn antichess_is_won_by_running_out_of_pieces_or_moves() {
+        let mut taken = game(Variant::Antichess, Some("8/8/8/8/8/8/p7/1R6 b - - 0 1"));
+        taken.make_move("a2", "b1", Some("q")).unwrap();
+        assert!(matches!(
+            taken.result,
+            Some(GameResult::AllPiecesLost {
+                winner: PlayerColor::White
+            })
+        ));
+
+        let mut blocked = game(Variant::Antichess, Some("8/8/8/8/p7/8/P7/8 w - - 0 1"));
+        play(&mut blocked, &[("a2", "a3")]);
+        assert!(matches!(
+            blocked.result,
+            Some(GameResult::NoMovesLeft {
+                winner: PlayerColor::Black
+            })
+        ));
+    }
+}